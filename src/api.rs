@@ -0,0 +1,470 @@
+//! Read-only HTTP API exposing the latest results of jobs.
+//!
+//! The results of a job are served as JSON at `/jobs/{name}/latest`,
+//! with the name percent-encoded since names of jobs default to URLs of their resources,
+//! and the recent numbers of each of its [tracked](crate::job::Job::track) targets at `/jobs/{name}/series/{target}`.
+//! Numbers of changes found by the polls of jobs are served as Prometheus metrics at `/metrics`,
+//! while [events](PollEvent) of the polls are streamed as Server-Sent Events at `/events`.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, VecDeque},
+    convert::Infallible,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use http_body_util::{combinators::BoxBody, BodyExt, Full, StreamBody};
+use hyper::{
+    body::{Bytes, Frame, Incoming},
+    header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use serde_json::json;
+use tokio::{
+    net::TcpListener,
+    sync::broadcast::{self, error::RecvError},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    diff::Change,
+    series::{Point, Series},
+};
+
+/// Number of events kept for each client of `/events` until it receives them.
+const EVENTS_CAPACITY: usize = 256;
+
+/// Number of the most recent points kept in each series of a tracked target.
+const SERIES_CAPACITY: usize = 1000;
+
+/// Results of the last poll of each job by its name, shared by the jobs and the API.
+#[derive(Debug)]
+pub struct LatestResults {
+    results: RwLock<HashMap<String, serde_json::Value>>,
+    /// Numbers of changes of the jobs detecting them, ordered by name to keep metrics stable
+    changes: RwLock<BTreeMap<String, ChangeCount>>,
+    /// Events of the polls broadcast to the clients of `/events`
+    events: broadcast::Sender<PollEvent>,
+    /// Recent numbers of the tracked targets by the names of their jobs and targets, oldest first
+    series: RwLock<HashMap<(String, String), VecDeque<Point>>>,
+}
+
+impl Default for LatestResults {
+    fn default() -> Self {
+        Self {
+            results: RwLock::default(),
+            changes: RwLock::default(),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            series: RwLock::default(),
+        }
+    }
+}
+
+/// Event of a poll of a job, serialized with its name under `event`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PollEvent {
+    PollStarted {
+        job: String,
+    },
+    PollCompleted {
+        job: String,
+        pages: usize,
+        succeeded: bool,
+    },
+    Changed {
+        job: String,
+        changes: Vec<Change>,
+    },
+}
+
+impl PollEvent {
+    /// Name of the event, which is also the type of its Server-Sent Event.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::PollStarted { .. } => "poll_started",
+            Self::PollCompleted { .. } => "poll_completed",
+            Self::Changed { .. } => "changed",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ChangeCount {
+    /// Number of changes found by the last poll
+    last: usize,
+    /// Number of changes found by all polls
+    total: u64,
+}
+
+impl LatestResults {
+    pub fn update(&self, job: &str, result: serde_json::Value) {
+        self.results
+            .write()
+            .expect("latest results should not be poisoned")
+            .insert(job.to_owned(), result);
+    }
+
+    pub fn get(&self, job: &str) -> Option<serde_json::Value> {
+        self.results
+            .read()
+            .expect("latest results should not be poisoned")
+            .get(job)
+            .cloned()
+    }
+
+    /// Remembers the number of changes found by the poll of the job since the previous one.
+    pub fn count_changes(&self, job: &str, changes: usize) {
+        let mut counts = self
+            .changes
+            .write()
+            .expect("change counts should not be poisoned");
+        let count = counts.entry(job.to_owned()).or_default();
+        count.last = changes;
+        count.total += changes as u64;
+    }
+
+    /// Appends the number of the tracked target of the job to its series, dropping the oldest points if it is full.
+    pub fn track(&self, job: &str, target: &str, time: DateTime<Utc>, value: f64) {
+        let mut series = self.series.write().expect("series should not be poisoned");
+        let points = series
+            .entry((job.to_owned(), target.to_owned()))
+            .or_default();
+        if points.len() == SERIES_CAPACITY {
+            points.pop_front();
+        }
+        points.push_back(Point { time, value });
+    }
+
+    /// Series of all tracked targets of the job.
+    pub fn job_series(&self, job: &str) -> Series {
+        self.series
+            .read()
+            .expect("series should not be poisoned")
+            .iter()
+            .filter(|((series_job, _), _)| series_job == job)
+            .map(|((_, target), points)| (target.clone(), points.iter().copied().collect()))
+            .collect()
+    }
+
+    /// Replaces the series of the tracked targets of the job, keeping only their most recent points.
+    pub fn restore_series(&self, job: &str, job_series: Series) {
+        let mut series = self.series.write().expect("series should not be poisoned");
+        for (target, points) in job_series {
+            let skipped = points.len().saturating_sub(SERIES_CAPACITY);
+            series.insert(
+                (job.to_owned(), target),
+                points.into_iter().skip(skipped).collect(),
+            );
+        }
+    }
+
+    fn series(&self, job: &str, target: &str) -> Option<Vec<Point>> {
+        self.series
+            .read()
+            .expect("series should not be poisoned")
+            .get(&(job.to_owned(), target.to_owned()))
+            .map(|points| points.iter().copied().collect())
+    }
+
+    /// Broadcasts the event to the clients of `/events`, creating it only if there are any.
+    pub fn publish(&self, event: impl FnOnce() -> PollEvent) {
+        if self.events.receiver_count() > 0 {
+            // Clients may disconnect in the meantime, which leaves nobody to receive the event.
+            let _ = self.events.send(event());
+        }
+    }
+
+    /// Formats the numbers of changes in the Prometheus text format.
+    fn metrics(&self) -> String {
+        let counts = self
+            .changes
+            .read()
+            .expect("change counts should not be poisoned");
+        let mut metrics = String::from(
+            "# HELP wetcher_changes Number of changes found by the last poll of the job.\n\
+             # TYPE wetcher_changes gauge\n",
+        );
+        for (job, count) in counts.iter() {
+            metrics.push_str(&format!(
+                "wetcher_changes{{job=\"{}\"}} {}\n",
+                label(job),
+                count.last
+            ));
+        }
+        metrics.push_str(
+            "# HELP wetcher_changes_total Number of changes found by all polls of the job.\n\
+             # TYPE wetcher_changes_total counter\n",
+        );
+        for (job, count) in counts.iter() {
+            metrics.push_str(&format!(
+                "wetcher_changes_total{{job=\"{}\"}} {}\n",
+                label(job),
+                count.total
+            ));
+        }
+        metrics
+    }
+}
+
+/// Serves the API on the listener until the task is aborted.
+pub async fn serve(listener: TcpListener, results: Arc<LatestResults>) {
+    match listener.local_addr() {
+        Ok(address) => info!("Serving API at http://{address}"),
+        Err(error) => error!("Failed to get address of the API: {error}"),
+    }
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(error) => {
+                error!("Failed to accept API connection: {error}");
+                continue;
+            }
+        };
+        let results = results.clone();
+        tokio::spawn(async move {
+            let service = service_fn(|request| {
+                let response = respond(&results, &request);
+                async move { Ok::<_, Infallible>(response) }
+            });
+            if let Err(error) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!(%peer, "API connection failed: {error}");
+            }
+        });
+    }
+}
+
+/// Body of responses, which is streamed for [events](PollEvent).
+type Body = BoxBody<Bytes, Infallible>;
+
+/// Resource of the API requested by the path, with percent-decoded names.
+enum Route<'path> {
+    Latest(Cow<'path, str>),
+    Series(Cow<'path, str>, Cow<'path, str>),
+    Metrics,
+    Events,
+}
+
+impl<'path> Route<'path> {
+    fn parse(path: &'path str) -> Option<Self> {
+        let decode = |name| percent_decode_str(name).decode_utf8_lossy();
+        if let Some(path) = path.strip_prefix("/jobs/") {
+            if let Some(job) = path.strip_suffix("/latest") {
+                return Some(Self::Latest(decode(job)));
+            }
+            let (job, target) = path.split_once("/series/")?;
+            return Some(Self::Series(decode(job), decode(target)));
+        }
+        match path {
+            "/metrics" => Some(Self::Metrics),
+            "/events" => Some(Self::Events),
+            _ => None,
+        }
+    }
+}
+
+fn respond(results: &LatestResults, request: &Request<Incoming>) -> Response<Body> {
+    let Some(route) = Route::parse(request.uri().path()) else {
+        return json_response(StatusCode::NOT_FOUND, &json!({ "error": "not found" }));
+    };
+    if request.method() != Method::GET {
+        return json_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            &json!({ "error": "only GET is allowed" }),
+        );
+    }
+    match route {
+        Route::Latest(job) => match results.get(&job) {
+            Some(result) => json_response(StatusCode::OK, &result),
+            None => json_response(
+                StatusCode::NOT_FOUND,
+                &json!({ "error": format!("no results of job {job:?}") }),
+            ),
+        },
+        Route::Series(job, target) => match results.series(&job, &target) {
+            Some(points) => json_response(StatusCode::OK, &json!(points)),
+            None => json_response(
+                StatusCode::NOT_FOUND,
+                &json!({ "error": format!("no series of target {target:?} of job {job:?}") }),
+            ),
+        },
+        Route::Metrics => {
+            let mut response = Response::new(Full::new(Bytes::from(results.metrics())).boxed());
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; version=0.0.4"),
+            );
+            response
+        }
+        Route::Events => event_stream(results.events.subscribe()),
+    }
+}
+
+/// Streams the events as Server-Sent Events until the client disconnects.
+///
+/// Events missed by a client which does not keep up are skipped with a warning.
+fn event_stream(receiver: broadcast::Receiver<PollEvent>) -> Response<Body> {
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data =
+                        serde_json::to_string(&event).expect("events should be serializable");
+                    let frame = Frame::data(Bytes::from(format!(
+                        "event: {}\ndata: {data}\n\n",
+                        event.name()
+                    )));
+                    return Some((Ok(frame), receiver));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Events client is lagging behind, skipped {skipped} events");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let mut response = Response::new(StreamBody::new(events).boxed());
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+/// Escapes the value of a label of Prometheus metrics.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn json_response(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Full::new(Bytes::from(body.to_string())).boxed());
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_latest_results_are_served_by_job_name() {
+        let results = Arc::new(LatestResults::default());
+        results.update("https://shop.example/tees", json!({ "price": [19.0] }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, results.clone()));
+
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("http://{address}{path}")).send();
+        let response = get("/jobs/https%3A%2F%2Fshop.example%2Ftees/latest")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            json!({ "price": [19.0] })
+        );
+
+        results.update("https://shop.example/tees", json!({ "price": [17.0] }));
+        let response = get("/jobs/https%3A%2F%2Fshop.example%2Ftees/latest")
+            .await
+            .unwrap();
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            json!({ "price": [17.0] })
+        );
+
+        for path in ["/jobs/hoodies/latest", "/jobs", "/"] {
+            assert_eq!(get(path).await.unwrap().status(), StatusCode::NOT_FOUND);
+        }
+        let response = client
+            .delete(format!("http://{address}/jobs/hoodies/latest"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_series_of_tracked_targets_are_served_in_order() {
+        let results = Arc::new(LatestResults::default());
+        let start = DateTime::parse_from_rfc3339("2026-10-15T09:00:00Z")
+            .unwrap()
+            .to_utc();
+        for (minutes, price) in [(0, 19.0), (5, 17.5), (10, 18.0)] {
+            let time = start + chrono::Duration::minutes(minutes);
+            results.track("tees", "price", time, price);
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, results.clone()));
+
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("http://{address}{path}")).send();
+        let response = get("/jobs/tees/series/price").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            json!([
+                { "time": "2026-10-15T09:00:00Z", "value": 19.0 },
+                { "time": "2026-10-15T09:05:00Z", "value": 17.5 },
+                { "time": "2026-10-15T09:10:00Z", "value": 18.0 },
+            ])
+        );
+        for path in ["/jobs/tees/series/stock", "/jobs/hoodies/series/price"] {
+            assert_eq!(get(path).await.unwrap().status(), StatusCode::NOT_FOUND);
+        }
+
+        for _ in 0..SERIES_CAPACITY {
+            results.track("tees", "price", start, 0.0);
+        }
+        let series = results.series("tees", "price").unwrap();
+        assert_eq!(series.len(), SERIES_CAPACITY);
+        assert!(series.iter().all(|point| point.value == 0.0));
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_change_counts_are_served_as_metrics() {
+        let results = Arc::new(LatestResults::default());
+        results.count_changes("tees", 2);
+        results.count_changes("tees", 0);
+        results.count_changes("\"hoodies\"", 3);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, results));
+
+        let response = reqwest::get(format!("http://{address}/metrics"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "# HELP wetcher_changes Number of changes found by the last poll of the job.\n\
+             # TYPE wetcher_changes gauge\n\
+             wetcher_changes{job=\"\\\"hoodies\\\"\"} 3\n\
+             wetcher_changes{job=\"tees\"} 0\n\
+             # HELP wetcher_changes_total Number of changes found by all polls of the job.\n\
+             # TYPE wetcher_changes_total counter\n\
+             wetcher_changes_total{job=\"\\\"hoodies\\\"\"} 3\n\
+             wetcher_changes_total{job=\"tees\"} 2\n"
+        );
+        server.abort();
+    }
+}
@@ -1,12 +1,75 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(version, about, author, long_about = None)]
 pub struct CmdArgs {
-    #[arg(short, long, default_value = "./config")]
+    #[arg(short, long, default_value = "./config", global = true)]
     pub config: PathBuf,
+    /// Format of the logs written to the standard output
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "WETCHER_LOG_FORMAT",
+        global = true
+    )]
+    pub log_format: LogFormat,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Check the config without performing any requests, failing if it has problems
+    Validate,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON object per line, with the fields of events and spans kept structured
+    Json,
+}
+
+/// Arguments of running the jobs, which is done if no command is given.
+#[derive(Args)]
+pub struct RunArgs {
+    /// Record every HTTP response into the directory
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Replay HTTP responses recorded into the directory instead of performing requests
+    #[arg(long, value_name = "DIR")]
+    pub replay: Option<PathBuf>,
+    /// Poll every resource once and exit
+    #[arg(long)]
+    pub once: bool,
+    /// Which polls make the process exit with a failure once it finishes
+    #[arg(long, value_enum, default_value_t)]
+    pub fail_on: FailOn,
+    /// Run only the jobs having any of the tags, all jobs are run if none is given
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+    /// Serve the latest results of jobs over HTTP at the address
+    #[arg(long, value_name = "ADDR", conflicts_with = "once")]
+    pub api_addr: Option<SocketAddr>,
+}
+
+/// Condition on the polls of all jobs run by the process for it to exit with a failure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FailOn {
+    /// Any poll of any job has failed
+    #[default]
+    AnyFailure,
+    /// Some job has been polled but never successfully
+    NeverSucceeded,
+    /// The process never fails because of the polls
+    Never,
 }
 
 #[cfg(test)]
@@ -18,4 +81,38 @@ mod tests {
         use clap::CommandFactory;
         CmdArgs::command().debug_assert();
     }
+
+    #[test]
+    fn test_validate_command_accepts_config() {
+        for args in [
+            ["wetcher", "validate", "-c", "shop.json5"],
+            ["wetcher", "-c", "shop.json5", "validate"],
+        ] {
+            let CmdArgs {
+                config, command, ..
+            } = CmdArgs::try_parse_from(args).unwrap();
+            assert_eq!(config, PathBuf::from("shop.json5"));
+            assert!(matches!(command, Some(Command::Validate)));
+        }
+    }
+
+    #[test]
+    fn test_tags_are_repeatable() {
+        let CmdArgs { run, .. } = CmdArgs::try_parse_from(["wetcher"]).unwrap();
+        assert!(run.tags.is_empty());
+        let CmdArgs { run, .. } =
+            CmdArgs::try_parse_from(["wetcher", "--once", "--tag", "prices", "--tag", "stock"])
+                .unwrap();
+        assert_eq!(run.tags, ["prices", "stock"]);
+    }
+
+    #[test]
+    fn test_log_format_is_parsed() {
+        let CmdArgs { log_format, .. } = CmdArgs::try_parse_from(["wetcher"]).unwrap();
+        assert_eq!(log_format, LogFormat::Text);
+        let CmdArgs { log_format, .. } =
+            CmdArgs::try_parse_from(["wetcher", "validate", "--log-format", "json"]).unwrap();
+        assert_eq!(log_format, LogFormat::Json);
+        assert!(CmdArgs::try_parse_from(["wetcher", "--log-format", "xml"]).is_err());
+    }
 }
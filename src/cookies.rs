@@ -0,0 +1,68 @@
+//! Cookies kept between requests of a job and optionally persisted between runs.
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
+use tokio::fs;
+use tracing::debug;
+
+use crate::job;
+
+/// Cookie jar of a job.
+#[derive(Debug)]
+pub struct CookieJar {
+    store: Arc<CookieStoreMutex>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CookieJarError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid cookie jar: {0}")]
+    Format(cookie_store::Error),
+}
+
+impl CookieJar {
+    /// Creates the jar of the session, loading the cookies persisted to its file if it exists.
+    pub fn load(session: &job::Session) -> Result<Self, CookieJarError> {
+        let store = match &session.path {
+            Some(path) if path.exists() => {
+                debug!("Loading cookies from {}", path.display());
+                cookie_store::serde::json::load_all(BufReader::new(File::open(path)?))
+                    .map_err(CookieJarError::Format)?
+            }
+            _ => CookieStore::default(),
+        };
+        Ok(Self {
+            store: Arc::new(CookieStoreMutex::new(store)),
+            path: session.path.clone(),
+        })
+    }
+
+    /// Store which should be used as the cookie provider of the client.
+    pub fn store(&self) -> Arc<CookieStoreMutex> {
+        self.store.clone()
+    }
+
+    /// Persists the cookies, including the session ones, to the file of the session if it has one.
+    pub async fn save(&self) -> Result<(), CookieJarError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut contents = Vec::new();
+        cookie_store::serde::json::save_incl_expired_and_nonpersistent(
+            &self.store.lock().expect("lock should not be poisoned"),
+            &mut contents,
+        )
+        .map_err(CookieJarError::Format)?;
+        fs::write(path, contents).await?;
+        debug!("Saved cookies to {}", path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+//! Merging of the results of the pages visited during a single poll,
+//! dropping the records which have already been found on the previous pages.
+
+use std::{borrow::Cow, collections::HashSet};
+
+use indexmap::IndexMap;
+use tracing::{debug, warn};
+
+use crate::{ProcessingResult, HEADERS_KEY};
+
+/// Number of remembered records after which the growth of the set is reported.
+const LARGE_SEEN_SET: usize = 10_000;
+
+/// Results of the pages accumulated until all of them are visited.
+///
+/// Groups whose records all have the key target are deduplicated by its value,
+/// keeping the first record; other results are merged by their names.
+#[derive(Debug)]
+pub struct Dedup {
+    key: String,
+    result: Option<ProcessingResult<'static>>,
+    /// Captured response headers of the first page, which are not deduplicated as records
+    headers: Option<ProcessingResult<'static>>,
+    /// Paths of the groups of the merged records paired with the values of their keys
+    seen: HashSet<(String, String)>,
+}
+
+impl Dedup {
+    pub fn new(key: String) -> Self {
+        Self {
+            key,
+            result: None,
+            headers: None,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Merges the results of the next page into the accumulated ones.
+    pub fn merge(&mut self, mut page: ProcessingResult<'static>) {
+        let Self {
+            key,
+            result,
+            headers,
+            seen,
+        } = self;
+        if let ProcessingResult::Group(group) = &mut page {
+            if let Some(page_headers) = group.shift_remove(HEADERS_KEY) {
+                headers.get_or_insert(page_headers);
+            }
+        }
+        let before = seen.len();
+        let result = result.get_or_insert_with(|| ProcessingResult::Group(IndexMap::new()));
+        merge(key, seen, "", result, page);
+        debug!("Remembered {} records to deduplicate", seen.len());
+        if before < LARGE_SEEN_SET && seen.len() >= LARGE_SEEN_SET {
+            warn!(
+                "Remembered {} records to deduplicate by {key:?}, which is unexpectedly many",
+                seen.len()
+            );
+        }
+    }
+
+    /// Gets the accumulated results unless no page has been merged.
+    pub fn into_result(self) -> Option<ProcessingResult<'static>> {
+        let mut result = self.result?;
+        if let (ProcessingResult::Group(group), Some(headers)) = (&mut result, self.headers) {
+            group.insert(Cow::Borrowed(HEADERS_KEY), headers);
+        }
+        Some(result)
+    }
+}
+
+fn merge(
+    key: &str,
+    seen: &mut HashSet<(String, String)>,
+    path: &str,
+    into: &mut ProcessingResult<'static>,
+    page: ProcessingResult<'static>,
+) {
+    let (ProcessingResult::Group(into), ProcessingResult::Group(page)) = (into, page) else {
+        // values and errors are kept as they have been found on the first page
+        return;
+    };
+
+    if is_records(key, &page) {
+        for (name, record) in page {
+            if let Some(value) = key_value(key, &record) {
+                if !seen.insert((path.to_owned(), value)) {
+                    debug!("Skipping duplicate record {name:?} of {path:?}");
+                    continue;
+                }
+            }
+            let name = if name.starts_with('[') || into.contains_key(&name) {
+                Cow::Owned(format!("[{}]", into.len()))
+            } else {
+                name
+            };
+            into.insert(name, record);
+        }
+        return;
+    }
+
+    for (name, result) in page {
+        let path = format!("{path}/{name}");
+        match (into.get_mut(&name), result) {
+            (Some(into), result) => merge(key, seen, &path, into, result),
+            (None, result @ ProcessingResult::Group(_)) => {
+                let mut group = ProcessingResult::Group(IndexMap::new());
+                merge(key, seen, &path, &mut group, result);
+                into.insert(name, group);
+            }
+            (None, result) => {
+                into.insert(name, result);
+            }
+        }
+    }
+}
+
+/// Checks if the group consists of records having the key target.
+pub fn is_records(key: &str, group: &IndexMap<Cow<str>, ProcessingResult>) -> bool {
+    !group.is_empty()
+        && group.values().all(
+            |record| matches!(record, ProcessingResult::Group(fields) if fields.contains_key(key)),
+        )
+}
+
+/// Serializes the value of the key target of the record unless it is missing.
+pub fn key_value(key: &str, record: &ProcessingResult) -> Option<String> {
+    let ProcessingResult::Group(fields) = record else {
+        return None;
+    };
+    let value = fields.get(key).filter(|value| !value.is_missing())?;
+    Some(serde_json::to_string(value).expect("results should be serializable"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::Value;
+
+    fn product(name: Value<'static>, price: &'static str) -> ProcessingResult<'static> {
+        ProcessingResult::Group(IndexMap::from([
+            (Cow::Borrowed("name"), ProcessingResult::Values(vec![name])),
+            (
+                Cow::Borrowed("price"),
+                ProcessingResult::Values(vec![Value::String(Cow::Borrowed(price))]),
+            ),
+        ]))
+    }
+
+    fn named(name: &'static str, price: &'static str) -> ProcessingResult<'static> {
+        product(Value::String(Cow::Borrowed(name)), price)
+    }
+
+    fn page(products: Vec<ProcessingResult<'static>>) -> ProcessingResult<'static> {
+        ProcessingResult::Group(IndexMap::from([(
+            Cow::Borrowed("[0]"),
+            ProcessingResult::Group(IndexMap::from([(
+                Cow::Borrowed("products"),
+                ProcessingResult::Group(
+                    products
+                        .into_iter()
+                        .enumerate()
+                        .map(|(id, product)| (Cow::Owned(format!("[{id}]")), product))
+                        .collect(),
+                ),
+            )])),
+        )]))
+    }
+
+    #[test]
+    fn test_records_are_deduplicated_across_pages() {
+        let mut dedup = Dedup::new("name".to_owned());
+        dedup.merge(page(vec![
+            named("Loom Hoodie", "49"),
+            named("Canvas Tee", "19"),
+        ]));
+        dedup.merge(page(vec![
+            named("Canvas Tee", "17"),
+            named("Wool Socks", "9"),
+        ]));
+        dedup.merge(page(vec![
+            named("Wool Socks", "9"),
+            named("Wool Socks", "9"),
+        ]));
+
+        assert_eq!(
+            dedup.into_result().unwrap().to_json(),
+            serde_json::json!({
+                "[0]": {
+                    "products": {
+                        "[0]": { "name": ["Loom Hoodie"], "price": ["49"] },
+                        "[1]": { "name": ["Canvas Tee"], "price": ["19"] },
+                        "[2]": { "name": ["Wool Socks"], "price": ["9"] },
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_captured_headers_are_not_deduplicated_as_records() {
+        let page = |product, cache: &'static str| {
+            ProcessingResult::Group(IndexMap::from([
+                (Cow::Borrowed("[0]"), product),
+                (
+                    Cow::Borrowed(HEADERS_KEY),
+                    ProcessingResult::Group(IndexMap::from([(
+                        Cow::Borrowed("cache-control"),
+                        ProcessingResult::Values(vec![Value::String(Cow::Borrowed(cache))]),
+                    )])),
+                ),
+            ]))
+        };
+        let mut dedup = Dedup::new("name".to_owned());
+        dedup.merge(page(named("Loom Hoodie", "49"), "max-age=600"));
+        dedup.merge(page(named("Canvas Tee", "19"), "no-cache"));
+
+        assert_eq!(
+            dedup.into_result().unwrap().to_json(),
+            serde_json::json!({
+                "[0]": { "name": ["Loom Hoodie"], "price": ["49"] },
+                "[1]": { "name": ["Canvas Tee"], "price": ["19"] },
+                "_headers": { "cache-control": ["max-age=600"] },
+            })
+        );
+    }
+
+    #[test]
+    fn test_records_with_missing_key_are_kept() {
+        let mut dedup = Dedup::new("name".to_owned());
+        dedup.merge(page(vec![
+            named("Canvas Tee", "19"),
+            product(Value::Unknown, "9"),
+        ]));
+        dedup.merge(page(vec![product(Value::Unknown, "9")]));
+
+        assert_eq!(
+            dedup.into_result().unwrap().to_json(),
+            serde_json::json!({
+                "[0]": {
+                    "products": {
+                        "[0]": { "name": ["Canvas Tee"], "price": ["19"] },
+                        "[1]": { "name": [null], "price": ["9"] },
+                        "[2]": { "name": [null], "price": ["9"] },
+                    },
+                },
+            })
+        );
+    }
+}
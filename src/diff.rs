@@ -0,0 +1,136 @@
+//! Structural comparison of extracted values.
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// A single difference between two JSON documents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {path}: {value}"),
+            Change::Removed { path, value } => write!(f, "- {path}: {value}"),
+            Change::Changed { path, old, new } => write!(f, "~ {path}: {old} -> {new}"),
+        }
+    }
+}
+
+/// Computes the changes turning `old` into `new`.
+///
+/// Paths of the changes are [JSON pointers](https://datatracker.ietf.org/doc/html/rfc6901),
+/// objects are compared key by key regardless of the order of keys
+/// and arrays are compared element by element.
+pub fn json(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    json_into(&mut String::new(), old, new, &mut changes);
+    changes
+}
+
+fn json_into(path: &mut String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old) in old {
+                with_segment(path, key, |path| match new.get(key) {
+                    Some(new) => json_into(path, old, new, changes),
+                    None => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old.clone(),
+                    }),
+                });
+            }
+            for (key, new) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                with_segment(path, key, |path| {
+                    changes.push(Change::Added {
+                        path: path.clone(),
+                        value: new.clone(),
+                    })
+                });
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                with_segment(path, &index.to_string(), |path| {
+                    match (old.get(index), new.get(index)) {
+                        (Some(old), Some(new)) => json_into(path, old, new, changes),
+                        (Some(old), None) => changes.push(Change::Removed {
+                            path: path.clone(),
+                            value: old.clone(),
+                        }),
+                        (None, Some(new)) => changes.push(Change::Added {
+                            path: path.clone(),
+                            value: new.clone(),
+                        }),
+                        (None, None) => unreachable!("index is within one of the arrays"),
+                    }
+                });
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Runs `f` with `segment` temporarily appended to the JSON pointer `path`.
+fn with_segment(path: &mut String, segment: &str, f: impl FnOnce(&mut String)) {
+    let length = path.len();
+    path.push('/');
+    path.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    f(path);
+    path.truncate(length);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json_diff_reports_changed_keys() {
+        let old = json!({ "name": "Loom Hoodie", "price": 42, "tags": ["warm"], "sku": "L-1" });
+        let new = json!({ "name": "Loom Hoodie", "price": 39, "tags": ["warm", "sale"] });
+
+        assert_eq!(
+            json(&old, &new),
+            [
+                Change::Changed {
+                    path: "/price".to_owned(),
+                    old: json!(42),
+                    new: json!(39),
+                },
+                Change::Added {
+                    path: "/tags/1".to_owned(),
+                    value: json!("sale"),
+                },
+                Change::Removed {
+                    path: "/sku".to_owned(),
+                    value: json!("L-1"),
+                },
+            ]
+        );
+        assert_eq!(json(&old, &old), []);
+    }
+}
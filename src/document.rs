@@ -0,0 +1,77 @@
+//! Documents which targets are processed against, abstracting over their formats.
+
+use std::{borrow::Cow, fmt::Debug};
+
+use skyscraper::xpath::{
+    self, grammar::data_model::XpathItem, ExpressionApplyError, XpathItemTree,
+};
+use url::Url;
+
+use crate::{
+    job::{self, Selector, Value, ValueExtractor},
+    seo, ProcessingResult,
+};
+
+/// Failure to select items of a document.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum SelectError {
+    #[error(transparent)]
+    XPath(#[from] ExpressionApplyError),
+    #[error("invalid XPath: {0}")]
+    InvalidXPath(String),
+    #[error("{0}")]
+    Unsupported(&'static str),
+}
+
+/// Parsed document whose items are selected by [selectors](Selector).
+pub trait Document<'doc>: Copy {
+    type Item: Clone + Debug;
+
+    /// Items which the root targets are applied to.
+    fn roots(self) -> Result<Vec<Self::Item>, SelectError>;
+
+    /// Selects items relative to the given one.
+    fn select(self, selector: &Selector, item: Self::Item) -> Result<Vec<Self::Item>, SelectError>;
+
+    /// Gets the whole text content of the item.
+    fn text(self, item: &Self::Item) -> Option<Cow<'doc, str>>;
+
+    /// Extracts a value from each of the items.
+    fn extract(self, extractor: &ValueExtractor, items: Vec<Self::Item>) -> Vec<Value<'doc>>;
+
+    /// Collects SEO metadata of the items.
+    fn seo(self, items: Vec<Self::Item>, base: Option<&Url>) -> ProcessingResult<'doc>;
+
+    /// Computes a path uniquely addressing the item in the document.
+    fn path(self, item: &Self::Item) -> Option<String>;
+}
+
+impl<'tree> Document<'tree> for &'tree XpathItemTree {
+    type Item = XpathItem<'tree>;
+
+    fn roots(self) -> Result<Vec<Self::Item>, SelectError> {
+        let root =
+            xpath::parse("//").map_err(|error| SelectError::InvalidXPath(error.to_string()))?;
+        Ok(root.apply(self)?.into_iter().collect())
+    }
+
+    fn select(self, selector: &Selector, item: Self::Item) -> Result<Vec<Self::Item>, SelectError> {
+        Ok(selector.apply(self, item)?.into_iter().collect())
+    }
+
+    fn text(self, item: &Self::Item) -> Option<Cow<'tree, str>> {
+        job::item_text(self, item)
+    }
+
+    fn extract(self, extractor: &ValueExtractor, items: Vec<Self::Item>) -> Vec<Value<'tree>> {
+        extractor.extract(self, items.into_iter().collect())
+    }
+
+    fn seo(self, items: Vec<Self::Item>, base: Option<&Url>) -> ProcessingResult<'tree> {
+        seo::extract(self, items.into_iter().collect(), base)
+    }
+
+    fn path(self, item: &Self::Item) -> Option<String> {
+        crate::absolute_path(self, item)
+    }
+}
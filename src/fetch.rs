@@ -0,0 +1,1391 @@
+//! Execution of HTTP requests, optionally recording or replaying responses.
+
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    redirect, Body, Method, Request, Response, StatusCode,
+};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{fs, sync::Semaphore};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::{
+    api::LatestResults,
+    cookies::{CookieJar, CookieJarError},
+    job::{Auth, Body as JobBody, Job, Proxy, Redirects, ResponseCache, UserAgent, Validators},
+    quota::{DomainRates, Quota, RequestBudget},
+    robots::{self, RobotsCache},
+    HandleError,
+};
+
+/// Directory with recorded HTTP responses.
+#[derive(Debug, Clone)]
+pub enum Cassette {
+    /// Responses received from servers are recorded into the directory.
+    Record(PathBuf),
+    /// Responses are replayed from the directory without performing any requests.
+    Replay(PathBuf),
+}
+
+/// Maximal size of a response body in bytes unless another one is configured.
+pub const DEFAULT_MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// User agent sent with requests unless another one is configured.
+pub const DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Creates the client which should be used to perform requests of jobs,
+/// sending the user agent unless it is overridden by the job.
+///
+/// Requests are routed through the proxy if it is given and are sent directly otherwise.
+/// Redirects are followed as configured, or by [default](Redirects::default) if not.
+/// Cookies are only kept if the store is given.
+pub fn client(
+    user_agent: Option<&UserAgent>,
+    proxy: Option<&Proxy>,
+    redirects: Option<&Redirects>,
+    cookies: Option<Arc<CookieStoreMutex>>,
+) -> reqwest::Result<reqwest::Client> {
+    let user_agent = user_agent
+        .map_or(HeaderValue::from_static(DEFAULT_USER_AGENT), |user_agent| {
+            user_agent.0.clone()
+        });
+    let mut builder = reqwest::Client::builder().user_agent(user_agent);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.to_reqwest()?);
+    }
+    if let Some(cookies) = cookies {
+        builder = builder.cookie_provider(cookies);
+    }
+    builder
+        .redirect(redirect_policy(redirects.copied().unwrap_or_default()))
+        .build()
+}
+
+/// Follows the redirects, failing on loops and on exceeding the maximal number of them.
+///
+/// Redirects which should not be followed are not followed at all, so that their responses are received.
+fn redirect_policy(redirects: Redirects) -> redirect::Policy {
+    if redirects.max == 0 {
+        return redirect::Policy::none();
+    }
+    redirect::Policy::custom(move |attempt| {
+        let previous = attempt.previous();
+        if !redirects.cross_host
+            && previous.first().and_then(Url::host_str) != attempt.url().host_str()
+        {
+            return attempt.stop();
+        }
+        let looped = previous.contains(attempt.url());
+        let exhausted = previous.len() >= redirects.max;
+        if !looped && !exhausted {
+            return attempt.follow();
+        }
+
+        let chain = RedirectChain(previous.iter().chain([attempt.url()]).cloned().collect());
+        attempt.error(if looped {
+            RedirectError::Loop(chain)
+        } else {
+            RedirectError::TooMany(redirects.max, chain)
+        })
+    })
+}
+
+/// URLs visited while following redirects, ending with the one which was not followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectChain(pub Vec<Url>);
+
+impl fmt::Display for RedirectChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, url) in self.0.iter().enumerate() {
+            if index != 0 {
+                f.write_str(" -> ")?;
+            }
+            write!(f, "{url}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reason why redirects of a request were not followed to the end.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum RedirectError {
+    #[error("redirect loop: {0}")]
+    Loop(RedirectChain),
+    #[error("more than {0} redirects: {1}")]
+    TooMany(usize, RedirectChain),
+}
+
+/// Performs HTTP requests on behalf of jobs.
+#[derive(Debug, Clone)]
+pub struct Fetcher {
+    client: reqwest::Client,
+    /// Client passed to [`Fetcher::new`], which is kept by the fetchers of jobs having their own clients
+    shared_client: reqwest::Client,
+    cassette: Option<Cassette>,
+    budget: Option<Arc<RequestBudget>>,
+    concurrency: Option<ConcurrencyLimit>,
+    robots: Arc<RobotsCache>,
+    domain_rates: Arc<DomainRates>,
+    domain_rate: Option<Quota>,
+    user_agent: Option<UserAgent>,
+    proxy: Option<Proxy>,
+    cookies: Option<Arc<CookieJar>>,
+    max_body_bytes: u64,
+    cache: Option<ResponseCache>,
+    encoding: Option<&'static Encoding>,
+    /// Latest results of the jobs of this fetcher and all of its clones, which are joined and served by the [API](crate::api)
+    latest_results: Arc<LatestResults>,
+}
+
+/// An error which may occur while creating the [fetcher of a job](Fetcher::for_job).
+#[derive(Debug, thiserror::Error)]
+pub enum JobFetcherError {
+    #[error(transparent)]
+    Client(#[from] reqwest::Error),
+    #[error("failed to load cookies: {0}")]
+    Cookies(#[from] CookieJarError),
+}
+
+/// Semaphore with the number of permits it was created with.
+#[derive(Debug, Clone)]
+struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+}
+
+/// HTTP response with fully received body.
+#[derive(Debug)]
+pub struct Fetched {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl Fetcher {
+    pub fn new(client: reqwest::Client, cassette: Option<Cassette>) -> Self {
+        Self {
+            shared_client: client.clone(),
+            client,
+            cassette,
+            budget: None,
+            concurrency: None,
+            robots: Arc::default(),
+            domain_rates: Arc::default(),
+            domain_rate: None,
+            user_agent: None,
+            proxy: None,
+            cookies: None,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            cache: None,
+            encoding: None,
+            latest_results: Arc::default(),
+        }
+    }
+
+    /// Sets the user agent of the clients created for [jobs](Fetcher::for_job).
+    pub fn with_user_agent(self, user_agent: UserAgent) -> Self {
+        Self {
+            user_agent: Some(user_agent),
+            ..self
+        }
+    }
+
+    /// Sets the proxy of the clients created for [jobs](Fetcher::for_job) which do not have their own.
+    pub fn with_proxy(self, proxy: Proxy) -> Self {
+        Self {
+            proxy: Some(proxy),
+            ..self
+        }
+    }
+
+    /// Creates the fetcher performing requests of the job, which shares the limits of this one.
+    ///
+    /// A separate client is created if the job has its own proxy or keeps cookies of its session.
+    pub fn for_job(&self, job: &Job) -> Result<Self, JobFetcherError> {
+        let fetcher = Self {
+            max_body_bytes: job.max_body_bytes.unwrap_or(self.max_body_bytes),
+            cache: job.cache.clone(),
+            encoding: job.encoding(),
+            ..self.clone()
+        };
+        if job.proxy.is_none() && job.session.is_none() && job.redirects.is_none() {
+            return Ok(fetcher);
+        }
+        let cookies = job
+            .session
+            .as_ref()
+            .map(CookieJar::load)
+            .transpose()?
+            .map(Arc::new);
+        Ok(Self {
+            client: client(
+                self.user_agent.as_ref(),
+                job.proxy.as_ref().or(self.proxy.as_ref()),
+                job.redirects.as_ref(),
+                cookies.as_ref().map(|cookies| cookies.store()),
+            )?,
+            cookies,
+            ..fetcher
+        })
+    }
+
+    /// Persists the cookies of the session of the job, if it has one.
+    pub async fn save_cookies(&self) -> Result<(), CookieJarError> {
+        match &self.cookies {
+            Some(cookies) => cookies.save().await,
+            None => Ok(()),
+        }
+    }
+
+    pub fn latest_results(&self) -> &Arc<LatestResults> {
+        &self.latest_results
+    }
+
+    /// Limits requests performed by this fetcher and all of its clones.
+    pub fn with_budget(self, budget: RequestBudget) -> Self {
+        Self {
+            budget: Some(Arc::new(budget)),
+            ..self
+        }
+    }
+
+    /// Limits the number of requests performed simultaneously by this fetcher and all of its clones.
+    ///
+    /// Requests exceeding the limit wait for the others to complete.
+    pub fn with_concurrency_limit(self, limit: usize) -> Self {
+        Self {
+            concurrency: Some(ConcurrencyLimit {
+                semaphore: Arc::new(Semaphore::new(limit)),
+                limit,
+            }),
+            ..self
+        }
+    }
+
+    /// Limits the rate of requests to each domain performed by this fetcher and all of its clones.
+    pub fn with_domain_rate(self, rate: Quota) -> Self {
+        Self {
+            domain_rate: Some(rate),
+            ..self
+        }
+    }
+
+    /// Limits the size of response bodies received by this fetcher and the ones of jobs without their own limit.
+    pub fn with_max_body_bytes(self, max_body_bytes: u64) -> Self {
+        Self {
+            max_body_bytes,
+            ..self
+        }
+    }
+
+    /// Waits until a request to the URL is allowed by the rate of requests to its domain.
+    ///
+    /// The rate overrides the one of this fetcher, if given.
+    pub async fn wait_for_domain(&self, url: &Url, rate: Option<Quota>) {
+        if let Some(Cassette::Replay(_)) = self.cassette {
+            return;
+        }
+        if let Some(rate) = rate.or(self.domain_rate) {
+            self.domain_rates.acquire(url, rate).await;
+        }
+    }
+
+    /// Client shared by all jobs, which sends the results to sinks,
+    /// so that they bypass the proxy, the cookies and the redirects of the job.
+    pub fn shared_client(&self) -> &reqwest::Client {
+        &self.shared_client
+    }
+
+    /// Builds the request of the job to the URL.
+    ///
+    /// The request is conditional if validators of the previous response are given.
+    pub fn request(
+        &self,
+        job: &Job,
+        validators: Option<&Validators>,
+        url: &Url,
+    ) -> Result<Request, HandleError> {
+        let mut request = self.client.request(job.method.0.clone(), url.clone());
+        if let Some(user_agent) = &job.user_agent {
+            request = request.header(header::USER_AGENT, user_agent.0.clone());
+        }
+        match &job.body {
+            Some(JobBody::Raw(text)) => request = request.body(text.clone()),
+            Some(JobBody::Json(value)) => request = request.json(value),
+            Some(JobBody::Form(fields)) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(name, value)| (name, value.expose()))
+                    .collect();
+                request = request.form(&fields);
+            }
+            None => {}
+        }
+        for (name, value) in &job.headers.0 {
+            request = request.header(name, value);
+        }
+        if let Some(Validators {
+            etag,
+            last_modified,
+        }) = validators
+        {
+            if let Some(etag) = etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        match &job.auth {
+            Some(Auth::Basic { username, password }) => {
+                debug!("Applying basic authentication as {username:?}");
+                request = request.basic_auth(username, Some(password.expose()));
+            }
+            Some(Auth::Bearer { token }) => {
+                debug!("Applying bearer authentication");
+                request = request.bearer_auth(token.expose());
+            }
+            None => {}
+        }
+        for (name, value) in job.dynamic_headers.render() {
+            request = request.header(name, value);
+        }
+        if let Some(timeout) = job.timeout {
+            request = request.timeout(timeout);
+        }
+
+        let mut request = request.build()?;
+        if let Some(accept_language) = &job.accept_language {
+            request
+                .headers_mut()
+                .entry(header::ACCEPT_LANGUAGE)
+                .or_insert_with(|| accept_language.0.clone());
+        }
+        Ok(request)
+    }
+
+    pub async fn fetch(&self, request: Request) -> Result<Fetched, HandleError> {
+        let (method, url) = (request.method().clone(), request.url().clone());
+        if let Some(Cassette::Replay(directory)) = &self.cassette {
+            debug!("Replaying recorded response");
+            return Record::load(directory, method, url).await;
+        }
+        let cached_request = match &self.cache {
+            Some(cache) => {
+                if let Some(fetched) = CacheEntry::load(cache, &request).await {
+                    debug!("Serving cached response");
+                    return Ok(fetched);
+                }
+                request.try_clone()
+            }
+            None => None,
+        };
+        if let Some(budget) = &self.budget {
+            if !budget.try_acquire() {
+                return Err(HandleError::QuotaExceeded);
+            }
+        }
+        let _permit = match &self.concurrency {
+            Some(ConcurrencyLimit { semaphore, limit }) => {
+                let permit = semaphore
+                    .acquire()
+                    .await
+                    .expect("semaphore should never be closed");
+                let in_flight = limit - semaphore.available_permits();
+                debug!("Performing request, {in_flight}/{limit} in flight");
+                Some(permit)
+            }
+            None => None,
+        };
+
+        let response = self.client.execute(request).await.map_err(send_error)?;
+        if response.url() != &url {
+            info!(from = %url, to = %response.url(), "Followed redirects");
+        }
+        if let Some(location) = response
+            .headers()
+            .get(header::LOCATION)
+            .filter(|_| response.status().is_redirection())
+        {
+            debug!(?location, "Redirect is not followed");
+        }
+        let fetched = Fetched {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: receive_body(response, self.max_body_bytes, self.encoding).await?,
+        };
+        if let Some(Cassette::Record(directory)) = &self.cassette {
+            debug!("Recording response");
+            Record::store(directory, method, url, &fetched).await?;
+        }
+        if let (Some(cache), Some(request)) = (&self.cache, cached_request) {
+            if fetched.status.is_success() {
+                if let Err(error) = CacheEntry::store(cache, &request, &fetched).await {
+                    warn!("Failed to cache response: {error}");
+                }
+            }
+        }
+
+        Ok(fetched)
+    }
+
+    /// Checks whether `robots.txt` of the origin of the URL allows fetching it
+    /// by the user agent of the job, or the global one if the job has none.
+    ///
+    /// The `robots.txt` files are fetched once in a while and shared by all clones of this fetcher.
+    /// They are requested directly by the client, so that they neither count against the budget
+    /// nor end up in the cache or cassettes, and are not consulted while replaying responses.
+    pub async fn allowed_by_robots(
+        &self,
+        url: &Url,
+        user_agent: Option<&UserAgent>,
+    ) -> Result<bool, HandleError> {
+        if let Some(Cassette::Replay(_)) = &self.cassette {
+            return Ok(true);
+        }
+        let origin = url.origin().ascii_serialization();
+        let rules = match self.robots.get(&origin) {
+            Some(rules) => rules,
+            None => {
+                debug!("Fetching robots.txt of {origin}");
+                let mut request = self.client.get(robots::robots_url(url));
+                if let Some(user_agent) = user_agent {
+                    request = request.header(header::USER_AGENT, user_agent.0.clone());
+                }
+                let response = request.send().await.map_err(send_error)?;
+                let status = response.status();
+                let rules: Arc<str> = if status.is_success() {
+                    receive_body(response, self.max_body_bytes, None)
+                        .await?
+                        .into()
+                } else if status.is_client_error() {
+                    debug!("No robots.txt at {origin}, everything is allowed");
+                    Arc::from("")
+                } else {
+                    return Err(HandleError::RobotsUnavailable(status));
+                };
+                self.robots.insert(origin, rules.clone());
+                rules
+            }
+        };
+        let user_agent = user_agent
+            .or(self.user_agent.as_ref())
+            .and_then(|user_agent| user_agent.0.to_str().ok())
+            .unwrap_or(DEFAULT_USER_AGENT);
+        Ok(robots::allows(&rules, user_agent, url))
+    }
+}
+
+/// Receives the body of the response chunk by chunk, failing as soon as it exceeds the limit.
+///
+/// The body is [decoded](decode) according to the charset of its `Content-Type` unless it is overridden.
+async fn receive_body(
+    mut response: Response,
+    limit: u64,
+    encoding: Option<&'static Encoding>,
+) -> Result<String, HandleError> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit)
+    {
+        return Err(HandleError::BodyTooLarge(limit));
+    }
+    let declared = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok()?.parse::<mime::Mime>().ok())
+        .and_then(|content_type| {
+            Encoding::for_label(content_type.get_param(mime::CHARSET)?.as_str().as_bytes())
+        });
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(send_error)? {
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(HandleError::BodyTooLarge(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(decode(&body, encoding.or(declared)))
+}
+
+/// Decodes the document in the encoding if it is known,
+/// or else in the one declared by its `<meta>` tag, which defaults to UTF-8.
+///
+/// A byte order mark takes precedence over both.
+pub fn decode(document: &[u8], encoding: Option<&'static Encoding>) -> String {
+    let encoding = encoding.or_else(|| meta_charset(document)).unwrap_or(UTF_8);
+    let (document, _, _) = encoding.decode(document);
+    document.into_owned()
+}
+
+/// Finds the charset declared by a `<meta>` tag at the beginning of an HTML document,
+/// either as `<meta charset="...">` or as `<meta http-equiv="Content-Type" content="...; charset=...">`.
+fn meta_charset(document: &[u8]) -> Option<&'static Encoding> {
+    static META_CHARSET: OnceLock<regex::bytes::Regex> = OnceLock::new();
+
+    let regex = META_CHARSET.get_or_init(|| {
+        regex::bytes::Regex::new(r#"(?i)<meta\s[^>]*charset\s*=\s*["']?\s*([a-z0-9_:.\-]+)"#)
+            .expect("regex should be valid")
+    });
+    let head = &document[..document.len().min(1024)];
+    let label = regex.captures(head)?.get(1)?;
+    Encoding::for_label(label.as_bytes())
+}
+
+/// Delay requested by the `Retry-After` header, either in seconds or until an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Distinguishes timeouts and failed redirects from other errors of sending the request.
+fn send_error(error: reqwest::Error) -> HandleError {
+    if error.is_timeout() {
+        return HandleError::Timeout;
+    }
+    match redirect_error(&error) {
+        Some(redirect) => HandleError::Redirect(redirect.clone()),
+        None => error.into(),
+    }
+}
+
+/// Gets the reason of the failed redirect from the error of the [client](client()).
+fn redirect_error(error: &reqwest::Error) -> Option<&RedirectError> {
+    if !error.is_redirect() {
+        return None;
+    }
+    error.source()?.downcast_ref()
+}
+
+/// Serialized form of a recorded response.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    method: String,
+    url: Url,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Record {
+    fn path(directory: &Path, method: &Method, url: &Url) -> PathBuf {
+        let digest = Sha256::new()
+            .chain_update(method.as_str())
+            .chain_update(" ")
+            .chain_update(url.as_str())
+            .finalize();
+        directory.join(format!("{digest:x}.json"))
+    }
+
+    async fn store(
+        directory: &Path,
+        method: Method,
+        url: Url,
+        fetched: &Fetched,
+    ) -> Result<(), HandleError> {
+        let path = Self::path(directory, &method, &url);
+        let record = Self::new(method, url, fetched);
+
+        fs::create_dir_all(directory).await?;
+        fs::write(path, serde_json::to_vec_pretty(&record)?).await?;
+        Ok(())
+    }
+
+    fn new(method: Method, url: Url, fetched: &Fetched) -> Self {
+        Self {
+            method: method.to_string(),
+            url,
+            status: fetched.status.as_u16(),
+            headers: fetched
+                .headers
+                .iter()
+                .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.into())))
+                .collect(),
+            body: fetched.body.clone(),
+        }
+    }
+
+    async fn load(directory: &Path, method: Method, url: Url) -> Result<Fetched, HandleError> {
+        let record = match fs::read(Self::path(directory, &method, &url)).await {
+            Ok(record) => record,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Err(HandleError::NotRecorded(method, url));
+            }
+            Err(error) => return Err(error.into()),
+        };
+        serde_json::from_slice::<Self>(&record)?.into_fetched()
+    }
+
+    fn into_fetched(self) -> Result<Fetched, HandleError> {
+        let Self {
+            status,
+            headers,
+            body,
+            ..
+        } = self;
+
+        Ok(Fetched {
+            status: StatusCode::from_u16(status).map_err(|error| {
+                <serde_json::Error as serde::de::Error>::custom(format_args!(
+                    "invalid status: {error}"
+                ))
+            })?,
+            headers: headers
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    Some((
+                        HeaderName::try_from(name).ok()?,
+                        HeaderValue::try_from(value).ok()?,
+                    ))
+                })
+                .collect(),
+            body,
+        })
+    }
+}
+
+/// Response stored in the [cache](ResponseCache) along with the time it was received.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// UNIX timestamp in seconds
+    stored_at: u64,
+    #[serde(flatten)]
+    record: Record,
+}
+
+impl CacheEntry {
+    /// Path of the entry of the request, which is identified by its method, URL and body.
+    fn path(cache: &ResponseCache, request: &Request) -> PathBuf {
+        let digest = Sha256::new()
+            .chain_update(request.method().as_str())
+            .chain_update(" ")
+            .chain_update(request.url().as_str())
+            .chain_update(" ")
+            .chain_update(request.body().and_then(Body::as_bytes).unwrap_or_default())
+            .finalize();
+        cache.path.join(format!("{digest:x}.json"))
+    }
+
+    /// Loads the response to the request unless there is no fresh one.
+    async fn load(cache: &ResponseCache, request: &Request) -> Option<Fetched> {
+        let path = Self::path(cache, request);
+        let entry = match fs::read(&path).await {
+            Ok(entry) => entry,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+            Err(error) => {
+                warn!(
+                    "Failed to read cached response from {}: {error}",
+                    path.display()
+                );
+                return None;
+            }
+        };
+        let entry = match serde_json::from_slice::<Self>(&entry) {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!(
+                    "Ignoring corrupt cached response in {}: {error}",
+                    path.display()
+                );
+                return None;
+            }
+        };
+        let age = Duration::from_secs(unix_time().saturating_sub(entry.stored_at));
+        if age >= cache.ttl {
+            debug!(age_secs = age.as_secs(), "Cached response is stale");
+            return None;
+        }
+        entry.record.into_fetched().ok()
+    }
+
+    async fn store(
+        cache: &ResponseCache,
+        request: &Request,
+        fetched: &Fetched,
+    ) -> Result<(), HandleError> {
+        let entry = Self {
+            stored_at: unix_time(),
+            record: Record::new(request.method().clone(), request.url().clone(), fetched),
+        };
+        fs::create_dir_all(&cache.path).await?;
+        fs::write(
+            Self::path(cache, request),
+            serde_json::to_vec_pretty(&entry)?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::{quota::Quota, testing};
+
+    #[tokio::test]
+    async fn test_replay_recorded_response() {
+        let cassette = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("GET"))
+            .and(matchers::path("/products"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-served-by", "cache-1")
+                    .set_body_string("<h3>Loom Hoodie</h3>"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let url = Url::parse(&server.uri())
+            .unwrap()
+            .join("/products")
+            .unwrap();
+
+        let recorder = Fetcher::new(
+            reqwest::Client::new(),
+            Some(Cassette::Record(cassette.path().to_owned())),
+        );
+        let request = recorder.client.get(url.clone()).build().unwrap();
+        let recorded = recorder.fetch(request).await.unwrap();
+        drop(server);
+
+        let player = Fetcher::new(
+            reqwest::Client::new(),
+            Some(Cassette::Replay(cassette.path().to_owned())),
+        );
+        let request = player.client.get(url.clone()).build().unwrap();
+        let replayed = player.fetch(request).await.unwrap();
+
+        assert_eq!(replayed.status, recorded.status);
+        assert_eq!(replayed.body, "<h3>Loom Hoodie</h3>");
+        assert_eq!(replayed.headers["x-served-by"], "cache-1");
+
+        let request = player.client.post(url).build().unwrap();
+        assert!(matches!(
+            player.fetch(request).await,
+            Err(HandleError::NotRecorded(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_quota_are_not_sent() {
+        let server = MockServer::start().await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let fetcher =
+            Fetcher::new(reqwest::Client::new(), None).with_budget(RequestBudget::new(Quota {
+                requests: 1,
+                window: Duration::from_secs(3600),
+            }));
+        let request = || fetcher.client.get(server.uri()).build().unwrap();
+
+        assert!(fetcher.fetch(request()).await.is_ok());
+        assert!(matches!(
+            fetcher.fetch(request()).await,
+            Err(HandleError::QuotaExceeded)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_robots_are_fetched_outside_of_budget_and_cache() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+        Mock::given(matchers::path("/robots.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("User-agent: *\nAllow: /\n"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::path("/products"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let url = Url::parse(&server.uri())
+            .unwrap()
+            .join("/products")
+            .unwrap();
+
+        let fetcher = Fetcher {
+            cache: Some(ResponseCache {
+                path: directory.path().to_owned(),
+                ttl: Duration::from_secs(3600),
+            }),
+            ..Fetcher::new(reqwest::Client::new(), None).with_budget(RequestBudget::new(Quota {
+                requests: 1,
+                window: Duration::from_secs(3600),
+            }))
+        };
+        assert!(fetcher.allowed_by_robots(&url, None).await.unwrap());
+        let request = fetcher.client.get(url).build().unwrap();
+        assert!(fetcher.fetch(request).await.is_ok());
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_responses_are_served_from_cache() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+        Mock::given(matchers::path("/products"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+            .expect(3)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::path("/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let fetcher = |ttl| Fetcher {
+            cache: Some(ResponseCache {
+                path: directory.path().to_owned(),
+                ttl,
+            }),
+            ..Fetcher::new(reqwest::Client::new(), None)
+        };
+        let fetch = |fetcher: Fetcher, path: &'static str, body: &'static str| {
+            let server = &server;
+            async move {
+                let request = fetcher
+                    .client
+                    .post(format!("{}{path}", server.uri()))
+                    .body(body)
+                    .build()
+                    .unwrap();
+                fetcher.fetch(request).await.unwrap()
+            }
+        };
+
+        let fresh = fetcher(Duration::from_secs(3600));
+        for _ in 0..2 {
+            let fetched = fetch(fresh.clone(), "/products", "q=hoodie").await;
+            assert_eq!(fetched.body, "<h3>Loom Hoodie</h3>");
+        }
+        fetch(fresh.clone(), "/products", "q=tee").await;
+        fetch(fetcher(Duration::ZERO), "/products", "q=hoodie").await;
+        for _ in 0..2 {
+            assert_eq!(
+                fetch(fresh.clone(), "/missing", "").await.status,
+                StatusCode::NOT_FOUND
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_concurrency_limit_wait() {
+        let server =
+            testing::serve(ResponseTemplate::new(200).set_delay(Duration::from_millis(200))).await;
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None).with_concurrency_limit(2);
+        let fetch = || async {
+            let request = fetcher.client.get(server.uri()).build().unwrap();
+            fetcher.fetch(request).await
+        };
+        let start = tokio::time::Instant::now();
+        let (first, second, third) = tokio::join!(fetch(), fetch(), fetch());
+
+        assert!(first.is_ok() && second.is_ok() && third.is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_bodies_over_limit_are_rejected() {
+        let server =
+            testing::serve(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+                .await;
+
+        let request = || Request::new(Method::GET, server.uri().parse().unwrap());
+        let fetcher = Fetcher::new(reqwest::Client::new(), None).with_max_body_bytes(20);
+        assert_eq!(
+            fetcher.fetch(request()).await.unwrap().body,
+            "<h3>Loom Hoodie</h3>"
+        );
+
+        let fetcher = fetcher.with_max_body_bytes(19);
+        assert!(matches!(
+            fetcher.fetch(request()).await,
+            Err(HandleError::BodyTooLarge(19))
+        ));
+    }
+
+    #[test]
+    fn test_retry_after_is_parsed_in_both_forms() {
+        let headers = |value: &str| {
+            HeaderMap::from_iter([(header::RETRY_AFTER, HeaderValue::from_str(value).unwrap())])
+        };
+        assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+        let in_a_minute = (Utc::now() + chrono::Duration::seconds(60))
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        let delay = retry_after(&headers(&in_a_minute)).unwrap();
+        assert!((Duration::from_secs(58)..=Duration::from_secs(60)).contains(&delay));
+        assert_eq!(
+            retry_after(&headers("Sun, 06 Nov 1994 08:49:37 GMT")),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_charset_is_detected_unless_overridden() {
+        let server = MockServer::start().await;
+        let encode = |text| encoding_rs::WINDOWS_1251.encode(text).0.into_owned();
+        Mock::given(matchers::path("/header"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                encode("<h3>Толстовка</h3>"),
+                "text/html; charset=windows-1251",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(matchers::path("/meta"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                encode(r#"<meta http-equiv="Content-Type" content="text/html; charset=cp1251"><h3>Толстовка</h3>"#),
+                "text/html",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(matchers::path("/lying"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(encode("<h3>Толстовка</h3>"), "text/html; charset=utf-8"),
+            )
+            .mount(&server)
+            .await;
+        async fn fetch(fetcher: &Fetcher, url: String) -> String {
+            let request = Request::new(Method::GET, url.parse().unwrap());
+            fetcher.fetch(request).await.unwrap().body
+        }
+        let url = |path| format!("{}{path}", server.uri());
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        assert_eq!(fetch(&fetcher, url("/header")).await, "<h3>Толстовка</h3>");
+        assert!(fetch(&fetcher, url("/meta"))
+            .await
+            .ends_with("<h3>Толстовка</h3>"));
+        assert_ne!(fetch(&fetcher, url("/lying")).await, "<h3>Толстовка</h3>");
+
+        let fetcher = Fetcher {
+            encoding: Some(encoding_rs::WINDOWS_1251),
+            ..fetcher
+        };
+        assert_eq!(fetch(&fetcher, url("/lying")).await, "<h3>Толстовка</h3>");
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_followed_as_configured() {
+        let server = MockServer::start().await;
+        let other = MockServer::start().await;
+        let other_url = format!("http://localhost:{}/b", other.address().port());
+        for (from, to) in [("/a", "/b"), ("/c", other_url.as_str())] {
+            Mock::given(matchers::path(from))
+                .respond_with(ResponseTemplate::new(302).insert_header("location", to))
+                .mount(&server)
+                .await;
+        }
+        for server in [&server, &other] {
+            Mock::given(matchers::path("/b"))
+                .respond_with(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+                .mount(server)
+                .await;
+        }
+        let fetch = |redirects, path| {
+            let url = Url::parse(&server.uri()).unwrap().join(path).unwrap();
+            async move {
+                let fetcher =
+                    Fetcher::new(client(None, None, Some(&redirects), None).unwrap(), None);
+                fetcher
+                    .fetch(Request::new(Method::GET, url))
+                    .await
+                    .unwrap()
+                    .status
+            }
+        };
+        let default = Redirects::default();
+
+        assert_eq!(fetch(default, "/a").await, StatusCode::OK);
+        assert_eq!(fetch(default, "/c").await, StatusCode::OK);
+        let disabled = Redirects { max: 0, ..default };
+        assert_eq!(fetch(disabled, "/a").await, StatusCode::FOUND);
+        let same_host = Redirects {
+            cross_host: false,
+            ..default
+        };
+        assert_eq!(fetch(same_host, "/a").await, StatusCode::OK);
+        assert_eq!(fetch(same_host, "/c").await, StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_loop_reports_chain() {
+        let server = MockServer::start().await;
+        for (from, to) in [("/a", "/b"), ("/b", "/c"), ("/c", "/b")] {
+            Mock::given(matchers::path(from))
+                .respond_with(ResponseTemplate::new(302).insert_header("location", to))
+                .mount(&server)
+                .await;
+        }
+        let url = |path| Url::parse(&server.uri()).unwrap().join(path).unwrap();
+
+        let fetcher = Fetcher::new(client(None, None, None, None).unwrap(), None);
+        let request = fetcher.client.get(url("/a")).build().unwrap();
+        let Err(HandleError::Redirect(RedirectError::Loop(chain))) = fetcher.fetch(request).await
+        else {
+            panic!("redirect loop should be detected");
+        };
+
+        assert_eq!(
+            chain,
+            RedirectChain(vec![url("/a"), url("/b"), url("/c"), url("/b")])
+        );
+        assert_eq!(
+            HandleError::Redirect(RedirectError::Loop(chain)).to_string(),
+            format!(
+                "redirect loop: {} -> {} -> {} -> {}",
+                url("/a"),
+                url("/b"),
+                url("/c"),
+                url("/b"),
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_robots_rules_are_cached_per_origin() {
+        let server = MockServer::start().await;
+        Mock::given(matchers::path("/robots.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "User-agent: *\nDisallow: /cart\n\nUser-agent: wetcher\nDisallow: /private\n",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let url = |path| Url::parse(&server.uri()).unwrap().join(path).unwrap();
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        assert!(!fetcher
+            .allowed_by_robots(&url("/private/orders?page=2"), None)
+            .await
+            .unwrap());
+        assert!(fetcher
+            .allowed_by_robots(&url("/cart"), None)
+            .await
+            .unwrap());
+        assert!(fetcher
+            .clone()
+            .allowed_by_robots(&url("/products"), None)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_robots_rules_are_matched_against_user_agent() {
+        let server = MockServer::start().await;
+        Mock::given(matchers::path("/robots.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "User-agent: *\nDisallow: /cart\n\nUser-agent: shopbot\nDisallow: /private\n",
+            ))
+            .mount(&server)
+            .await;
+        let url = |path| Url::parse(&server.uri()).unwrap().join(path).unwrap();
+        let user_agent = |raw: &str| UserAgent::try_from(raw.to_owned()).unwrap();
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None)
+            .with_user_agent(user_agent("ShopBot/2.0 (+https://shop.example/bot)"));
+        assert!(!fetcher
+            .allowed_by_robots(&url("/private"), None)
+            .await
+            .unwrap());
+        assert!(fetcher
+            .allowed_by_robots(&url("/cart"), None)
+            .await
+            .unwrap());
+        let job_user_agent = user_agent("Mozilla/5.0 (X11; Linux x86_64)");
+        assert!(fetcher
+            .allowed_by_robots(&url("/private"), Some(&job_user_agent))
+            .await
+            .unwrap());
+        assert!(!fetcher
+            .allowed_by_robots(&url("/cart"), Some(&job_user_agent))
+            .await
+            .unwrap());
+    }
+
+    /// Sends the request of the job to its URL.
+    async fn send(fetcher: &Fetcher, job: &Job) -> Fetched {
+        let crate::job::Resource::Url(url) = &job.resource else {
+            unreachable!("job should poll a URL");
+        };
+        let request = fetcher.request(job, None, url).unwrap();
+        fetcher.fetch(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_configured_method_and_body_are_sent() {
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("POST"))
+            .and(matchers::path("/search"))
+            .and(matchers::body_string("q=hoodie"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let job = testing::url_job(
+            format!("{}/search", server.uri()),
+            r#"method: "post", body: "q=hoodie","#,
+        );
+        assert_eq!(job.method, crate::job::HttpMethod(Method::POST));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        assert_eq!(send(&fetcher, &job).await.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_json_and_form_bodies_are_encoded() {
+        let server = MockServer::start().await;
+        Mock::given(matchers::path("/graphql"))
+            .and(matchers::header("content-type", "application/json"))
+            .and(matchers::body_json(serde_json::json!({
+                "query": "{ products { name } }",
+                "variables": { "token": "s3cr3t", "pageSize": 10 },
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::path("/search"))
+            .and(matchers::header(
+                "content-type",
+                "application/x-www-form-urlencoded",
+            ))
+            .and(matchers::body_string(
+                "q=loom+hoodie&pageSize=10&token=s3cr3t",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let json = testing::url_job(
+            format!("{}/graphql", server.uri()),
+            r#"method: "POST", body: { Json: '{ "query": "{ products { name } }", "variables": { "token": "s3cr3t", "pageSize": 10 } }' },"#,
+        );
+        let form = testing::url_job(
+            format!("{}/search", server.uri()),
+            r#"method: "POST", body: { Form: [
+                { name: "q", value: "loom hoodie" },
+                { name: "pageSize", value: "10" },
+                { name: "token", value: "s3cr3t" },
+            ] },"#,
+        );
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        for job in [&json, &form] {
+            assert_eq!(send(&fetcher, job).await.status, StatusCode::OK);
+            assert!(!format!("{job:?}").contains("s3cr3t"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_agent_is_sent() {
+        let server = MockServer::start().await;
+        for user_agent in [DEFAULT_USER_AGENT, "Mozilla/5.0 (X11; Linux x86_64)"] {
+            Mock::given(matchers::header("user-agent", user_agent))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let fetcher = Fetcher::new(client(None, None, None, None).unwrap(), None);
+        for fields in ["", r#"user_agent: "Mozilla/5.0 (X11; Linux x86_64)","#] {
+            send(&fetcher, &testing::url_job(server.uri(), fields)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accept_language_yields_to_explicit_header() {
+        let server = testing::serve(ResponseTemplate::new(200)).await;
+        let job = |headers| {
+            testing::url_job(
+                server.uri(),
+                &format!(r#"accept_language: "en-US,en;q=0.9", headers: {headers},"#),
+            )
+        };
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        for job in [job("{}"), job(r#"{ "Accept-Language": "de-DE" }"#)] {
+            send(&fetcher, &job).await;
+        }
+
+        let languages = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                request
+                    .headers
+                    .get_all("accept-language")
+                    .iter()
+                    .map(|value| value.to_str().unwrap().to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(languages, [["en-US,en;q=0.9"], ["de-DE"]]);
+    }
+
+    #[tokio::test]
+    async fn test_job_proxy_is_used() {
+        let proxy = MockServer::start().await;
+        Mock::given(matchers::path("/catalog"))
+            .and(matchers::header("host", "shop.example"))
+            // `scraper:hunter2` encoded as Base64
+            .and(matchers::header(
+                "proxy-authorization",
+                "Basic c2NyYXBlcjpodW50ZXIy",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&proxy)
+            .await;
+        let job = testing::url_job(
+            "http://shop.example/catalog",
+            &format!(
+                r#"proxy: {{ url: "{}", username: "scraper", password: "hunter2" }},"#,
+                proxy.uri()
+            ),
+        );
+        assert!(!format!("{job:?}").contains("hunter2"));
+
+        let fetcher = Fetcher::new(client(None, None, None, None).unwrap(), None)
+            .for_job(&job)
+            .unwrap();
+        assert_eq!(send(&fetcher, &job).await.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_session_cookies_are_kept_and_persisted() {
+        let server = testing::serve(
+            ResponseTemplate::new(200).insert_header("set-cookie", "session=abc; Path=/"),
+        )
+        .await;
+        let directory = tempfile::tempdir().unwrap();
+        let job = testing::url_job(
+            server.uri(),
+            &format!(
+                r#"session: {{ path: "{}" }},"#,
+                directory.path().join("cookies.json").display()
+            ),
+        );
+        let fetcher = Fetcher::new(client(None, None, None, None).unwrap(), None);
+
+        let first = fetcher.for_job(&job).unwrap();
+        send(&first, &job).await;
+        send(&first, &job).await;
+        first.save_cookies().await.unwrap();
+        send(&fetcher.for_job(&job).unwrap(), &job).await;
+
+        let cookies: Vec<_> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                request
+                    .headers
+                    .get("cookie")
+                    .map(|cookie| cookie.to_str().unwrap().to_owned())
+            })
+            .collect();
+        assert_eq!(
+            cookies,
+            [
+                None,
+                Some("session=abc".to_owned()),
+                Some("session=abc".to_owned())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configured_headers_are_sent() {
+        let server = MockServer::start().await;
+        Mock::given(matchers::header("accept", "text/html"))
+            .and(matchers::header("x-api-key", "secret"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let job = testing::url_job(
+            server.uri(),
+            r#"headers: { "Accept": "text/html", "X-Api-Key": "secret" },"#,
+        );
+        assert_eq!(
+            job.headers
+                .0
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["accept", "x-api-key"]
+        );
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        assert_eq!(send(&fetcher, &job).await.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_auth_is_applied_without_exposing_secrets() {
+        let server = MockServer::start().await;
+        for authorization in ["Bearer t0ken", "Basic YWRtaW46aHVudGVyMg=="] {
+            Mock::given(matchers::header("authorization", authorization))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        let bearer = testing::url_job(server.uri(), r#"auth: { Bearer: { token: "t0ken" } },"#);
+        let basic = testing::url_job(
+            server.uri(),
+            r#"auth: { Basic: { username: "admin", password: "hunter2" } },"#,
+        );
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        for job in [&bearer, &basic] {
+            assert_eq!(send(&fetcher, job).await.status, StatusCode::OK);
+            let debug = format!("{job:?}");
+            assert!(!debug.contains("t0ken") && !debug.contains("hunter2"));
+        }
+    }
+}
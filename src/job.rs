@@ -1,34 +1,224 @@
-use std::{fmt, fmt::Formatter, path::PathBuf, time::Duration};
+use std::{
+    borrow::Cow,
+    cmp,
+    collections::HashMap,
+    env::VarError,
+    fmt,
+    fmt::Formatter,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use indexmap::IndexMap;
-use serde::{Deserialize, Deserializer};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use encoding_rs::Encoding;
+use indexmap::{IndexMap, IndexSet};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use regex::Regex;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Method,
+};
+use serde::{Deserialize, Deserializer, Serialize};
 use skyscraper::{
     xpath,
     xpath::{
-        grammar::{data_model::Node, NonTreeXpathNode},
+        grammar::{
+            data_model::{AttributeNode, Node, XpathItem},
+            NonTreeXpathNode, XpathItemTreeNode, XpathItemTreeNodeData,
+        },
         xpath_item_set::XpathItemSet,
         Xpath, XpathItemTree,
     },
 };
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+use tracing::{debug, warn};
 use url::Url;
 
+use crate::{
+    diff::{self, Change},
+    document::SelectError,
+    json::ParsedJsonPath,
+    quota::Quota,
+    sink::{self, OwnedEvent, Sink, SinkWriter},
+};
+
 /// A resource which should be polled for info.
 #[derive(Debug, Clone, Deserialize)]
 pub struct Job {
+    /// Name of the job identifying it in logs and between config reloads,
+    /// the [address](Resource::address) of its resource by default
+    #[serde(default)]
+    pub name: String,
+    /// Whether the job should be run, which allows turning it off without removing it from the config
+    #[serde(default = "Job::default_enabled")]
+    pub enabled: bool,
+    /// Labels by which the jobs to run are selected from the command line
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Digest of the raw config of the job, used to detect its changes when the config is reloaded
+    #[serde(skip)]
+    pub digest: String,
     /// The scraped resource
     pub resource: Resource,
-    /// Period at which the resource is polled
-    pub period: Duration,
+    /// Format of the documents of the resource, which determines the language of target paths
+    #[serde(default)]
+    pub format: DocumentFormat,
+    /// HTTP method of requests, `GET` by default
+    #[serde(default)]
+    pub method: HttpMethod,
+    /// Body of requests
+    #[serde(default)]
+    pub body: Option<Body>,
+    /// Query parameters merged into the URL of each requested page
+    #[serde(default)]
+    pub query: QueryParams,
+    /// Schedule at which the resource is polled
+    pub period: Schedule,
+    /// What to do with the ticks missed because a poll took longer than the period of the schedule
+    #[serde(default)]
+    pub missed_ticks: MissedTicks,
+    /// Random deviation of each period, so that jobs with the same period do not poll in lockstep
+    #[serde(default)]
+    pub jitter: Option<Jitter>,
+    /// Delay of the first poll after the job is started, none by default
+    #[serde(default)]
+    pub start_delay: Option<StartDelay>,
     /// Targets to be queried
     pub targets: Targets,
     /// The path which should be visited next
     pub continuation: Continuation,
+    /// Maximal number of pages visited by following the continuation during a single poll
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+    /// Condition which a page has to satisfy for its continuation to be followed
+    #[serde(default)]
+    pub continue_while: Option<ContinuationCondition>,
+    /// Name of the target by whose value the records found on all pages of a poll are deduplicated
+    #[serde(default)]
+    pub dedup_by: Option<String>,
+    /// Another job whose latest records are merged into the records of this one sharing their key
+    #[serde(default)]
+    pub join: Option<Join>,
+    /// File to which pages still pending in a poll are saved after each page, so that it is resumed after a restart
+    #[serde(default)]
+    pub progress: Option<PathBuf>,
+    /// Destinations to which results of each poll are written
+    #[serde(default)]
+    pub sinks: Vec<Sink>,
+    /// Queue of results waiting to be written into the sinks
+    #[serde(default)]
+    pub sink_queue: SinkQueue,
+    /// Names of response headers whose values should be captured alongside targets
+    #[serde(default)]
+    pub response_headers: Vec<String>,
+    /// Whether records should be annotated with absolute XPaths of their nodes
+    #[serde(default)]
+    pub annotate_paths: bool,
+    /// Maximal duration of each request, including receiving the body
+    #[serde(default)]
+    pub timeout: Option<Duration>,
+    /// Retrying of requests failed due to network or server errors
+    #[serde(default)]
+    pub retry: Option<Retry>,
+    /// Authentication of requests
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    /// Request headers sent with each request in the configured order
+    #[serde(default)]
+    pub headers: StaticHeaders,
+    /// User agent sent with each request instead of the global one
+    #[serde(default)]
+    pub user_agent: Option<UserAgent>,
+    /// Value of the `Accept-Language` request header, unless the header is configured explicitly
+    #[serde(default)]
+    pub accept_language: Option<AcceptLanguage>,
+    /// Proxy which requests are routed through instead of the global one
+    #[serde(default)]
+    pub proxy: Option<Proxy>,
+    /// Cookies kept between requests of the job
+    #[serde(default)]
+    pub session: Option<Session>,
+    /// Following of redirects overriding the default one
+    #[serde(default)]
+    pub redirects: Option<Redirects>,
+    /// Request headers rendered right before each request
+    #[serde(default)]
+    pub dynamic_headers: DynamicHeaders,
+    /// Condition which a fetched document has to satisfy for the poll to be successful
+    #[serde(default)]
+    pub success_when: Option<SuccessCriterion>,
+    /// Whether only changes of the results since the previous poll should be reported
+    #[serde(default)]
+    pub detect_changes: bool,
+    /// Whether the results should be reported with the number of their changes since the previous poll
+    #[serde(default)]
+    pub change_count: bool,
+    /// Names of targets whose numbers found by the polls are kept as series served by the [API](crate::api)
+    #[serde(default)]
+    pub track: Vec<String>,
+    /// File to which the series of the tracked targets are saved after each poll, so that they survive a restart
+    #[serde(default)]
+    pub series: Option<PathBuf>,
+    /// Whether requests should be conditional on the resource being modified since the previous poll
+    #[serde(default)]
+    pub conditional_requests: bool,
+    /// Whether URLs disallowed by `robots.txt` of their sites should be skipped
+    #[serde(default)]
+    pub respect_robots: bool,
+    /// Limit on the rate of requests to each domain overriding the global one
+    #[serde(default)]
+    pub domain_rate: Option<Quota>,
+    /// Encoding of documents overriding the one they declare
+    #[serde(default)]
+    pub encoding: Option<TextEncoding>,
+    /// Maximal size of a response body in bytes overriding the global one
+    #[serde(default)]
+    pub max_body_bytes: Option<u64>,
+    /// Cache of responses on disk, served instead of performing requests while they are fresh
+    #[serde(default)]
+    pub cache: Option<ResponseCache>,
+    /// Local document which the targets are checked against by the `validate` command
+    #[serde(default)]
+    pub sample: Option<PathBuf>,
+    /// Unsuccessful HTTP statuses whose responses are processed instead of failing the request
+    #[serde(default)]
+    pub accept_status: Vec<u16>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub enum Resource {
     Url(Url),
     Path(PathBuf),
+    /// Every local file matching the glob pattern
+    Glob(String),
+    /// One of the interchangeable mirrors, chosen by weight on each poll
+    Mirrors(Vec<Mirror>),
+    /// Standard input, read to the end once and reused by all polls
+    Stdin,
+}
+
+impl Resource {
+    /// Gets the URL, the path or the pattern of the resource, which is the URL of the first one for mirrors.
+    pub fn address(&self) -> String {
+        match self {
+            Resource::Url(url) => url.to_string(),
+            Resource::Path(path) => path.display().to_string(),
+            Resource::Glob(pattern) => pattern.clone(),
+            Resource::Mirrors(mirrors) => mirrors
+                .first()
+                .map_or_else(String::new, |mirror| mirror.url.to_string()),
+            Resource::Stdin => "stdin".to_owned(),
+        }
+    }
 }
 
 impl fmt::Display for Resource {
@@ -40,32 +230,691 @@ impl fmt::Display for Resource {
             Resource::Path(path) => {
                 write!(f, "Path({})", path.display())
             }
+            Resource::Glob(pattern) => {
+                write!(f, "Glob({pattern})")
+            }
+            Resource::Mirrors(mirrors) => {
+                f.write_str("Mirrors(")?;
+                for (index, Mirror { url, weight }) in mirrors.iter().enumerate() {
+                    if index != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{url} x{weight}")?;
+                }
+                f.write_str(")")
+            }
+            Resource::Stdin => f.write_str("Stdin"),
+        }
+    }
+}
+
+/// One of the interchangeable mirrors of a resource.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Mirror {
+    pub url: Url,
+    /// Relative share of polls served by this mirror
+    #[serde(default = "Mirror::default_weight")]
+    pub weight: u32,
+}
+
+impl Mirror {
+    fn default_weight() -> u32 {
+        1
+    }
+
+    /// Orders the mirrors by weighted random sampling without replacement:
+    /// the first one is chosen proportionally to its weight while the rest are fallbacks.
+    pub fn weighted_order<'a>(mirrors: &'a [Self], rng: &mut impl Rng) -> Vec<&'a Self> {
+        let mut remaining: Vec<_> = mirrors.iter().collect();
+        let mut ordered = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            // Sampling fails only if all of the remaining weights are zero.
+            let index = WeightedIndex::new(remaining.iter().map(|mirror| mirror.weight))
+                .map_or(0, |weights| weights.sample(rng));
+            ordered.push(remaining.remove(index));
         }
+
+        ordered
     }
 }
 
+/// Format of the documents of a resource.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum DocumentFormat {
+    /// HTML documents whose items are selected by XPath
+    #[default]
+    Html,
+    /// JSON documents whose items are selected by JSONPath
+    Json,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Targets(pub IndexMap<String, Target>);
 
+impl Targets {
+    /// Checks if there is a target with the name, including the nested ones.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|(target_name, target)| {
+            target_name == name
+                || matches!(&target.then, Then::Get(targets) if targets.contains(name))
+        })
+    }
+
+    /// Finds the [minimal change](Target::min_change) of the values at the JSON pointer into the results.
+    ///
+    /// Segments of the pointer alternate between keys of records and names of their targets.
+    fn min_change(&self, pointer: &str) -> Option<MinChange> {
+        let mut segments = pointer
+            .split('/')
+            .skip(1)
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"));
+        let mut targets = self;
+        loop {
+            segments.next()?;
+            let target = targets.0.get(&segments.next()?)?;
+            match &target.then {
+                Then::Get(nested) => targets = nested,
+                _ => return target.min_change,
+            }
+        }
+    }
+
+    /// Describes the problems of the targets, such as selectors not applicable to documents of the format.
+    fn problems(&self, format: DocumentFormat, parent: &str, problems: &mut Vec<String>) {
+        for (name, target) in &self.0 {
+            let name = format!("{parent}/{name}");
+            let mut selectors = vec![&target.path];
+            selectors.extend(&target.key);
+            problems.extend(target.default_problem(&name));
+            if !target.transforms.is_empty() && !matches!(target.then, Then::Extract(_)) {
+                problems.push(format!(
+                    "target {name:?} has transforms but extracts no values"
+                ));
+            }
+            if target.min_change.is_some() && !matches!(target.then, Then::Extract(_)) {
+                problems.push(format!(
+                    "target {name:?} has a minimal change but extracts no values"
+                ));
+            }
+            match &target.then {
+                Then::Get(targets) => targets.problems(format, &name, problems),
+                Then::Zip(Zip { labels, values }) => selectors.extend([labels, values]),
+                Then::Seo if format != DocumentFormat::Html => {
+                    problems.push(format!(
+                        "target {name:?} extracts SEO metadata of non-HTML documents"
+                    ));
+                }
+                Then::RegexCaptures {
+                    pattern: ParsedRegex(regex),
+                } if regex.capture_names().flatten().next().is_none() => {
+                    problems.push(format!("target {name:?} captures no named groups"));
+                }
+                Then::Product(product) => {
+                    if product.selectors().next().is_none() {
+                        problems.push(format!("target {name:?} selects no product fields"));
+                    }
+                    selectors.extend(product.selectors());
+                }
+                Then::Extract(_) | Then::Seo | Then::RegexCaptures { .. } => {}
+            }
+            if !selectors.iter().all(|selector| selector.supports(format)) {
+                problems.push(format!(
+                    "target {name:?} has selectors not applicable to {format:?} documents"
+                ));
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Target {
-    pub path: ParsedXPath,
+    pub path: Selector,
     pub then: Then,
+    /// Whether the enclosing record should be dropped if this target yields nothing
+    #[serde(default)]
+    pub required: bool,
+    /// Selector of the text keying each of the [nested](Then::Get) records instead of its index
+    #[serde(default)]
+    pub key: Option<Selector>,
+    /// Value substituting the unknown values [extracted](Then::Extract) by this target,
+    /// which is also produced if no items are found
+    #[serde(default)]
+    pub default: Option<serde_json::Value>,
+    /// Operations applied in order to each [extracted](Then::Extract) value before the default
+    #[serde(default)]
+    pub transforms: Vec<Transform>,
+    /// Smallest difference of extracted numbers which is [detected](Job::detect_changes) as their change
+    #[serde(default)]
+    pub min_change: Option<MinChange>,
+}
+
+/// Smallest difference between the previous and the current number considered to be a change.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum MinChange {
+    /// Absolute difference
+    Absolute(f64),
+    /// Percentage of the previous number
+    Percent(Percentage),
+}
+
+impl MinChange {
+    /// Checks if the difference between the numbers reaches this threshold.
+    pub fn is_reached(&self, old: f64, new: f64) -> bool {
+        let difference = (new - old).abs();
+        match *self {
+            Self::Absolute(min) => difference >= min,
+            Self::Percent(Percentage(percent)) => difference >= old.abs() * percent / 100.0,
+        }
+    }
+}
+
+impl Target {
+    /// Applies the [transforms](Self::transforms) to each of the extracted values.
+    pub fn transform(&self, values: &mut [Value<'_>]) {
+        for value in values {
+            *value = self.transforms.iter().fold(
+                std::mem::replace(value, Value::Unknown),
+                |value, transform| transform.apply(value),
+            );
+        }
+    }
+
+    /// Applies the [default](Self::default) to the extracted values, if there is one.
+    pub fn fill_defaults(&self, values: &mut Vec<Value<'_>>) {
+        let Some(default) = &self.default else {
+            return;
+        };
+        let default = match default {
+            serde_json::Value::String(value) => Value::String(Cow::Owned(value.clone())),
+            serde_json::Value::Number(value) => {
+                value.as_f64().map_or(Value::Unknown, Value::Number)
+            }
+            serde_json::Value::Bool(value) => Value::Bool(*value),
+            value => Value::Json(value.clone()),
+        };
+        if values.is_empty() {
+            values.push(default);
+        } else {
+            for value in values.iter_mut().filter(|value| **value == Value::Unknown) {
+                *value = default.clone();
+            }
+        }
+    }
+
+    /// Describes the problem of the default, which should be of the type of the extracted values.
+    fn default_problem(&self, name: &str) -> Option<String> {
+        let default = self.default.as_ref()?;
+        let (expected, matches) = match &self.then {
+            _ if self.required => {
+                return Some(format!("target {name:?} is required but has a default"))
+            }
+            Then::Extract(ValueExtractor::Json) => return None,
+            Then::Extract(_)
+                if self
+                    .transforms
+                    .iter()
+                    .any(|transform| matches!(transform, Transform::ParseNumber { .. })) =>
+            {
+                ("number", default.is_number())
+            }
+            Then::Extract(ValueExtractor::Number { .. } | ValueExtractor::Count) => {
+                ("number", default.is_number())
+            }
+            Then::Extract(ValueExtractor::Exists) => ("boolean", default.is_boolean()),
+            Then::Extract(_) => ("string", default.is_string()),
+            _ => {
+                return Some(format!(
+                    "target {name:?} has a default but extracts no values"
+                ))
+            }
+        };
+        (!matches).then(|| format!("default of target {name:?} is not a {expected}"))
+    }
+}
+
+/// Rule selecting items relative to the current one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Selector {
+    /// Items matched by the JSONPath expression, which is tried first since XPath does not start with `$`
+    JsonPath(ParsedJsonPath),
+    /// Items matched by the XPath expression
+    XPath(ParsedXPath),
+    /// Items produced by combining results of other selectors
+    Combined(Combinator),
+}
+
+/// Combination of selectors.
+///
+/// Variants are lowercase since the config lowercases keys
+/// while [untagged](Selector) deserialization matches them exactly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Combinator {
+    /// Items matched by any of the selectors, ordered by their position in the document.
+    ///
+    /// Items which are not part of the document tree, such as attributes, go last.
+    Ordered(Vec<Selector>),
+    /// Items matched by any of the selectors, in the order the selectors are listed.
+    Union(Vec<Selector>),
+    /// Items of the first selector which are matched by each of the others.
+    Intersect(Vec<Selector>),
+    /// Items of the first selector which are matched by none of the others.
+    Difference(Vec<Selector>),
+}
+
+impl Selector {
+    pub fn apply<'tree>(
+        &self,
+        tree: &'tree XpathItemTree,
+        item: XpathItem<'tree>,
+    ) -> Result<XpathItemSet<'tree>, SelectError> {
+        match self {
+            Self::JsonPath(_) => Err(SelectError::Unsupported(
+                "JSONPath selectors cannot be applied to HTML documents",
+            )),
+            Self::XPath(path) => Ok(path.xpath().apply_to_item(tree, item)?),
+            Self::Combined(Combinator::Ordered(selectors)) => {
+                let mut items = Vec::new();
+                for selector in selectors {
+                    items.extend(selector.apply(tree, item.clone())?);
+                }
+                // Tree nodes are ordered by their IDs which follow the document order.
+                items.sort();
+                items.dedup();
+                Ok(items.into_iter().collect())
+            }
+            Self::Combined(Combinator::Union(selectors)) => {
+                let mut items = XpathItemSet::new();
+                for selector in selectors {
+                    items.extend(selector.apply(tree, item.clone())?);
+                }
+                Ok(items)
+            }
+            Self::Combined(Combinator::Intersect(selectors)) => {
+                Self::filter_first(selectors, tree, item, true)
+            }
+            Self::Combined(Combinator::Difference(selectors)) => {
+                Self::filter_first(selectors, tree, item, false)
+            }
+        }
+    }
+
+    /// Checks if the selector is applicable to documents of the format.
+    fn supports(&self, format: DocumentFormat) -> bool {
+        match self {
+            Self::JsonPath(_) => format == DocumentFormat::Json,
+            Self::XPath(_) => format == DocumentFormat::Html,
+            Self::Combined(
+                Combinator::Ordered(selectors)
+                | Combinator::Union(selectors)
+                | Combinator::Intersect(selectors)
+                | Combinator::Difference(selectors),
+            ) => selectors.iter().all(|selector| selector.supports(format)),
+        }
+    }
+
+    /// Keeps items of the first selector depending on whether each of the others matches them.
+    fn filter_first<'tree>(
+        selectors: &[Self],
+        tree: &'tree XpathItemTree,
+        item: XpathItem<'tree>,
+        matched: bool,
+    ) -> Result<XpathItemSet<'tree>, SelectError> {
+        let mut selectors = selectors.iter();
+        let mut items: IndexSet<_> = match selectors.next() {
+            Some(selector) => selector.apply(tree, item.clone())?.into_iter().collect(),
+            None => IndexSet::new(),
+        };
+        for selector in selectors {
+            let other: IndexSet<_> = selector.apply(tree, item.clone())?.into_iter().collect();
+            items.retain(|item| other.contains(item) == matched);
+        }
+        Ok(items.into())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub enum Then {
     Get(Targets),
     Extract(ValueExtractor),
+    /// Collect canonical URL, robots directives, title, description and Open Graph tags.
+    Seo,
+    /// Pair labels with values by their positions.
+    Zip(Zip),
+    /// Map the named capture groups of the regular expression to their matches in the text of each item.
+    RegexCaptures {
+        pattern: ParsedRegex,
+    },
+    /// Collect the fields of a product found relative to each item, normalizing their values.
+    Product(Product),
+}
+
+/// Selectors of the fields of a [product](Then::Product), applied relative to each item.
+///
+/// Fields without a selector or without a text found by it are [unknown](Value::Unknown).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Product {
+    #[serde(default)]
+    pub name: Option<Selector>,
+    /// Text of the price, in which the currency is also looked for unless it has its own selector
+    #[serde(default)]
+    pub price: Option<Selector>,
+    /// Text with the symbol or the ISO 4217 code of the currency
+    #[serde(default)]
+    pub currency: Option<Selector>,
+    /// Text of the availability, such as `In stock` or a schema.org URL like `https://schema.org/InStock`
+    #[serde(default)]
+    pub availability: Option<Selector>,
+    /// URL of the image, which is resolved against the URL of the page
+    #[serde(default)]
+    pub image: Option<Selector>,
+    #[serde(default)]
+    pub sku: Option<Selector>,
+    /// Convention of writing the price
+    #[serde(default)]
+    pub locale: NumberLocale,
+}
+
+impl Product {
+    fn selectors(&self) -> impl Iterator<Item = &Selector> {
+        [
+            &self.name,
+            &self.price,
+            &self.currency,
+            &self.availability,
+            &self.image,
+            &self.sku,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Selectors of labels and values which are paired by index (`labels[i] → values[i]`).
+///
+/// Both selectors are applied relative to each item, and the text of each label becomes a key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Zip {
+    pub labels: Selector,
+    pub values: Selector,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub enum ValueExtractor {
+    /// Get the raw content of each text node.
     Text,
+    /// Get the whole text content of each item with its whitespace normalized.
+    ///
+    /// It is a separate variant since `Text` is configured as a plain string which cannot carry options.
+    NormalizedText {
+        /// Whether leading and trailing whitespace should be removed
+        #[serde(default = "ValueExtractor::default_normalize")]
+        trim: bool,
+        /// Whether each run of whitespace should be replaced with a single space
+        #[serde(default = "ValueExtractor::default_normalize")]
+        collapse_whitespace: bool,
+    },
+    /// Parse the whole text content of each item as a JSON document.
+    Json,
+    /// Count the items, producing a single number regardless of their content.
+    Count,
+    /// Check whether there are any items, producing a single boolean.
+    Exists,
+    /// Join the whole text of all items into a single value.
+    Join {
+        #[serde(default)]
+        separator: String,
+        /// Value produced if there are no items with text
+        #[serde(default)]
+        on_empty: OnEmptyJoin,
+    },
+    /// Serialize each element back to HTML, only its content if `inner` is set.
+    Html {
+        #[serde(default)]
+        inner: bool,
+    },
+    /// Get the value of the named attribute of each element.
+    ///
+    /// Attribute items matched by the path are taken as is if they have the same name.
+    Attribute(String),
+    /// Get the capture group of the regular expression matched against the text of each item.
+    Regex {
+        pattern: ParsedRegex,
+        /// Index of the capture group, the whole match by default
+        #[serde(default)]
+        group: usize,
+    },
+    /// Parse the first number in the text of each item, ignoring currency symbols and thousands separators.
+    Number {
+        #[serde(default)]
+        locale: NumberLocale,
+    },
+    /// Parse the text of each item as a date and time, normalizing it to RFC 3339.
+    DateTime {
+        /// [`chrono` format](chrono::format::strftime) of the text, which may lack the time or the offset
+        format: String,
+        /// Offset of the text lacking one, UTC by default
+        #[serde(default)]
+        tz: TimeZone,
+    },
+}
+
+/// Deterministic operation on an extracted value.
+///
+/// Transforms of text leave other values as they are,
+/// while the ones failing to find what they need in the text produce [unknown](Value::Unknown) values.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Transform {
+    /// Remove leading and trailing whitespace.
+    Trim,
+    Lowercase,
+    Uppercase,
+    /// Replace all occurrences of the text.
+    Replace {
+        from: String,
+        #[serde(default)]
+        to: String,
+    },
+    /// Take the characters starting at the index, up to the length if it is given.
+    Substring {
+        #[serde(default)]
+        start: usize,
+        #[serde(default)]
+        length: Option<usize>,
+    },
+    /// Remove the prefix if the text starts with it.
+    StripPrefix(String),
+    /// Remove the suffix if the text ends with it.
+    StripSuffix(String),
+    /// Replace all matches of the regular expression, referring to its capture groups like `$1`.
+    RegexReplace {
+        pattern: ParsedRegex,
+        #[serde(default)]
+        replacement: String,
+    },
+    /// Get the capture group of the first match of the regular expression.
+    RegexCapture {
+        pattern: ParsedRegex,
+        /// Index of the capture group, the whole match by default
+        #[serde(default)]
+        group: usize,
+    },
+    /// Parse the first number in the text as [the number extractor](ValueExtractor::Number) does.
+    ParseNumber {
+        #[serde(default)]
+        locale: NumberLocale,
+    },
+}
+
+impl Transform {
+    pub fn apply<'tree>(&self, value: Value<'tree>) -> Value<'tree> {
+        let Value::String(text) = value else {
+            return value;
+        };
+        match self {
+            Self::Trim => Value::String(slice(text, str::trim)),
+            Self::Lowercase => Value::String(Cow::Owned(text.to_lowercase())),
+            Self::Uppercase => Value::String(Cow::Owned(text.to_uppercase())),
+            Self::Replace { from, to } if !from.is_empty() && text.contains(from.as_str()) => {
+                Value::String(Cow::Owned(text.replace(from.as_str(), to)))
+            }
+            Self::Replace { .. } => Value::String(text),
+            Self::Substring { start, length } => Value::String(slice(text, |text| {
+                let byte = |chars| {
+                    text.char_indices()
+                        .nth(chars)
+                        .map_or(text.len(), |(i, _)| i)
+                };
+                let begin = byte(*start);
+                let end = length.map_or(text.len(), |length| byte(start + length));
+                &text[begin..end]
+            })),
+            Self::StripPrefix(prefix) => Value::String(slice(text, |text| {
+                text.strip_prefix(prefix.as_str()).unwrap_or(text)
+            })),
+            Self::StripSuffix(suffix) => Value::String(slice(text, |text| {
+                text.strip_suffix(suffix.as_str()).unwrap_or(text)
+            })),
+            Self::RegexReplace {
+                pattern: ParsedRegex(regex),
+                replacement,
+            } if regex.is_match(&text) => Value::String(Cow::Owned(
+                regex.replace_all(&text, replacement.as_str()).into_owned(),
+            )),
+            Self::RegexReplace { .. } => Value::String(text),
+            Self::RegexCapture { pattern, group } => ValueExtractor::Regex {
+                pattern: pattern.clone(),
+                group: *group,
+            }
+            .extract_text(text),
+            Self::ParseNumber { locale } => {
+                ValueExtractor::Number { locale: *locale }.extract_text(text)
+            }
+        }
+    }
+}
+
+/// Narrows the text to a part of it, borrowing the part if the text is borrowed.
+fn slice<'tree>(
+    text: Cow<'tree, str>,
+    part: impl for<'a> FnOnce(&'a str) -> &'a str,
+) -> Cow<'tree, str> {
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(part(text)),
+        Cow::Owned(text) => Cow::Owned(part(&text).to_owned()),
+    }
+}
+
+/// Value of a [join](ValueExtractor::Join) of no items.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum OnEmptyJoin {
+    #[default]
+    Unknown,
+    EmptyString,
+}
+
+/// Fixed offset from UTC, configured as `UTC` or like `+03:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeZone(pub FixedOffset);
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self(FixedOffset::east_opt(0).expect("zero offset should be valid"))
+    }
+}
+
+impl TryFrom<String> for TimeZone {
+    type Error = String;
+
+    fn try_from(tz: String) -> Result<Self, Self::Error> {
+        if tz.eq_ignore_ascii_case("utc") {
+            return Ok(Self::default());
+        }
+        tz.parse()
+            .map(Self)
+            .map_err(|error| format!("invalid time zone {tz:?}: {error}"))
+    }
+}
+
+impl TimeZone {
+    /// Parses the date and time, which is taken to be in this time zone unless the format has an offset.
+    ///
+    /// Dates without time are taken at midnight.
+    pub fn parse(self, text: &str, format: &str) -> Option<DateTime<FixedOffset>> {
+        if let Ok(date_time) = DateTime::parse_from_str(text, format) {
+            return Some(date_time);
+        }
+        let naive = NaiveDateTime::parse_from_str(text, format)
+            .or_else(|_| {
+                NaiveDate::parse_from_str(text, format).map(|date| date.and_time(NaiveTime::MIN))
+            })
+            .ok()?;
+        naive.and_local_timezone(self.0).single()
+    }
+}
+
+/// Convention of writing numbers which determines their separators.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum NumberLocale {
+    /// `1,234.5`
+    #[default]
+    En,
+    /// `1.234,5`
+    De,
+    /// `1 234,5`, separating thousands with any kind of space
+    Ru,
+    /// `1'234.5`
+    Ch,
+}
+
+impl NumberLocale {
+    /// Thousands separators and the decimal separator.
+    fn separators(self) -> (&'static [char], char) {
+        match self {
+            Self::En => (&[','], '.'),
+            Self::De => (&['.'], ','),
+            Self::Ru => (&[' ', '\u{a0}', '\u{202f}'], ','),
+            Self::Ch => (&['\'', '’'], '.'),
+        }
+    }
+
+    /// Parses the first number found in the text.
+    ///
+    /// The number is negative if it is preceded by a minus sign, possibly followed by a currency symbol.
+    pub fn parse(self, text: &str) -> Option<f64> {
+        let (thousands, decimal) = self.separators();
+        let start = text.find(|c: char| c.is_ascii_digit())?;
+        let negative = text[..start]
+            .trim_end_matches(|c: char| {
+                !c.is_alphanumeric() && !c.is_whitespace() && !matches!(c, '-' | '−')
+            })
+            .ends_with(['-', '−']);
+
+        let mut number = String::from(if negative { "-" } else { "" });
+        for c in text[start..].chars() {
+            match c {
+                '0'..='9' => number.push(c),
+                c if c == decimal => number.push('.'),
+                c if thousands.contains(&c) => {}
+                _ => break,
+            }
+        }
+        number.parse().ok()
+    }
 }
 
 impl ValueExtractor {
-    pub fn extract<'tree>(&self, items: XpathItemSet<'tree>) -> Vec<Value<'tree>> {
+    fn default_normalize() -> bool {
+        true
+    }
+
+    pub fn extract<'tree>(
+        &self,
+        tree: &'tree XpathItemTree,
+        items: XpathItemSet<'tree>,
+    ) -> Vec<Value<'tree>> {
         use skyscraper::xpath::grammar::data_model::*;
         match self {
             Self::Text => items
@@ -74,18 +923,264 @@ impl ValueExtractor {
                     item.as_node()
                         .and_then(Node::as_tree_node)
                         .and_then(|tree| tree.data.as_text_node())
-                        .map(|item| Value::String(&item.content))
+                        .map(|item| Value::String(Cow::Borrowed(&item.content)))
                         .unwrap_or(Value::Unknown)
                 })
                 .collect(),
+            Self::Attribute(name) => items
+                .iter()
+                .map(|item| {
+                    let value = match item.as_node() {
+                        Ok(Node::TreeNode(node)) => match node.data {
+                            XpathItemTreeNodeData::ElementNode(element) => {
+                                element.get_attribute(name).map(Cow::Borrowed)
+                            }
+                            _ => None,
+                        },
+                        _ => as_attribute(item)
+                            .filter(|attribute| attribute.name == *name)
+                            .map(|attribute| Cow::Owned(attribute.value.clone())),
+                    };
+                    value.map_or(Value::Unknown, Value::String)
+                })
+                .collect(),
+            Self::Count => vec![Value::Number(items.len() as f64)],
+            Self::Exists => vec![Value::Bool(!items.is_empty())],
+            Self::Join {
+                separator,
+                on_empty,
+            } => vec![join_texts(
+                items.iter().filter_map(|item| item_text(tree, item)),
+                separator,
+                *on_empty,
+            )],
+            Self::Html { inner } => items
+                .iter()
+                .map(|item| {
+                    item_html(tree, item, *inner)
+                        .map_or(Value::Unknown, |html| Value::String(Cow::Owned(html)))
+                })
+                .collect(),
+            extractor => items
+                .iter()
+                .map(|item| {
+                    item_text(tree, item)
+                        .map_or(Value::Unknown, |text| extractor.extract_text(text))
+                })
+                .collect(),
+        }
+    }
+
+    /// Extracts the value from the whole text of an item.
+    ///
+    /// [`Text`](Self::Text), [`Attribute`](Self::Attribute), [`Join`](Self::Join)
+    /// and [`Html`](Self::Html) extractors take the text as is,
+    /// while [`Count`](Self::Count) and [`Exists`](Self::Exists) only take its presence into account.
+    pub fn extract_text<'tree>(&self, text: Cow<'tree, str>) -> Value<'tree> {
+        match self {
+            Self::Count => Value::Number(1.0),
+            Self::Exists => Value::Bool(true),
+            Self::Text | Self::Attribute(_) | Self::Join { .. } | Self::Html { .. } => {
+                Value::String(text)
+            }
+            Self::NormalizedText {
+                trim,
+                collapse_whitespace,
+            } => Value::String(Cow::Owned(normalize_whitespace(
+                &text,
+                *trim,
+                *collapse_whitespace,
+            ))),
+            Self::Json => serde_json::from_str(&text).map_or_else(
+                |error| {
+                    debug!("Failed to parse item as JSON: {error}");
+                    Value::Unknown
+                },
+                Value::Json,
+            ),
+            Self::Regex {
+                pattern: ParsedRegex(regex),
+                group,
+            } => {
+                let capture = |text: &str| Some(regex.captures(text)?.get(*group)?.range());
+                let value = match text {
+                    Cow::Borrowed(text) => capture(text).map(|range| Cow::Borrowed(&text[range])),
+                    Cow::Owned(text) => {
+                        capture(&text).map(|range| Cow::Owned(text[range].to_owned()))
+                    }
+                };
+                value.map_or(Value::Unknown, Value::String)
+            }
+            Self::Number { locale } => locale.parse(&text).map_or_else(
+                || {
+                    debug!("Failed to parse {text:?} as a number");
+                    Value::Unknown
+                },
+                Value::Number,
+            ),
+            Self::DateTime { format, tz } => match tz.parse(text.trim(), format) {
+                Some(date_time) => Value::String(Cow::Owned(date_time.to_rfc3339())),
+                None => {
+                    warn!("Failed to parse {text:?} as a date and time in format {format:?}");
+                    Value::Unknown
+                }
+            },
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Joins the texts with the separator, producing the configured value if there are none.
+pub fn join_texts<'tree>(
+    texts: impl Iterator<Item = Cow<'tree, str>>,
+    separator: &str,
+    on_empty: OnEmptyJoin,
+) -> Value<'tree> {
+    let texts: Vec<_> = texts.collect();
+    if texts.is_empty() {
+        return match on_empty {
+            OnEmptyJoin::Unknown => Value::Unknown,
+            OnEmptyJoin::EmptyString => Value::String(Cow::Borrowed("")),
+        };
+    }
+    Value::String(Cow::Owned(texts.join(separator)))
+}
+
+pub fn normalize_whitespace(text: &str, trim: bool, collapse: bool) -> String {
+    let text = if trim { text.trim() } else { text };
+    if !collapse {
+        return text.to_owned();
+    }
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                normalized.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            normalized.push(c);
+            in_whitespace = false;
+        }
+    }
+    normalized
+}
+
+/// Gets the attribute node which the item is.
+fn as_attribute<'item>(item: &'item XpathItem) -> Option<&'item AttributeNode> {
+    item.as_node()
+        .and_then(Node::as_non_tree_node)
+        .and_then(NonTreeXpathNode::as_attribute_node)
+        .ok()
+}
+
+/// Gets the whole text content of the item, be it a text node, an element or an attribute.
+pub fn item_text<'tree>(
+    tree: &'tree XpathItemTree,
+    item: &XpathItem<'tree>,
+) -> Option<Cow<'tree, str>> {
+    match item.as_node().ok()? {
+        Node::TreeNode(node) => match node.data {
+            XpathItemTreeNodeData::TextNode(text) => Some(Cow::Borrowed(&text.content)),
+            XpathItemTreeNodeData::ElementNode(_) => Some(Cow::Owned(node.all_text(tree))),
+            _ => None,
+        },
+        Node::NonTreeNode(node) => node
+            .as_attribute_node()
+            .ok()
+            .map(|attribute| Cow::Owned(attribute.value.clone())),
+    }
+}
+
+/// Elements which have no content and thus no closing tag.
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Gets the HTML markup of the item, which is only the markup of its children if `inner` is set.
+///
+/// Attributes are taken as their values.
+fn item_html(tree: &XpathItemTree, item: &XpathItem, inner: bool) -> Option<String> {
+    match item.as_node().ok()? {
+        Node::TreeNode(node) => {
+            let mut html = String::new();
+            if inner {
+                for child in node.children(tree) {
+                    write_html(tree, &child, &mut html);
+                }
+            } else {
+                write_html(tree, node, &mut html);
+            }
+            Some(html)
+        }
+        Node::NonTreeNode(node) => node
+            .as_attribute_node()
+            .ok()
+            .map(|attribute| attribute.value.clone()),
+    }
+}
+
+/// Reconstructs the markup of the node and its descendants.
+fn write_html(tree: &XpathItemTree, node: &XpathItemTreeNode, html: &mut String) {
+    match node.data {
+        XpathItemTreeNodeData::ElementNode(element) => {
+            html.push('<');
+            html.push_str(&element.name);
+            for AttributeNode { name, value } in &element.attributes {
+                html.push_str(&format!(r#" {name}="{}""#, value.replace('"', "&quot;")));
+            }
+            html.push('>');
+            if VOID_ELEMENTS.contains(&element.name.as_str()) {
+                return;
+            }
+            for child in node.children(tree) {
+                write_html(tree, &child, html);
+            }
+            html.push_str(&format!("</{}>", element.name));
+        }
+        XpathItemTreeNodeData::TextNode(text) => html.push_str(
+            &text
+                .content
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;"),
+        ),
+        XpathItemTreeNodeData::CommentNode(comment) => {
+            html.push_str(&format!("<!--{}-->", comment.content));
+        }
+        XpathItemTreeNodeData::DocumentNode(_) => {
+            for child in node.children(tree) {
+                write_html(tree, &child, html);
+            }
+        }
+        XpathItemTreeNodeData::PINode(_) => {}
+    }
+}
+
+/// Unknown values are serialized as `null`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
 pub enum Value<'tree> {
     Unknown,
-    String(&'tree str),
+    String(Cow<'tree, str>),
+    Number(f64),
+    Bool(bool),
+    Json(serde_json::Value),
+}
+
+impl Value<'_> {
+    /// Detaches the value from the tree it was found in.
+    pub fn into_owned(self) -> Value<'static> {
+        match self {
+            Value::Unknown => Value::Unknown,
+            Value::String(value) => Value::String(Cow::Owned(value.into_owned())),
+            Value::Number(value) => Value::Number(value),
+            Value::Bool(value) => Value::Bool(value),
+            Value::Json(value) => Value::Json(value),
+        }
+    }
 }
 
 impl fmt::Display for Value<'_> {
@@ -93,65 +1188,2292 @@ impl fmt::Display for Value<'_> {
         match self {
             Value::Unknown => f.write_str("?"),
             Value::String(value) => f.write_str(value),
+            Value::Number(value) => write!(f, "{value}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Json(value) => write!(f, "{value}"),
         }
     }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub enum Continuation {
+    /// Addresses of the next pages are the values of the attributes at the path.
     Ref(ParsedXPath),
+    /// The next page is requested with the query parameter increased by the step,
+    /// as long as the current page has results and the number of pages does not exceed the maximum.
+    Offset {
+        param: String,
+        /// Value of the parameter on the first page, used if the URL does not have it
+        #[serde(default)]
+        start: u64,
+        #[serde(default = "Continuation::default_step")]
+        step: u64,
+        max_pages: u64,
+    },
 }
 
 impl Continuation {
-    pub fn evaluate(&self, tree: &XpathItemTree) -> Vec<String> {
+    fn default_step() -> u64 {
+        1
+    }
+
+    /// Evaluates addresses of the next pages, which may be relative to the URL of the page.
+    pub fn evaluate(&self, page: Option<&Url>, tree: &XpathItemTree) -> Vec<String> {
         match self {
             Continuation::Ref(path) => {
-                let Ok(items) = path.to_xpath().apply(tree) else {
+                let Ok(items) = path.xpath().apply(tree) else {
                     return vec![];
                 };
 
                 items
                     .iter()
-                    .filter_map(|item| {
-                        item.as_node()
-                            .and_then(Node::as_non_tree_node)
-                            .and_then(NonTreeXpathNode::as_attribute_node)
-                            .map(|node| node.value.clone())
-                            .ok()
-                    })
+                    .filter_map(|item| Some(as_attribute(item)?.value.clone()))
                     .collect()
             }
+            Continuation::Offset {
+                param,
+                start,
+                step,
+                max_pages,
+            } => page
+                .and_then(|page| next_offset(page, param, *start, *step, *max_pages))
+                .map(String::from)
+                .into_iter()
+                .collect(),
         }
     }
 }
 
-/// [`XPath`] internally stored as a [`String`].
-#[derive(Debug, Clone)]
-pub struct ParsedXPath(String);
+/// Address of the next page which cannot be resolved to a URL.
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("invalid continuation {href:?}: {error}")]
+pub struct InvalidContinuation {
+    pub href: String,
+    pub error: url::ParseError,
+}
 
-impl fmt::Display for ParsedXPath {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let Self(xpath) = self;
-        f.write_str(xpath)
+/// URL which relative addresses of the page are resolved against,
+/// specified by its `<base>` element and defaulting to the URL of the page.
+fn document_base(page: &Url, tree: &XpathItemTree) -> Url {
+    let href = xpath::parse("//base/@href")
+        .expect("base path should be valid")
+        .apply(tree)
+        .ok()
+        .and_then(|items| {
+            items
+                .iter()
+                .find_map(|item| Some(as_attribute(item)?.value.clone()))
+        });
+    match href.map(|href| page.join(&href)) {
+        Some(Ok(base)) => base,
+        Some(Err(error)) => {
+            warn!("Ignoring invalid base of the page: {error}");
+            page.clone()
+        }
+        None => page.clone(),
     }
 }
 
-impl ParsedXPath {
-    pub fn to_xpath(&self) -> Xpath {
-        xpath::parse(&self.0).expect("Path should have been parsed")
+/// Rewrites the offset parameter of the URL to point to the next page, unless it is the last one.
+fn next_offset(url: &Url, param: &str, start: u64, step: u64, max_pages: u64) -> Option<Url> {
+    let current = url
+        .query_pairs()
+        .find(|(name, _)| name == param)
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(start);
+    let page = current.checked_sub(start)?.checked_div(step)?;
+    if page + 1 >= max_pages {
+        debug!("Reached the maximal number of pages");
+        return None;
     }
+
+    let query: Vec<_> = url
+        .query_pairs()
+        .filter(|(name, _)| name != param)
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(query)
+        .append_pair(param, &(current + step).to_string());
+    Some(url)
 }
 
-impl<'de> Deserialize<'de> for ParsedXPath {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        use serde::de::Error;
+/// Merging of the records of the latest results of another job into the records having the same key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Join {
+    /// Name of the other job
+    pub job: String,
+    /// Name of the target whose values are the key of the records of both jobs
+    pub by: String,
+}
 
-        let raw = String::deserialize(deserializer)?;
-        xpath::parse(&raw)
-            .map_err(|error| Error::custom(format_args!("failed to parse XPath: {error}")))
-            .map(|_| Self(raw))
+/// Condition which a page has to satisfy for its [continuation](Continuation) to be followed.
+#[derive(Debug, Clone, Deserialize)]
+pub enum ContinuationCondition {
+    /// The XPath expression has a `true` effective boolean value, e.g. it matches something.
+    XPath(ParsedXPath),
+    /// The path matches something, and the text of each matched item lies within the inclusive bounds.
+    ///
+    /// Values are compared as numbers if both of them are numeric and as strings otherwise,
+    /// so that ISO 8601 dates are compared chronologically.
+    Within {
+        path: ParsedXPath,
+        #[serde(default)]
+        min: Option<String>,
+        #[serde(default)]
+        max: Option<String>,
+    },
+}
+
+impl ContinuationCondition {
+    pub fn holds(&self, tree: &XpathItemTree) -> bool {
+        match self {
+            Self::XPath(path) => path.xpath().apply(tree).is_ok_and(|items| items.boolean()),
+            Self::Within { path, min, max } => {
+                let Ok(items) = path.xpath().apply(tree) else {
+                    return false;
+                };
+                !items.is_empty()
+                    && items.iter().all(|item| {
+                        let Some(text) = item_text(tree, item) else {
+                            return false;
+                        };
+                        let text = text.trim();
+                        min.as_deref().map_or(true, |min| {
+                            matches!(
+                                compare(text, min),
+                                Some(cmp::Ordering::Greater | cmp::Ordering::Equal)
+                            )
+                        }) && max.as_deref().map_or(true, |max| {
+                            matches!(
+                                compare(text, max),
+                                Some(cmp::Ordering::Less | cmp::Ordering::Equal)
+                            )
+                        })
+                    })
+            }
+        }
+    }
+}
+
+/// Compares the values as numbers if both of them are numeric and as strings otherwise.
+fn compare(left: &str, right: &str) -> Option<cmp::Ordering> {
+    match (left.parse::<f64>(), right.parse::<f64>()) {
+        (Ok(left), Ok(right)) => left.partial_cmp(&right),
+        _ => Some(left.cmp(right)),
+    }
+}
+
+/// Retrying of failed requests with exponentially growing delays between attempts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Retry {
+    /// Maximal number of attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the second attempt
+    pub delay: Duration,
+    /// Factor by which the delay grows after each attempt
+    #[serde(default = "Retry::default_multiplier")]
+    pub multiplier: f64,
+    /// Delay which the growing delays do not exceed
+    #[serde(default = "Retry::default_max_delay")]
+    pub max_delay: Duration,
+    /// Maximal delay requested by the `Retry-After` header which is honored
+    #[serde(default = "Retry::default_max_retry_after")]
+    pub max_retry_after: Duration,
+}
+
+impl Retry {
+    fn default_multiplier() -> f64 {
+        2.0
+    }
+
+    fn default_max_delay() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_max_retry_after() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    /// Delays before each of the attempts following the first one, capped at the [maximal one](Self::max_delay).
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + '_ {
+        (0..self.attempts.saturating_sub(1)).map(|retry| {
+            let factor = self
+                .multiplier
+                .powi(i32::try_from(retry).unwrap_or(i32::MAX));
+            Duration::try_from_secs_f64(self.delay.as_secs_f64() * factor)
+                .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+        })
+    }
+}
+
+/// HTTP or SOCKS5 proxy which requests are routed through.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Proxy {
+    /// URL of the proxy, e.g. `http://proxy.corp:3128` or `socks5://127.0.0.1:1080`
+    pub url: Url,
+    /// Requests which should be routed through the proxy
+    #[serde(default)]
+    pub scheme: ProxyScheme,
+    #[serde(default)]
+    pub username: Option<Secret>,
+    #[serde(default)]
+    pub password: Option<Secret>,
+}
+
+/// Schemes of the requests which are routed through a [proxy](Proxy).
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum ProxyScheme {
+    #[default]
+    All,
+    Http,
+    Https,
+}
+
+impl Proxy {
+    pub fn to_reqwest(&self) -> reqwest::Result<reqwest::Proxy> {
+        let url = self.url.clone();
+        let proxy = match self.scheme {
+            ProxyScheme::All => reqwest::Proxy::all(url),
+            ProxyScheme::Http => reqwest::Proxy::http(url),
+            ProxyScheme::Https => reqwest::Proxy::https(url),
+        }?;
+        Ok(match &self.username {
+            Some(username) => proxy.basic_auth(
+                username.expose(),
+                self.password.as_ref().map_or("", Secret::expose),
+            ),
+            None => proxy,
+        })
+    }
+}
+
+/// Cookies set by the responses and sent with the following requests of a job.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Session {
+    /// File to which the cookies are persisted after each poll and from which they are loaded on start
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+/// Following of redirects by requests of a job.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Redirects {
+    /// Maximal number of redirects followed by a single request, none are followed if it is zero
+    #[serde(default = "Redirects::default_max")]
+    pub max: usize,
+    /// Whether redirects to hosts other than the one of the request are followed
+    #[serde(default = "Redirects::default_cross_host")]
+    pub cross_host: bool,
+}
+
+impl Default for Redirects {
+    fn default() -> Self {
+        Self {
+            max: Self::default_max(),
+            cross_host: Self::default_cross_host(),
+        }
+    }
+}
+
+impl Redirects {
+    fn default_max() -> usize {
+        10
+    }
+
+    fn default_cross_host() -> bool {
+        true
+    }
+}
+
+/// Cache of successful responses on disk, keyed by the method, URL and body of their requests.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponseCache {
+    /// Directory in which responses are stored
+    pub path: PathBuf,
+    /// Duration for which a stored response is served instead of performing the request
+    pub ttl: Duration,
+}
+
+/// Bounded queue between fetching results and writing them into sinks,
+/// so that slow sinks hold back fetching instead of accumulating results in memory.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SinkQueue {
+    /// Maximal number of results waiting to be written
+    #[serde(default = "SinkQueue::default_capacity")]
+    pub capacity: NonZeroUsize,
+    /// What happens to new results when the queue is full
+    #[serde(default)]
+    pub when_full: WhenFull,
+}
+
+impl Default for SinkQueue {
+    fn default() -> Self {
+        Self {
+            capacity: Self::default_capacity(),
+            when_full: WhenFull::default(),
+        }
+    }
+}
+
+impl SinkQueue {
+    fn default_capacity() -> NonZeroUsize {
+        NonZeroUsize::new(16).expect("capacity should be positive")
+    }
+}
+
+/// Handling of results which do not fit into a full [sink queue](SinkQueue).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum WhenFull {
+    /// Fetching waits until the sinks catch up
+    #[default]
+    Wait,
+    /// Results are dropped with a warning
+    Drop,
+}
+
+/// Authentication applied to requests.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Auth {
+    Basic { username: String, password: Secret },
+    Bearer { token: Secret },
+}
+
+/// Secret value which is never printed.
+///
+/// It is configured either inline or as `{ env: "NAME" }`,
+/// in which case it is read from the environment variable when the config is loaded.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "SecretSource")]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SecretSource {
+    Env { env: String },
+    Value(String),
+}
+
+impl SecretSource {
+    /// Resolves the secret, reading environment variables with `var`.
+    fn resolve(self, var: &impl Fn(&str) -> Result<String, VarError>) -> Result<Secret, String> {
+        match self {
+            Self::Env { env } => var(&env)
+                .map(Secret)
+                .map_err(|error| format!("failed to read secret from {env:?}: {error}")),
+            Self::Value(value) => Ok(Secret(value)),
+        }
+    }
+}
+
+impl TryFrom<SecretSource> for Secret {
+    type Error = String;
+
+    fn try_from(source: SecretSource) -> Result<Self, Self::Error> {
+        source.resolve(&|name| std::env::var(name))
+    }
+}
+
+/// Body of requests.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "BodySource")]
+pub enum Body {
+    /// Text sent as is
+    Raw(String),
+    /// Value sent as JSON.
+    ///
+    /// Since the config lowercases keys, a value with other keys is configured as a string of JSON text.
+    Json(serde_json::Value),
+    /// Fields sent URL-encoded as a form
+    Form(IndexMap<String, Secret>),
+}
+
+impl fmt::Debug for Body {
+    /// Contents of JSON bodies are not printed since they may contain secrets.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Raw(text) => f.debug_tuple("Raw").field(text).finish(),
+            Self::Json(_) => f.write_str("Json(***)"),
+            Self::Form(fields) => f.debug_tuple("Form").field(fields).finish(),
+        }
+    }
+}
+
+/// Variants are lowercase since the config lowercases keys
+/// while [untagged](BodySource) deserialization matches them exactly.
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TypedBody {
+    Json(serde_json::Value),
+    Form(Vec<FormFieldSource>),
+}
+
+/// Field of a [form](Body::Form), configured as an entry of a list
+/// since the config lowercases keys, while names of fields are case-sensitive.
+#[derive(Deserialize)]
+struct FormFieldSource {
+    name: String,
+    value: SecretSource,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BodySource {
+    Raw(String),
+    Typed(TypedBody),
+}
+
+impl BodySource {
+    /// Resolves the body, reading environment variables with `var`.
+    fn resolve(self, var: &impl Fn(&str) -> Result<String, VarError>) -> Result<Body, String> {
+        Ok(match self {
+            Self::Raw(text) => Body::Raw(text),
+            Self::Typed(TypedBody::Json(mut value)) => {
+                if let serde_json::Value::String(text) = &value {
+                    value = serde_json::from_str(text)
+                        .map_err(|error| format!("invalid JSON body: {error}"))?;
+                }
+                resolve_env(&mut value, var)?;
+                Body::Json(value)
+            }
+            Self::Typed(TypedBody::Form(fields)) => Body::Form(
+                fields
+                    .into_iter()
+                    .map(|FormFieldSource { name, value }| Ok((name, value.resolve(var)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+        })
+    }
+}
+
+impl TryFrom<BodySource> for Body {
+    type Error = String;
+
+    fn try_from(source: BodySource) -> Result<Self, Self::Error> {
+        source.resolve(&|name| std::env::var(name))
+    }
+}
+
+/// Replaces objects of the form `{ env: "NAME" }` with the values of the environment variables.
+fn resolve_env(
+    value: &mut serde_json::Value,
+    var: &impl Fn(&str) -> Result<String, VarError>,
+) -> Result<(), String> {
+    match value {
+        serde_json::Value::Object(object) => {
+            if let (1, Some(serde_json::Value::String(env))) = (object.len(), object.get("env")) {
+                *value = var(env)
+                    .map_err(|error| format!("failed to read body value from {env:?}: {error}"))?
+                    .into();
+                return Ok(());
+            }
+            object
+                .values_mut()
+                .try_for_each(|value| resolve_env(value, var))
+        }
+        serde_json::Value::Array(array) => array
+            .iter_mut()
+            .try_for_each(|value| resolve_env(value, var)),
+        _ => Ok(()),
+    }
+}
+
+/// Request headers validated when the config is loaded.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "IndexMap<String, String>")]
+pub struct StaticHeaders(pub Vec<(HeaderName, HeaderValue)>);
+
+impl TryFrom<IndexMap<String, String>> for StaticHeaders {
+    type Error = String;
+
+    fn try_from(headers: IndexMap<String, String>) -> Result<Self, Self::Error> {
+        headers
+            .into_iter()
+            .map(|(name, value)| {
+                let value = HeaderValue::try_from(value)
+                    .map_err(|error| format!("invalid value of header {name:?}: {error}"))?;
+                let name = HeaderName::try_from(name)
+                    .map_err(|error| format!("invalid header name: {error}"))?;
+                Ok((name, value))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// Encoding of documents configured by its [label](https://encoding.spec.whatwg.org/#names-and-labels).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TextEncoding(pub &'static Encoding);
+
+impl TryFrom<String> for TextEncoding {
+    type Error = String;
+
+    fn try_from(label: String) -> Result<Self, Self::Error> {
+        Encoding::for_label(label.trim().as_bytes())
+            .map(Self)
+            .ok_or_else(|| format!("unknown encoding {label:?}"))
+    }
+}
+
+/// Value of the `User-Agent` request header.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct UserAgent(pub HeaderValue);
+
+impl TryFrom<String> for UserAgent {
+    type Error = String;
+
+    fn try_from(user_agent: String) -> Result<Self, Self::Error> {
+        HeaderValue::try_from(user_agent)
+            .map(Self)
+            .map_err(|error| format!("invalid user agent: {error}"))
+    }
+}
+
+/// Value of the `Accept-Language` request header, such as `en-US,en;q=0.9`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct AcceptLanguage(pub HeaderValue);
+
+impl TryFrom<String> for AcceptLanguage {
+    type Error = String;
+
+    fn try_from(languages: String) -> Result<Self, Self::Error> {
+        HeaderValue::try_from(languages)
+            .map(Self)
+            .map_err(|error| format!("invalid accept language: {error}"))
+    }
+}
+
+/// Request headers whose values are templates rendered right before each request.
+///
+/// Supported placeholders are:
+/// - `{timestamp}`: current UNIX timestamp in seconds;
+/// - `{uuid}`: random UUID (v4), unique for each request;
+/// - `{counter}`: number of requests performed by the job so far, starting from `0`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct DynamicHeaders {
+    templates: IndexMap<String, String>,
+    #[serde(skip)]
+    counter: AtomicU64,
+}
+
+impl Clone for DynamicHeaders {
+    fn clone(&self) -> Self {
+        Self {
+            templates: self.templates.clone(),
+            counter: AtomicU64::new(self.counter.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl DynamicHeaders {
+    /// Renders header values for a new request.
+    pub fn render(&self) -> impl Iterator<Item = (&str, String)> {
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed).to_string();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |timestamp| timestamp.as_secs())
+            .to_string();
+        let uuid = uuid::Uuid::new_v4().to_string();
+
+        self.templates.iter().map(move |(name, template)| {
+            let value = template
+                .replace("{timestamp}", &timestamp)
+                .replace("{uuid}", &uuid)
+                .replace("{counter}", &counter);
+            (name.as_str(), value)
+        })
+    }
+}
+
+/// Query parameters whose values are templates rendered right before each page is requested.
+///
+/// Supported placeholders are:
+/// - `{now}`: current time in RFC 3339 format, in UTC;
+/// - `{date}`: current date in UTC as `YYYY-MM-DD`;
+/// - `{page}`: number of the page within the poll, starting from `1`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct QueryParams(Vec<QueryParam>);
+
+/// Parameter of the [query](QueryParams).
+///
+/// Parameters are configured as a list rather than a map since the config lowercases keys,
+/// while names of parameters are case-sensitive.
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueryParam {
+    pub name: String,
+    /// Template of the value
+    pub value: String,
+}
+
+impl QueryParams {
+    /// Sets the parameters in the URL, overriding the existing ones of the same names in place
+    /// and appending the others, while the rest of the existing parameters is preserved.
+    pub fn apply(&self, url: &Url, page: usize) -> Url {
+        if self.0.is_empty() {
+            return url.clone();
+        }
+        let now = Utc::now();
+        let render = |template: &str| {
+            template
+                .replace(
+                    "{now}",
+                    &now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                )
+                .replace("{date}", &now.format("%Y-%m-%d").to_string())
+                .replace("{page}", &page.to_string())
+        };
+
+        let mut rendered: IndexMap<_, _> = self
+            .0
+            .iter()
+            .map(|QueryParam { name, value }| (name.as_str(), Some(render(value))))
+            .collect();
+        let mut pairs = Vec::new();
+        for (name, value) in url.query_pairs() {
+            match rendered.get_mut(name.as_ref()) {
+                Some(value) => pairs.extend(value.take().map(|value| (name.into_owned(), value))),
+                None => pairs.push((name.into_owned(), value.into_owned())),
+            }
+        }
+        pairs.extend(
+            rendered
+                .into_iter()
+                .filter_map(|(name, value)| Some((name.to_owned(), value?))),
+        );
+
+        let mut url = url.clone();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+        url
+    }
+}
+
+/// Schedule of polls of a resource.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "ScheduleSource")]
+pub enum Schedule {
+    /// Polls happen at the fixed period, starting immediately.
+    Every(Duration),
+    /// Polls happen at the occurrences of the cron expression, evaluated in UTC.
+    Cron(cron::Schedule),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScheduleSource {
+    Every(Duration),
+    Cron(String),
+}
+
+impl TryFrom<ScheduleSource> for Schedule {
+    type Error = String;
+
+    fn try_from(source: ScheduleSource) -> Result<Self, Self::Error> {
+        match source {
+            ScheduleSource::Every(period) => Ok(Self::Every(period)),
+            ScheduleSource::Cron(expression) => expression
+                .parse()
+                .map(Self::Cron)
+                .map_err(|error| format!("invalid cron expression {expression:?}: {error}")),
+        }
+    }
+}
+
+/// Random deviation of the [period](Schedule::Every) of a job.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jitter {
+    /// Maximal deviation of each period in either direction
+    pub max: JitterAmount,
+    /// Seed of the random delays making them reproducible, random by default
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum JitterAmount {
+    /// Fixed duration
+    Fixed(Duration),
+    /// Percentage of the period
+    Percent(Percentage),
+}
+
+/// Number within `0..=100`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "f64")]
+pub struct Percentage(f64);
+
+impl TryFrom<f64> for Percentage {
+    type Error = String;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        if (0.0..=100.0).contains(&value) {
+            Ok(Self(value))
+        } else {
+            Err(format!("percentage {value} is not within 0..=100"))
+        }
+    }
+}
+
+impl Jitter {
+    /// Computes the maximal deviation of the period, which never exceeds the period itself.
+    fn deviation(&self, period: Duration) -> Duration {
+        match self.max {
+            JitterAmount::Fixed(deviation) => deviation.min(period),
+            JitterAmount::Percent(Percentage(percent)) => period.mul_f64(percent / 100.0),
+        }
+    }
+}
+
+/// Delay of the first poll of a job spreading the load of simultaneously started jobs.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum StartDelay {
+    /// Fixed duration
+    Fixed(Duration),
+    /// Duration chosen uniformly within the inclusive bounds each time the job is started
+    Random { min: Duration, max: Duration },
+}
+
+impl StartDelay {
+    /// Chooses the delay, treating the bounds in the wrong order as the upper one only.
+    pub fn sample(self, rng: &mut impl Rng) -> Duration {
+        match self {
+            Self::Fixed(delay) => delay,
+            Self::Random { min, max } => rng.gen_range(min.min(max)..=max),
+        }
+    }
+}
+
+/// Source of the ticks at which a job is polled.
+#[derive(Debug)]
+pub enum Ticker {
+    Interval(Interval),
+    Jittered(Box<JitteredInterval>),
+    Cron(cron::Schedule),
+}
+
+/// Interval whose each period randomly deviates within the jitter, starting immediately.
+///
+/// Delays are computed one by one, so [missed ticks](MissedTicks) are handled with the same strategies
+/// as by [`Interval`] but against the jittered deadlines.
+#[derive(Debug)]
+pub struct JitteredInterval {
+    period: Duration,
+    deviation: Duration,
+    missed_ticks: MissedTicks,
+    rng: StdRng,
+    next: Instant,
+}
+
+impl JitteredInterval {
+    /// Computes the next delay, which lies within `period ± deviation`.
+    fn delay(&mut self) -> Duration {
+        self.period - self.deviation + self.rng.gen_range(Duration::ZERO..=self.deviation * 2)
+    }
+
+    async fn tick(&mut self) {
+        let deadline = self.next;
+        let now = Instant::now();
+        time::sleep_until(deadline).await;
+        self.next = match self.missed_ticks {
+            MissedTicks::Burst => deadline + self.delay(),
+            MissedTicks::Delay => deadline.max(now) + self.delay(),
+            MissedTicks::Skip => {
+                let mut next = deadline;
+                loop {
+                    let delay = self.delay();
+                    next += delay;
+                    if next > now || delay.is_zero() {
+                        break next;
+                    }
+                }
+            }
+        };
+        debug!("Next poll is scheduled in {:?}", self.next - Instant::now());
+    }
+}
+
+impl Ticker {
+    /// Waits for the next tick.
+    pub async fn tick(&mut self) {
+        match self {
+            Self::Interval(interval) => {
+                interval.tick().await;
+            }
+            Self::Jittered(interval) => interval.tick().await,
+            Self::Cron(schedule) => {
+                let Some(next) = schedule.upcoming(Utc).next() else {
+                    debug!("Schedule has no more occurrences");
+                    return std::future::pending().await;
+                };
+                debug!("Next poll is scheduled at {next}");
+                let delay = (next - Utc::now()).to_std().unwrap_or_default();
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Behavior of the polling schedule when a poll overruns the [period](Schedule::Every).
+///
+/// See [`MissedTickBehavior`] for the details of each strategy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum MissedTicks {
+    /// Missed ticks are fired immediately one after another to catch up with the schedule.
+    Burst,
+    /// The schedule is shifted so that the next tick happens a whole period after the late one.
+    Delay,
+    /// Missed ticks are dropped and the next tick happens at the next multiple of the period.
+    #[default]
+    Skip,
+}
+
+impl From<MissedTicks> for MissedTickBehavior {
+    fn from(missed_ticks: MissedTicks) -> Self {
+        match missed_ticks {
+            MissedTicks::Burst => Self::Burst,
+            MissedTicks::Delay => Self::Delay,
+            MissedTicks::Skip => Self::Skip,
+        }
+    }
+}
+
+impl Job {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn encoding(&self) -> Option<&'static Encoding> {
+        self.encoding.map(|encoding| encoding.0)
+    }
+
+    /// Evaluates the continuation of the page unless it does not satisfy [`Job::continue_while`].
+    ///
+    /// Relative addresses are resolved against the `<base>` of the page if it has one
+    /// and against the URL of the page otherwise.
+    pub fn continuations(
+        &self,
+        page: &Url,
+        tree: &XpathItemTree,
+    ) -> Vec<Result<Url, InvalidContinuation>> {
+        if let Some(condition) = &self.continue_while {
+            if !condition.holds(tree) {
+                debug!("Page does not satisfy the continuation condition, stopping");
+                return vec![];
+            }
+        }
+        let base = document_base(page, tree);
+        self.continuation
+            .evaluate(Some(page), tree)
+            .into_iter()
+            .map(|href| {
+                base.join(&href)
+                    .map_err(|error| InvalidContinuation { href, error })
+            })
+            .collect()
+    }
+
+    /// Evaluates the continuation of a JSON page.
+    ///
+    /// Only [offset](Continuation::Offset) continuations are followed
+    /// since references and [`Job::continue_while`] are XPath expressions.
+    pub fn json_continuations(&self, page: &Url) -> Vec<Result<Url, InvalidContinuation>> {
+        match &self.continuation {
+            Continuation::Offset {
+                param,
+                start,
+                step,
+                max_pages,
+            } => next_offset(page, param, *start, *step, *max_pages)
+                .map(Ok)
+                .into_iter()
+                .collect(),
+            Continuation::Ref(_) => vec![],
+        }
+    }
+
+    /// Checks the parts of the job which are not validated when the config is loaded,
+    /// describing each of the found problems.
+    ///
+    /// No requests are performed, so it is not checked whether the URLs are reachable.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        match &self.resource {
+            Resource::Url(url) => problems.extend(url_problem(url)),
+            Resource::Mirrors(mirrors) => {
+                if mirrors.is_empty() {
+                    problems.push("no mirrors are configured".to_owned());
+                }
+                problems.extend(mirrors.iter().filter_map(|mirror| url_problem(&mirror.url)));
+            }
+            Resource::Path(path) => {
+                if !path.is_file() {
+                    problems.push(format!("{} is not a file", path.display()));
+                }
+            }
+            Resource::Glob(pattern) => {
+                if let Err(error) = glob::Pattern::new(pattern) {
+                    problems.push(format!("invalid glob pattern {pattern:?}: {error}"));
+                }
+            }
+            Resource::Stdin => {}
+        }
+        if self.jitter.is_some() && matches!(self.period, Schedule::Cron(_)) {
+            problems.push("jitter is only supported by periodic schedules".to_owned());
+        }
+        for (index, sink) in self.sinks.iter().enumerate() {
+            if let Sink::Webhook {
+                body: Some(_),
+                format,
+                ..
+            } = sink
+            {
+                if *format != sink::Format::Json {
+                    problems.push(format!(
+                        "webhook sink {index} has a body template which is always filled with JSON, not {format:?}"
+                    ));
+                }
+            }
+        }
+        if let Some(Retry { multiplier, .. }) = self.retry {
+            if !multiplier.is_finite() || multiplier < 1.0 {
+                problems.push(format!(
+                    "retry multiplier {multiplier} is not a finite number of at least 1"
+                ));
+            }
+        }
+        if let Some(problem) = self.domain_rate.as_ref().and_then(Quota::problem) {
+            problems.push(format!("domain_rate {problem}"));
+        }
+        if self.change_count && self.detect_changes {
+            problems.push("change_count has no effect since detect_changes reports changes instead of results".to_owned());
+        }
+        if let Some(StartDelay::Random { min, max }) = self.start_delay {
+            if min > max {
+                problems.push(format!(
+                    "start_delay minimum {min:?} exceeds maximum {max:?}"
+                ));
+            }
+        }
+        if let Some(key) = &self.dedup_by {
+            if !self.targets.contains(key) {
+                problems.push(format!("dedup_by {key:?} does not name a target"));
+            }
+        }
+        // Targets of joined records are not known until the other job is polled.
+        if self.join.is_none() {
+            for target in &self.track {
+                if !self.targets.contains(target) {
+                    problems.push(format!("track {target:?} does not name a target"));
+                }
+            }
+        }
+        if self.series.is_some() && self.track.is_empty() {
+            problems.push("series has no effect since no targets are tracked".to_owned());
+        }
+        if let Some(join) = &self.join {
+            if join.job == self.name {
+                problems.push("join refers to the job itself".to_owned());
+            }
+            if !self.targets.contains(&join.by) {
+                problems.push(format!("join by {:?} does not name a target", join.by));
+            }
+        }
+        self.targets.problems(self.format, "", &mut problems);
+        if self.format == DocumentFormat::Json {
+            if self.continue_while.is_some() {
+                problems.push("continue_while is not supported by the JSON format".to_owned());
+            }
+            if matches!(self.success_when, Some(SuccessCriterion::XPath(_))) {
+                problems.push("XPath success_when is not supported by the JSON format".to_owned());
+            }
+        }
+        problems
+    }
+
+    /// Creates the ticker at which the job should be polled.
+    pub fn ticker(&self) -> Ticker {
+        match &self.period {
+            Schedule::Every(period) => {
+                if let Some(jitter) = &self.jitter {
+                    return Ticker::Jittered(Box::new(JitteredInterval {
+                        period: *period,
+                        deviation: jitter.deviation(*period),
+                        missed_ticks: self.missed_ticks,
+                        rng: jitter
+                            .seed
+                            .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+                        next: Instant::now(),
+                    }));
+                }
+                let mut interval = tokio::time::interval(*period);
+                interval.set_missed_tick_behavior(self.missed_ticks.into());
+                Ticker::Interval(interval)
+            }
+            Schedule::Cron(schedule) => Ticker::Cron(schedule.clone()),
+        }
+    }
+}
+
+/// Checks that the URL can be fetched over HTTP.
+fn url_problem(url: &Url) -> Option<String> {
+    (!matches!(url.scheme(), "http" | "https") || !url.has_host())
+        .then(|| format!("{url} is not an HTTP URL"))
+}
+
+/// State of a job kept between its polls.
+#[derive(Debug, Default)]
+pub struct JobState {
+    /// Serialized results of the previous poll of each resource
+    results: HashMap<String, serde_json::Value>,
+    /// Validators of the previous response from each URL
+    validators: HashMap<Url, Validators>,
+    /// Writer of results into the sinks, kept until the job stops so that slow sinks do not delay its polls
+    writer: Option<SinkWriter>,
+}
+
+/// Validators of a response, allowing to request the resource only if it has been modified since.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<HeaderValue>,
+    pub last_modified: Option<HeaderValue>,
+}
+
+impl JobState {
+    /// Queues the event to be written into the sinks of the job, starting their writer if needed.
+    pub async fn write(&mut self, client: &reqwest::Client, job: &Job, event: OwnedEvent) {
+        if job.sinks.is_empty() {
+            return;
+        }
+        self.writer
+            .get_or_insert_with(|| SinkWriter::spawn(client.clone(), job))
+            .send(event)
+            .await;
+    }
+
+    /// Waits until all queued events are written into the sinks, stopping their writer.
+    pub async fn flush(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            writer.close().await;
+        }
+    }
+
+    /// Forgets the results and validators of the previous polls, keeping the events queued for the sinks.
+    pub fn reset(&mut self) {
+        *self = Self {
+            writer: self.writer.take(),
+            ..Self::default()
+        };
+    }
+
+    /// Remembers the results of the resource, comparing them with the previous ones.
+    ///
+    /// Changes of numbers smaller than the [minimal change](Target::min_change) of their targets are ignored,
+    /// and the previous numbers are remembered instead so that gradual changes are reported once they add up.
+    /// Returns `None` if the resource has not been polled before.
+    pub fn changes(
+        &mut self,
+        resource: &Resource,
+        results: serde_json::Value,
+        targets: &Targets,
+    ) -> Option<Vec<Change>> {
+        let key = resource.to_string();
+        let previous = self.results.insert(key.clone(), results)?;
+        let current = self
+            .results
+            .get_mut(&key)
+            .expect("results should have been inserted");
+        let mut changes = diff::json(&previous, current);
+        changes.retain(|change| {
+            let Change::Changed { path, old, new } = change else {
+                return true;
+            };
+            match (old.as_f64(), new.as_f64(), targets.min_change(path)) {
+                (Some(old_number), Some(new_number), Some(min_change))
+                    if !min_change.is_reached(old_number, new_number) =>
+                {
+                    if let Some(value) = current.pointer_mut(path) {
+                        *value = old.clone();
+                    }
+                    false
+                }
+                _ => true,
+            }
+        });
+        Some(changes)
+    }
+
+    pub fn validators(&self, url: &Url) -> Option<&Validators> {
+        self.validators.get(url)
+    }
+
+    /// Remembers the `ETag` and `Last-Modified` headers of the response from the URL, if any.
+    pub fn remember_validators(&mut self, url: &Url, headers: &HeaderMap) {
+        let validators = Validators {
+            etag: headers.get(header::ETAG).cloned(),
+            last_modified: headers.get(header::LAST_MODIFIED).cloned(),
+        };
+        if validators.etag.is_none() && validators.last_modified.is_none() {
+            self.validators.remove(url);
+        } else {
+            self.validators.insert(url.clone(), validators);
+        }
+    }
+}
+
+/// Condition which a fetched document has to satisfy for the poll to be considered successful.
+///
+/// This allows to detect soft failures, such as captcha walls served with `200 OK` status.
+#[derive(Debug, Clone, Deserialize)]
+pub enum SuccessCriterion {
+    /// The XPath expression has a `true` effective boolean value, e.g. it matches something.
+    XPath(ParsedXPath),
+    /// The regular expression matches the raw document.
+    BodyRegex(ParsedRegex),
+}
+
+impl SuccessCriterion {
+    /// Checks the raw document and its tree, which JSON documents lack so that XPath is never met for them.
+    pub fn is_met(&self, document: &str, tree: Option<&XpathItemTree>) -> bool {
+        match self {
+            Self::XPath(path) => {
+                tree.is_some_and(|tree| path.xpath().apply(tree).is_ok_and(|items| items.boolean()))
+            }
+            Self::BodyRegex(ParsedRegex(regex)) => regex.is_match(document),
+        }
+    }
+}
+
+/// [`Method`] parsed from its name, such as `"POST"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpMethod(pub Method);
+
+impl<'de> Deserialize<'de> for HttpMethod {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let raw = String::deserialize(deserializer)?;
+        Method::from_bytes(raw.to_uppercase().as_bytes())
+            .map_err(|error| Error::custom(format_args!("invalid HTTP method: {error}")))
+            .map(Self)
+    }
+}
+
+/// [`XPath`] parsed once when deserialized, along with its source.
+///
+/// The parsed expression is shared by the clones as [`Xpath`] itself cannot be cloned.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ParsedXPath {
+    raw: String,
+    xpath: Arc<Xpath>,
+}
+
+impl TryFrom<String> for ParsedXPath {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let xpath =
+            xpath::parse(&raw).map_err(|error| format!("failed to parse XPath: {error}"))?;
+        Ok(Self {
+            xpath: Arc::new(xpath),
+            raw,
+        })
+    }
+}
+
+impl fmt::Display for ParsedXPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl ParsedXPath {
+    pub fn xpath(&self) -> &Xpath {
+        &self.xpath
+    }
+}
+
+/// [`Regex`] compiled when deserialized.
+#[derive(Debug, Clone)]
+pub struct ParsedRegex(pub Regex);
+
+impl<'de> Deserialize<'de> for ParsedRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let raw = String::deserialize(deserializer)?;
+        Regex::new(&raw)
+            .map_err(|error| Error::custom(format_args!("failed to parse regex: {error}")))
+            .map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::testing::{job, try_job};
+
+    #[test]
+    fn test_captcha_page_fails_success_criterion() {
+        use skyscraper::html;
+
+        let products = include_str!("../fixtures/products.html");
+        let products_tree = XpathItemTree::from(&html::parse(products).unwrap());
+        let captcha = include_str!("../fixtures/captcha.html");
+        let captcha_tree = XpathItemTree::from(&html::parse(captcha).unwrap());
+
+        let xpath = SuccessCriterion::XPath("//div/h3".to_owned().try_into().unwrap());
+        assert!(xpath.is_met(products, Some(&products_tree)));
+        assert!(!xpath.is_met(captcha, Some(&captcha_tree)));
+        assert!(!xpath.is_met(products, None));
+
+        let regex = SuccessCriterion::BodyRegex(ParsedRegex(Regex::new("(?i)captcha").unwrap()));
+        assert!(!regex.is_met(products, Some(&products_tree)));
+        assert!(regex.is_met(captcha, None));
+    }
+
+    #[test]
+    fn test_continuation_stops_outside_of_date_range() {
+        use skyscraper::html;
+
+        let job = job(r#"continue_while: {
+                Within: { path: "//article/time/@datetime", min: "2024-05-01" },
+            },"#);
+        let page = |dates: &[&str], next: &str| {
+            let mut articles = String::new();
+            for date in dates {
+                articles += &format!("<article><time datetime=\"{date}\"></time></article>");
+            }
+            XpathItemTree::from(
+                &html::parse(&format!(
+                    "<body>{articles}<a href=\"{next}\">Next</a></body>"
+                ))
+                .unwrap(),
+            )
+        };
+
+        let url = Url::parse("https://news.example/news/1").unwrap();
+        let next = |url: &str| Ok(Url::parse(url).unwrap());
+
+        assert_eq!(
+            job.continuations(&url, &page(&["2024-05-20", "2024-05-14"], "/news/2")),
+            [next("https://news.example/news/2")]
+        );
+        assert_eq!(
+            job.continuations(&url, &page(&["2024-05-07", "2024-05-01"], "/news/3")),
+            [next("https://news.example/news/3")]
+        );
+        assert!(job
+            .continuations(&url, &page(&["2024-04-30", "2024-04-22"], "/news/4"))
+            .is_empty());
+        assert!(job.continuations(&url, &page(&[], "/news/5")).is_empty());
+    }
+
+    #[test]
+    fn test_continuations_are_resolved_against_page_base() {
+        use skyscraper::html;
+
+        let job = job("");
+        let page = Url::parse("https://shop.example/catalog/tees?page=1").unwrap();
+        let next = |html: &str| {
+            job.continuations(&page, &XpathItemTree::from(&html::parse(html).unwrap()))
+        };
+        let url = |url: &str| Ok(Url::parse(url).unwrap());
+
+        assert_eq!(
+            next(r#"<body><a href="https://mirror.example/tees?page=2">Next</a></body>"#),
+            [url("https://mirror.example/tees?page=2")]
+        );
+        assert_eq!(
+            next(r#"<body><a href="/search?page=2">Next</a></body>"#),
+            [url("https://shop.example/search?page=2")]
+        );
+        assert_eq!(
+            next(r#"<body><a href="?page=2">Next</a></body>"#),
+            [url("https://shop.example/catalog/tees?page=2")]
+        );
+        assert_eq!(
+            next(
+                r#"<html><head><base href="/archive/"></head><body><a href="tees?page=2">Next</a></body></html>"#
+            ),
+            [url("https://shop.example/archive/tees?page=2")]
+        );
+        assert_eq!(
+            next(r#"<body><a href="http://[::1">Next</a></body>"#),
+            [Err(InvalidContinuation {
+                href: "http://[::1".to_owned(),
+                error: url::ParseError::InvalidIpv6Address,
+            })]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overrun_poll_respects_missed_ticks() {
+        use tokio::time::{self, Instant};
+
+        async fn next_tick_after_overrun(missed_ticks: MissedTicks) -> Duration {
+            let job = job(&format!(r#"missed_ticks: "{missed_ticks:?}","#));
+            assert_eq!(job.missed_ticks, missed_ticks);
+            let start = Instant::now();
+            let mut interval = job.ticker();
+            interval.tick().await;
+            // The poll takes 2.5 periods.
+            time::advance(Duration::from_millis(2_500)).await;
+            interval.tick().await;
+            interval.tick().await;
+            start.elapsed()
+        }
+
+        assert_eq!(
+            next_tick_after_overrun(MissedTicks::Burst).await,
+            Duration::from_millis(2_500)
+        );
+        assert_eq!(
+            next_tick_after_overrun(MissedTicks::Delay).await,
+            Duration::from_millis(3_500)
+        );
+        assert_eq!(
+            next_tick_after_overrun(MissedTicks::Skip).await,
+            Duration::from_millis(3_000)
+        );
+    }
+
+    #[test]
+    fn test_start_delay_is_sampled_within_bounds() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0xC0FFEE);
+        assert!(job("").start_delay.is_none());
+        let fixed = job(r#"start_delay: { Fixed: { secs: 3, nanos: 0 } },"#);
+        assert_eq!(
+            fixed.start_delay.unwrap().sample(&mut rng),
+            Duration::from_secs(3)
+        );
+
+        let random = job(
+            r#"start_delay: { Random: { min: { secs: 1, nanos: 0 }, max: { secs: 5, nanos: 0 } } },"#,
+        )
+        .start_delay
+        .unwrap();
+        let delays: Vec<_> = (0..100).map(|_| random.sample(&mut rng)).collect();
+        assert!(delays
+            .iter()
+            .all(|delay| (Duration::from_secs(1)..=Duration::from_secs(5)).contains(delay)));
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_jittered_ticks_are_reproducible_with_seed() {
+        async fn tick_delays(jitter: &str) -> Vec<Duration> {
+            let job = job(&format!("jitter: {jitter},"));
+            let mut ticker = job.ticker();
+            let mut delays = Vec::new();
+            let mut previous = Instant::now();
+            for _ in 0..20 {
+                ticker.tick().await;
+                delays.push(previous.elapsed());
+                previous = Instant::now();
+            }
+            delays
+        }
+
+        let fixed = r#"{ max: { Fixed: { secs: 0, nanos: 250000000 } }, seed: 42 }"#;
+        let delays = tick_delays(fixed).await;
+        assert_eq!(delays[0], Duration::ZERO, "first tick should be immediate");
+        assert!(delays[1..].iter().all(|delay| {
+            (Duration::from_millis(750)..=Duration::from_millis(1_250)).contains(delay)
+        }));
+        assert!(
+            delays[1..].iter().any(|delay| *delay != delays[1]),
+            "delays should vary"
+        );
+        assert_eq!(tick_delays(fixed).await, delays);
+        assert_ne!(
+            tick_delays(r#"{ max: { Fixed: { secs: 0, nanos: 250000000 } }, seed: 7 }"#).await,
+            delays
+        );
+
+        let percent = tick_delays(r#"{ max: { Percent: 10 }, seed: 42 }"#).await;
+        assert!(percent[1..].iter().all(|delay| {
+            (Duration::from_millis(900)..=Duration::from_millis(1_100)).contains(delay)
+        }));
+    }
+
+    #[test]
+    fn test_attribute_extractor_reads_named_attribute() {
+        use skyscraper::html;
+
+        let extractor: ValueExtractor = config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"{ extract: { Attribute: "href" } }"#,
+                config::FileFormat::Json5,
+            ))
+            .build()
+            .and_then(|config| config.get("extract"))
+            .unwrap();
+        let tree = XpathItemTree::from(
+            &html::parse(r#"<nav><a href="/next" data-id="2">Next</a><a>Back</a></nav>"#).unwrap(),
+        );
+        let apply = |path| xpath::parse(path).unwrap().apply(&tree).unwrap();
+
+        assert_eq!(
+            extractor.extract(&tree, apply("//a")),
+            [Value::String("/next".into()), Value::Unknown]
+        );
+        assert_eq!(
+            extractor.extract(&tree, apply("//a/@href")),
+            [Value::String("/next".into())]
+        );
+        assert_eq!(
+            extractor.extract(&tree, apply("//a/@data-id")),
+            [Value::Unknown]
+        );
+    }
+
+    #[test]
+    fn test_normalized_text_extractor_collapses_whitespace() {
+        use skyscraper::html;
+
+        let extractor = |source| {
+            config::Config::builder()
+                .add_source(config::File::from_str(source, config::FileFormat::Json5))
+                .build()
+                .and_then(|config| config.get::<ValueExtractor>("extract"))
+                .unwrap()
+        };
+        let tree = XpathItemTree::from(
+            &html::parse("<div><h2>\n    Loom\n    Hoodie  </h2><p>\t</p></div>").unwrap(),
+        );
+        let items = || xpath::parse("//h2/text()").unwrap().apply(&tree).unwrap();
+
+        assert_eq!(
+            extractor(r#"{ extract: "text" }"#).extract(&tree, items()),
+            [Value::String("\n    Loom\n    Hoodie  ".into())]
+        );
+        assert_eq!(
+            extractor("{ extract: { NormalizedText: {} } }").extract(&tree, items()),
+            [Value::String("Loom Hoodie".into())]
+        );
+        assert_eq!(
+            extractor("{ extract: { NormalizedText: { collapse_whitespace: false } } }")
+                .extract(&tree, items()),
+            [Value::String("Loom\n    Hoodie".into())]
+        );
+        assert_eq!(
+            extractor("{ extract: { NormalizedText: { trim: false } } }").extract(&tree, items()),
+            [Value::String(" Loom Hoodie ".into())]
+        );
+        assert_eq!(
+            extractor("{ extract: { NormalizedText: {} } }")
+                .extract(&tree, xpath::parse("//p").unwrap().apply(&tree).unwrap()),
+            [Value::String("".into())]
+        );
+    }
+
+    #[test]
+    fn test_html_extractor_serializes_elements() {
+        use skyscraper::html;
+
+        let extractor = |source| {
+            config::Config::builder()
+                .add_source(config::File::from_str(source, config::FileFormat::Json5))
+                .build()
+                .and_then(|config| config.get::<ValueExtractor>("extract"))
+                .unwrap()
+        };
+        let tree = XpathItemTree::from(
+            &html::parse(
+                r#"<div><p class="note">Fits <a href="/sizes?a=1&amp;b=2">true</a><br>to size &lt;3</p></div>"#,
+            )
+            .unwrap(),
+        );
+        let items = || xpath::parse("//p").unwrap().apply(&tree).unwrap();
+
+        assert_eq!(
+            extractor(r#"{ extract: { Html: {} } }"#).extract(&tree, items()),
+            [Value::String(
+                r#"<p class="note">Fits <a href="/sizes?a=1&amp;b=2">true</a><br>to size &lt;3</p>"#
+                    .into()
+            )]
+        );
+        assert_eq!(
+            extractor(r#"{ extract: { Html: { inner: true } } }"#).extract(&tree, items()),
+            [Value::String(
+                r#"Fits <a href="/sizes?a=1&amp;b=2">true</a><br>to size &lt;3"#.into()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_join_extractor_joins_all_items() {
+        use skyscraper::html;
+
+        let extractor = |source| {
+            config::Config::builder()
+                .add_source(config::File::from_str(source, config::FileFormat::Json5))
+                .build()
+                .and_then(|config| config.get::<ValueExtractor>("extract"))
+                .unwrap()
+        };
+        let tree = XpathItemTree::from(
+            &html::parse("<ul><li>cotton</li><li>organic</li><li><b>sale</b></li></ul>").unwrap(),
+        );
+        let items = |path| xpath::parse(path).unwrap().apply(&tree).unwrap();
+
+        let join = extractor(r#"{ extract: { Join: { separator: ", " } } }"#);
+        assert_eq!(
+            join.extract(&tree, items("//li")),
+            [Value::String("cotton, organic, sale".into())]
+        );
+        assert_eq!(join.extract(&tree, items("//p")), [Value::Unknown]);
+        let join = extractor(r#"{ extract: { Join: { on_empty: "EmptyString" } } }"#);
+        assert_eq!(
+            join.extract(&tree, items("//li/text()")),
+            [Value::String("cottonorganic".into())]
+        );
+        assert_eq!(
+            join.extract(&tree, items("//p")),
+            [Value::String("".into())]
+        );
+    }
+
+    #[test]
+    fn test_count_extractor_counts_items() {
+        use skyscraper::html;
+
+        let tree = XpathItemTree::from(
+            &html::parse("<ul><li>S</li><li class='out'>M</li><li>L</li></ul>").unwrap(),
+        );
+        let count = |path| {
+            ValueExtractor::Count.extract(&tree, xpath::parse(path).unwrap().apply(&tree).unwrap())
+        };
+
+        assert_eq!(count("//li"), [Value::Number(3.0)]);
+        assert_eq!(count("//li[@class='out']"), [Value::Number(1.0)]);
+        assert_eq!(count("//p"), [Value::Number(0.0)]);
+    }
+
+    #[test]
+    fn test_exists_extractor_checks_presence() {
+        use skyscraper::html;
+
+        let tree = XpathItemTree::from(
+            &html::parse("<div><h3>Loom Hoodie</h3><span class='badge'>Sold out</span></div>")
+                .unwrap(),
+        );
+        let exists = |path| {
+            ValueExtractor::Exists.extract(&tree, xpath::parse(path).unwrap().apply(&tree).unwrap())
+        };
+
+        assert_eq!(exists("//span[@class='badge']"), [Value::Bool(true)]);
+        assert_eq!(exists("//div[@class='captcha']"), [Value::Bool(false)]);
+        assert_eq!(serde_json::to_string(&exists("//span")).unwrap(), "[true]");
+    }
+
+    #[test]
+    fn test_regex_extractor_captures_group() {
+        use skyscraper::html;
+
+        let extractor = |source| {
+            config::Config::builder()
+                .add_source(config::File::from_str(source, config::FileFormat::Json5))
+                .build()
+                .and_then(|config| config.get::<ValueExtractor>("extract"))
+        };
+        let tree = XpathItemTree::from(
+            &html::parse(
+                "<ul><li>Price: $1,299.00 (incl. tax)</li><li>Price: <b>$49</b></li><li>Sold out</li></ul>",
+            )
+            .unwrap(),
+        );
+        let items = || xpath::parse("//li").unwrap().apply(&tree).unwrap();
+
+        let price =
+            extractor(r#"{ extract: { Regex: { pattern: "\\$([\\d,.]+)", group: 1 } } }"#).unwrap();
+        assert_eq!(
+            price.extract(&tree, items()),
+            [
+                Value::String("1,299.00".into()),
+                Value::String("49".into()),
+                Value::Unknown
+            ]
+        );
+        let whole = extractor(r#"{ extract: { Regex: { pattern: "\\$[\\d,.]+" } } }"#).unwrap();
+        assert_eq!(
+            whole.extract(&tree, items())[0],
+            Value::String("$1,299.00".into())
+        );
+
+        let error = extractor(r#"{ extract: { Regex: { pattern: "(unclosed" } } }"#).unwrap_err();
+        assert!(error.to_string().contains("failed to parse regex"));
+    }
+
+    #[test]
+    fn test_transforms_are_applied_in_order() {
+        let target = config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"{
+                    path: "//li",
+                    then: { extract: "text" },
+                    transforms: [
+                        "Trim",
+                        { RegexCapture: { pattern: "Price: (.+)", group: 1 } },
+                        { RegexReplace: { pattern: "\\s+", replacement: "" } },
+                        { ParseNumber: { locale: "Ru" } },
+                    ],
+                }"#,
+                config::FileFormat::Json5,
+            ))
+            .build()
+            .and_then(config::Config::try_deserialize::<Target>)
+            .unwrap();
+        let mut values = [
+            Value::String("  Price: 1 299,50 ₽ ".into()),
+            Value::String("Sold out".into()),
+            Value::Number(49.0),
+            Value::Unknown,
+        ];
+
+        target.transform(&mut values);
+        assert_eq!(
+            values,
+            [
+                Value::Number(1299.5),
+                Value::Unknown,
+                Value::Number(49.0),
+                Value::Unknown
+            ]
+        );
+
+        let mut value = Transform::Lowercase.apply(Value::String("Loom HOODIE".into()));
+        value = Transform::Uppercase.apply(value);
+        assert_eq!(value, Value::String("LOOM HOODIE".into()));
+        assert_eq!(Transform::Trim.apply(Value::Bool(true)), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_string_transforms_are_chained_after_text() {
+        let target = config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"{
+                    path: "//li",
+                    then: { extract: "text" },
+                    transforms: [
+                        { StripPrefix: "SKU: " },
+                        { Replace: { from: "-", to: "_" } },
+                        { StripSuffix: "_old" },
+                        { Substring: { start: 1, length: 6 } },
+                    ],
+                }"#,
+                config::FileFormat::Json5,
+            ))
+            .build()
+            .and_then(config::Config::try_deserialize::<Target>)
+            .unwrap();
+        let mut values = [
+            Value::String("SKU: LH-1-XL-old".into()),
+            Value::String("Артикул ЁЖ-12".into()),
+        ];
+
+        target.transform(&mut values);
+        assert_eq!(
+            values,
+            [
+                Value::String("H_1_XL".into()),
+                Value::String("ртикул".into())
+            ]
+        );
+        assert_eq!(
+            Transform::Substring {
+                start: 10,
+                length: None
+            }
+            .apply(Value::String("Loom".into())),
+            Value::String("".into())
+        );
+    }
+
+    #[test]
+    fn test_number_extractor_respects_locale() {
+        use skyscraper::html;
+
+        let extractor = |source| {
+            config::Config::builder()
+                .add_source(config::File::from_str(source, config::FileFormat::Json5))
+                .build()
+                .and_then(|config| config.get::<ValueExtractor>("extract"))
+                .unwrap()
+        };
+        let tree = XpathItemTree::from(
+            &html::parse(
+                "<ul><li>Price: $1,299.50</li><li>-€49</li><li>1.234,5 €</li><li>12 345,6 ₽</li><li>Sold out</li></ul>",
+            )
+            .unwrap(),
+        );
+        let items = || xpath::parse("//li").unwrap().apply(&tree).unwrap();
+
+        assert_eq!(
+            extractor("{ extract: { Number: {} } }").extract(&tree, items()),
+            [
+                Value::Number(1299.5),
+                Value::Number(-49.0),
+                Value::Number(1.2345),
+                Value::Number(12.0),
+                Value::Unknown
+            ]
+        );
+        assert_eq!(
+            extractor(r#"{ extract: { Number: { locale: "De" } } }"#).extract(&tree, items())[2],
+            Value::Number(1234.5)
+        );
+        assert_eq!(
+            extractor(r#"{ extract: { Number: { locale: "Ru" } } }"#).extract(&tree, items())[3],
+            Value::Number(12345.6)
+        );
+        assert_eq!(NumberLocale::Ch.parse("CHF 1'250.00"), Some(1250.0));
+        assert_eq!(NumberLocale::En.parse("Sale - $5"), Some(5.0));
+    }
+
+    #[test]
+    fn test_date_time_extractor_normalizes_timestamps() {
+        use skyscraper::html;
+
+        let extractor = |source| {
+            config::Config::builder()
+                .add_source(config::File::from_str(source, config::FileFormat::Json5))
+                .build()
+                .and_then(|config| config.get::<ValueExtractor>("extract"))
+        };
+        let tree = XpathItemTree::from(
+            &html::parse(
+                "<ul><li>14.05.2024 09:30</li><li> 01.05.2024 </li><li>yesterday</li></ul>",
+            )
+            .unwrap(),
+        );
+        let items = || xpath::parse("//li").unwrap().apply(&tree).unwrap();
+
+        let moscow =
+            extractor(r#"{ extract: { DateTime: { format: "%d.%m.%Y %H:%M", tz: "+03:00" } } }"#)
+                .unwrap();
+        assert_eq!(
+            moscow.extract(&tree, items()),
+            [
+                Value::String("2024-05-14T09:30:00+03:00".into()),
+                Value::Unknown,
+                Value::Unknown
+            ]
+        );
+        let dates = extractor(r#"{ extract: { DateTime: { format: "%d.%m.%Y" } } }"#).unwrap();
+        assert_eq!(
+            dates.extract(&tree, items())[1],
+            Value::String("2024-05-01T00:00:00+00:00".into())
+        );
+        assert_eq!(
+            TimeZone::default().parse("2024-05-14 09:30 +0200", "%Y-%m-%d %H:%M %z"),
+            DateTime::parse_from_rfc3339("2024-05-14T09:30:00+02:00").ok()
+        );
+
+        let error =
+            extractor(r#"{ extract: { DateTime: { format: "%Y", tz: "Mars" } } }"#).unwrap_err();
+        assert!(error.to_string().contains("invalid time zone"));
+    }
+
+    #[test]
+    fn test_secrets_are_read_from_environment() {
+        let var = |name: &str| match name {
+            "WETCHER_TOKEN" => Ok("from-env".to_owned()),
+            _ => Err(VarError::NotPresent),
+        };
+        let secret = |source| {
+            serde_json::from_str::<SecretSource>(source)
+                .unwrap()
+                .resolve(&var)
+        };
+        assert_eq!(
+            secret(r#"{ "env": "WETCHER_TOKEN" }"#).unwrap().expose(),
+            "from-env"
+        );
+        assert_eq!(secret(r#""hunter2""#).unwrap().expose(), "hunter2");
+        assert!(secret(r#"{ "env": "WETCHER_MISSING" }"#)
+            .unwrap_err()
+            .contains("WETCHER_MISSING"));
+
+        let body = |source| {
+            serde_json::from_str::<BodySource>(source)
+                .unwrap()
+                .resolve(&var)
+                .unwrap()
+        };
+        let Body::Json(json) = body(
+            r#"{ "json": "{ \"variables\": { \"token\": { \"env\": \"WETCHER_TOKEN\" }, \"pageSize\": 10 } }" }"#,
+        ) else {
+            panic!("body should be JSON");
+        };
+        assert_eq!(
+            json,
+            serde_json::json!({ "variables": { "token": "from-env", "pageSize": 10 } })
+        );
+        let Body::Form(fields) = body(
+            r#"{ "form": [{ "name": "q", "value": "hoodie" }, { "name": "token", "value": { "env": "WETCHER_TOKEN" } }] }"#,
+        ) else {
+            panic!("body should be a form");
+        };
+        assert_eq!(
+            fields
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.expose()))
+                .collect::<Vec<_>>(),
+            [("q", "hoodie"), ("token", "from-env")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_header_values_are_rejected() {
+        let error = try_job(r#"headers: { "X-Api-Key": "line\nbreak" },"#).unwrap_err();
+        assert!(error.to_string().contains("invalid value of header"));
+    }
+
+    #[test]
+    fn test_retry_delays_grow_exponentially() {
+        let retry = Retry {
+            attempts: 4,
+            delay: Duration::from_millis(100),
+            multiplier: 3.0,
+            max_delay: Retry::default_max_delay(),
+            max_retry_after: Retry::default_max_retry_after(),
+        };
+        assert_eq!(
+            retry.delays().collect::<Vec<_>>(),
+            [100, 300, 900].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn test_retry_delays_are_capped() {
+        let retry = Retry {
+            attempts: u32::MAX,
+            delay: Duration::from_secs(1),
+            multiplier: 10.0,
+            max_delay: Duration::from_secs(60),
+            max_retry_after: Retry::default_max_retry_after(),
+        };
+        assert_eq!(
+            retry.delays().take(4).collect::<Vec<_>>(),
+            [1, 10, 60, 60].map(Duration::from_secs)
+        );
+        // Delays overflowing `Duration` are capped as well.
+        assert_eq!(retry.delays().nth(1_000), Some(Duration::from_secs(60)));
+
+        for (literal, multiplier) in [
+            ("0.5", 0.5),
+            ("-2", -2.0),
+            ("NaN", f64::NAN),
+            ("Infinity", f64::INFINITY),
+        ] {
+            assert_eq!(
+                job(&format!(
+                    r#"retry: {{ attempts: 3, delay: {{ secs: 1, nanos: 0 }}, multiplier: {literal} }},"#
+                ))
+                .problems(),
+                [format!("retry multiplier {multiplier} is not a finite number of at least 1")]
+            );
+        }
+    }
+
+    #[test]
+    fn test_unsatisfiable_domain_rates_are_rejected() {
+        for (rate, problem) in [
+            (
+                "requests: 0, window: { secs: 1, nanos: 0 }",
+                "allows no requests",
+            ),
+            (
+                "requests: 1, window: { secs: 0, nanos: 0 }",
+                "has a zero window",
+            ),
+        ] {
+            assert_eq!(
+                job(&format!("domain_rate: {{ {rate} }},")).problems(),
+                [format!("domain_rate {problem}")]
+            );
+        }
+        assert!(
+            job("domain_rate: { requests: 1, window: { secs: 1, nanos: 0 } },")
+                .problems()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_mirror_selection_follows_weights() {
+        let mirrors = [
+            Mirror {
+                url: Url::parse("https://primary.example.com").unwrap(),
+                weight: 70,
+            },
+            Mirror {
+                url: Url::parse("https://secondary.example.com").unwrap(),
+                weight: 30,
+            },
+        ];
+        let mut rng = StdRng::seed_from_u64(0xC0FFEE);
+
+        const POLLS: usize = 10_000;
+        let mut primary = 0;
+        for _ in 0..POLLS {
+            let order = Mirror::weighted_order(&mirrors, &mut rng);
+            assert_eq!(order.len(), mirrors.len(), "all mirrors should be tried");
+            if order[0].url == mirrors[0].url {
+                primary += 1;
+            }
+        }
+
+        let share = primary as f64 / POLLS as f64;
+        assert!((0.67..=0.73).contains(&share), "primary share is {share}");
+    }
+
+    #[test]
+    fn test_dynamic_headers_are_rendered_per_request() {
+        let headers = DynamicHeaders {
+            templates: IndexMap::from([
+                ("X-Request-Id".to_owned(), "{uuid}".to_owned()),
+                ("X-Sequence".to_owned(), "seq-{counter}".to_owned()),
+                ("X-Client".to_owned(), "wetcher".to_owned()),
+            ]),
+            ..Default::default()
+        };
+
+        let first: IndexMap<_, _> = headers.render().collect();
+        let second: IndexMap<_, _> = headers.render().collect();
+
+        assert_ne!(first["X-Request-Id"], second["X-Request-Id"]);
+        assert_eq!(first["X-Sequence"], "seq-0");
+        assert_eq!(second["X-Sequence"], "seq-1");
+        assert_eq!(first["X-Client"], "wetcher");
+        assert_eq!(second["X-Client"], "wetcher");
+    }
+
+    #[test]
+    fn test_query_params_override_and_append() {
+        let query = job(r#"query: [
+                { name: "page", value: "{page}" },
+                { name: "sort", value: "new" },
+                { name: "since", value: "{date}" },
+            ],"#)
+        .query;
+        let apply = |url: &str, page| query.apply(&url.parse().unwrap(), page).to_string();
+        let today = Utc::now().format("%Y-%m-%d");
+
+        assert_eq!(
+            apply("https://shop.example/tees", 1),
+            format!("https://shop.example/tees?page=1&sort=new&since={today}")
+        );
+        assert_eq!(
+            apply("https://shop.example/tees?q=loom&sort=old&page=7&page=8", 3),
+            format!("https://shop.example/tees?q=loom&sort=new&page=3&since={today}")
+        );
+        assert_eq!(
+            QueryParams::default()
+                .apply(&"https://shop.example/tees?q=loom".parse().unwrap(), 1)
+                .as_str(),
+            "https://shop.example/tees?q=loom"
+        );
+
+        let query = job(r#"query: [{ name: "pageSize", value: "50" }],"#).query;
+        assert_eq!(
+            query
+                .apply(
+                    &"https://shop.example/tees?pageSize=20&pagesize=5"
+                        .parse()
+                        .unwrap(),
+                    1
+                )
+                .as_str(),
+            "https://shop.example/tees?pageSize=50&pagesize=5"
+        );
+    }
+
+    #[test]
+    fn test_changes_are_detected_per_resource() {
+        use serde_json::json;
+
+        let (hoodie, tee) = (
+            Resource::Url("https://shop.example/hoodie".parse().unwrap()),
+            Resource::Url("https://shop.example/tee".parse().unwrap()),
+        );
+        let mut state = JobState::default();
+        let targets = Targets(IndexMap::new());
+        assert_eq!(
+            state.changes(
+                &hoodie,
+                json!({ "name": ["Loom Hoodie"], "price": ["49"] }),
+                &targets
+            ),
+            None
+        );
+        assert_eq!(
+            state.changes(&tee, json!({ "price": ["19"] }), &targets),
+            None
+        );
+
+        // Keys in a different order are still the same results.
+        assert_eq!(
+            state.changes(
+                &hoodie,
+                json!({ "price": ["49"], "name": ["Loom Hoodie"] }),
+                &targets
+            ),
+            Some(vec![])
+        );
+        assert_eq!(
+            state.changes(
+                &hoodie,
+                json!({ "price": ["39"], "name": ["Loom Hoodie"] }),
+                &targets
+            ),
+            Some(vec![Change::Changed {
+                path: "/price/0".to_owned(),
+                old: json!("49"),
+                new: json!("39"),
+            }])
+        );
+        assert_eq!(
+            state.changes(&tee, json!({ "price": ["19"] }), &targets),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn test_numeric_changes_below_min_change_are_ignored() {
+        use serde_json::json;
+
+        let job = job(r#"targets: {
+            price: { path: "//span/text()", then: { Extract: { Number: {} } }, min_change: { Absolute: 1 } },
+            views: { path: "//em/text()", then: { Extract: { Number: {} } }, min_change: { Percent: 10 } },
+            name: { path: "//h3/text()", then: { Extract: "Text" } },
+        },"#);
+        let resource = Resource::Url("https://shop.example/hoodie".parse().unwrap());
+        let mut state = JobState::default();
+        let mut poll = |price: f64, views: f64| {
+            state.changes(
+                &resource,
+                json!({ "[0]": { "price": [price], "views": [views], "name": ["Loom Hoodie"] } }),
+                &job.targets,
+            )
+        };
+        assert_eq!(poll(19.0, 1000.0), None);
+
+        assert_eq!(poll(19.5, 1050.0), Some(vec![]));
+        // Ignored changes accumulate against the last reported numbers.
+        assert_eq!(
+            poll(20.0, 1100.0),
+            Some(vec![
+                Change::Changed {
+                    path: "/[0]/price/0".to_owned(),
+                    old: json!(19.0),
+                    new: json!(20.0),
+                },
+                Change::Changed {
+                    path: "/[0]/views/0".to_owned(),
+                    old: json!(1000.0),
+                    new: json!(1100.0),
+                },
+            ])
+        );
+        assert_eq!(poll(20.5, 1100.0), Some(vec![]));
+    }
+
+    #[test]
+    fn test_cron_schedule_is_parsed() {
+        use chrono::{DateTime, TimeZone};
+
+        assert!(
+            matches!(job("").period, Schedule::Every(period) if period == Duration::from_secs(1))
+        );
+
+        let Schedule::Cron(schedule) = job(r#"period: "0 0 9 * * Mon-Fri","#).period else {
+            panic!("cron expression should be parsed as a cron schedule");
+        };
+        // Friday evening.
+        let now = DateTime::from_timestamp(1_717_171_200, 0).unwrap();
+        assert_eq!(
+            schedule.after(&now).take(2).collect::<Vec<_>>(),
+            [
+                Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 6, 4, 9, 0, 0).unwrap(),
+            ]
+        );
+
+        let source = ScheduleSource::Cron("every weekday".to_owned());
+        assert!(Schedule::try_from(source)
+            .unwrap_err()
+            .starts_with("invalid cron expression"));
+    }
+
+    #[test]
+    fn test_problems_of_resources_are_found() {
+        assert!(job("").problems().is_empty());
+        assert_eq!(
+            job(r#"resource: { path: "fixtures/missing.html" },"#).problems(),
+            ["fixtures/missing.html is not a file"]
+        );
+        assert_eq!(
+            job(r#"resource: { mirrors: [{ url: "https://shop.example" }, { url: "ftp://shop.example/" }] },"#)
+                .problems(),
+            ["ftp://shop.example/ is not an HTTP URL"]
+        );
+        assert_eq!(
+            job(r#"resource: { glob: "fixtures/[pages/*.html" },"#)
+                .problems()
+                .len(),
+            1
+        );
+        assert!(job(
+            r#"targets: { products: { path: "//li", then: { get: { name: { path: "h2", then: { extract: "text" } } } } } },
+            dedup_by: "name","#
+        )
+        .problems()
+        .is_empty());
+        assert_eq!(
+            job(r#"dedup_by: "sku","#).problems(),
+            [r#"dedup_by "sku" does not name a target"#]
+        );
+        assert_eq!(
+            job(r#"name: "products", join: { job: "products", by: "sku" },"#).problems(),
+            [
+                "join refers to the job itself",
+                r#"join by "sku" does not name a target"#
+            ]
+        );
+        assert_eq!(
+            job(r#"track: ["price"],"#).problems(),
+            [r#"track "price" does not name a target"#]
+        );
+        assert_eq!(
+            job(r#"series: "state/series.json","#).problems(),
+            ["series has no effect since no targets are tracked"]
+        );
+        assert_eq!(
+            job(r#"period: "0 0 9 * * Mon-Fri", jitter: { max: { Percent: 10 } },"#).problems(),
+            ["jitter is only supported by periodic schedules"]
+        );
+        assert_eq!(
+            job("detect_changes: true, change_count: true,").problems(),
+            ["change_count has no effect since detect_changes reports changes instead of results"]
+        );
+        assert_eq!(
+            job(r#"sinks: [
+                { Webhook: { url: "https://hooks.example", format: "Yaml" } },
+                { Webhook: { url: "https://hooks.example", body: "{results}", format: "MsgPack" } },
+            ],"#)
+            .problems(),
+            ["webhook sink 1 has a body template which is always filled with JSON, not MsgPack"]
+        );
+        assert_eq!(
+            job(r#"start_delay: { Random: { min: { secs: 5, nanos: 0 }, max: { secs: 1, nanos: 0 } } },"#)
+                .problems(),
+            ["start_delay minimum 5s exceeds maximum 1s"]
+        );
+        assert!(job(
+            r#"format: "Json",
+            targets: { products: { path: { union: ["$.items[*]", "$.featured"] }, then: { get: { name: { path: "$.name", then: { extract: "text" } } } } } },"#
+        )
+        .problems()
+        .is_empty());
+        assert_eq!(
+            job(
+                r#"format: "Json",
+                targets: { products: { path: "$.items[*]", then: { get: { name: { path: "h2", then: { extract: "text" } } } } } },
+                continue_while: { XPath: "//li" },"#
+            )
+            .problems(),
+            [
+                r#"target "/products/name" has selectors not applicable to Json documents"#,
+                "continue_while is not supported by the JSON format",
+            ]
+        );
+        assert_eq!(
+            job(r#"targets: { name: { path: "$.name", then: { extract: "text" } } },"#).problems(),
+            [r#"target "/name" has selectors not applicable to Html documents"#]
+        );
+        assert_eq!(
+            job(r#"targets: {
+                    name: { path: "//h3", then: { extract: "text" }, default: "Unnamed" },
+                    price: { path: "//span", then: { extract: { number: {} } }, default: "free" },
+                    stock: { path: "//b", then: { extract: "text" }, default: 0, required: true },
+                    meta: { path: "//head", then: "seo", default: {} },
+                    rating: { path: "//i", then: { extract: "text" }, transforms: [{ ParseNumber: {} }], default: 0 },
+                    tags: { path: "//ul", then: { get: {} }, transforms: ["Trim"] },
+                    views: { path: "//em", then: "seo", min_change: { Percent: 5 } },
+                    address: { path: "//address", then: { RegexCaptures: { pattern: "(?<city>[^,]+), (\\w+)" } } },
+                    zip: { path: "//address", then: { RegexCaptures: { pattern: "\\d{5}" } } },
+                    hoodie: { path: "//main", then: { Product: { name: "//h1", price: "$.price" } } },
+                    tee: { path: "//main", then: { Product: { locale: "De" } } },
+                },"#)
+            .problems(),
+            [
+                r#"default of target "/price" is not a number"#,
+                r#"target "/stock" is required but has a default"#,
+                r#"target "/meta" has a default but extracts no values"#,
+                r#"target "/tags" has transforms but extracts no values"#,
+                r#"target "/views" has a minimal change but extracts no values"#,
+                r#"target "/zip" captures no named groups"#,
+                r#"target "/hoodie" has selectors not applicable to Html documents"#,
+                r#"target "/tee" selects no product fields"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offset_continuation_rewrites_query() {
+        use skyscraper::html;
+
+        let job = job(r#"continuation: { Offset: { param: "offset", step: 20, max_pages: 3 } },"#);
+        let tree = XpathItemTree::from(&html::parse("<div></div>").unwrap());
+        let next = |url| {
+            job.continuation
+                .evaluate(Some(&Url::parse(url).unwrap()), &tree)
+        };
+
+        assert_eq!(
+            next("https://shop.example/search?q=tee"),
+            ["https://shop.example/search?q=tee&offset=20"]
+        );
+        assert_eq!(
+            next("https://shop.example/search?offset=20&q=tee"),
+            ["https://shop.example/search?q=tee&offset=40"]
+        );
+        assert!(next("https://shop.example/search?q=tee&offset=40").is_empty());
+        assert!(job.continuation.evaluate(None, &tree).is_empty());
     }
 }
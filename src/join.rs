@@ -0,0 +1,113 @@
+//! Joining of the results of a job with the latest results of another job by the values of a shared key.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use indexmap::IndexMap;
+
+use crate::{
+    dedup::{is_records, key_value},
+    job::Value,
+    ProcessingResult, HEADERS_KEY,
+};
+
+/// Merges the targets of the records of the other results into the records of the result having the same key,
+/// keeping the targets which the records already have, and returns the number of joined records.
+///
+/// Records are found the same way as the [deduplicated](crate::dedup::Dedup) ones,
+/// while captured response headers are never joined.
+pub fn join(result: &mut ProcessingResult, key: &str, mut other: serde_json::Value) -> usize {
+    if let Some(other) = other.as_object_mut() {
+        other.remove(HEADERS_KEY);
+    }
+    let mut records = HashMap::new();
+    collect(key, from_json(other), &mut records);
+
+    let ProcessingResult::Group(group) = result else {
+        return 0;
+    };
+    let headers = group.shift_remove(HEADERS_KEY);
+    let joined = merge(key, &records, group);
+    if let Some(headers) = headers {
+        group.insert(Cow::Borrowed(HEADERS_KEY), headers);
+    }
+    joined
+}
+
+/// Collects the records of the result by the values of their keys, keeping the first record of each key.
+fn collect(
+    key: &str,
+    result: ProcessingResult<'static>,
+    records: &mut HashMap<String, serde_json::Value>,
+) {
+    let ProcessingResult::Group(group) = result else {
+        return;
+    };
+    if !is_records(key, &group) {
+        for result in group.into_values() {
+            collect(key, result, records);
+        }
+        return;
+    }
+    for record in group.into_values() {
+        if let Some(value) = key_value(key, &record) {
+            records.entry(value).or_insert_with(|| record.to_json());
+        }
+    }
+}
+
+fn merge<'tree>(
+    key: &str,
+    records: &HashMap<String, serde_json::Value>,
+    group: &mut IndexMap<Cow<'tree, str>, ProcessingResult<'tree>>,
+) -> usize {
+    if !is_records(key, group) {
+        return group
+            .values_mut()
+            .map(|result| match result {
+                ProcessingResult::Group(group) => merge(key, records, group),
+                _ => 0,
+            })
+            .sum();
+    }
+    let mut joined = 0;
+    for record in group.values_mut() {
+        let other = key_value(key, record)
+            .and_then(|value| records.get(&value))
+            .and_then(serde_json::Value::as_object);
+        if let (Some(other), ProcessingResult::Group(fields)) = (other, record) {
+            for (name, result) in other {
+                fields
+                    .entry(Cow::Owned(name.clone()))
+                    .or_insert_with(|| from_json(result.clone()));
+            }
+            joined += 1;
+        }
+    }
+    joined
+}
+
+/// Converts the [JSON of results](ProcessingResult::to_json) back, with errors becoming string values.
+fn from_json(result: serde_json::Value) -> ProcessingResult<'static> {
+    match result {
+        serde_json::Value::Object(group) => ProcessingResult::Group(
+            group
+                .into_iter()
+                .map(|(name, result)| (Cow::Owned(name), from_json(result)))
+                .collect(),
+        ),
+        serde_json::Value::Array(values) => {
+            ProcessingResult::Values(values.into_iter().map(value).collect())
+        }
+        error => ProcessingResult::Values(vec![value(error)]),
+    }
+}
+
+fn value(value: serde_json::Value) -> Value<'static> {
+    match value {
+        serde_json::Value::Null => Value::Unknown,
+        serde_json::Value::String(value) => Value::String(Cow::Owned(value)),
+        serde_json::Value::Number(value) => value.as_f64().map_or(Value::Unknown, Value::Number),
+        serde_json::Value::Bool(value) => Value::Bool(value),
+        value => Value::Json(value),
+    }
+}
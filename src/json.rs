@@ -0,0 +1,384 @@
+//! Selection of items of JSON documents by JSONPath expressions.
+
+use std::{borrow::Cow, fmt};
+
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    document::{Document, SelectError},
+    job::{self, Combinator, Selector, Value, ValueExtractor},
+    ProcessingResult,
+};
+
+/// JSONPath expression applied relative to the current item, which `$` refers to.
+///
+/// Supported segments are `.name`, `['name']`, `[index]` (negative indices count from the end)
+/// and wildcards `.*` and `[*]`, each of which selects descendants if prefixed with `..` instead.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ParsedJsonPath {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    /// Whether the selector is applied to all descendants of the items rather than to the items themselves
+    descendant: bool,
+    selector: SegmentSelector,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SegmentSelector {
+    Name(String),
+    Index(i64),
+    Wildcard,
+}
+
+impl TryFrom<String> for ParsedJsonPath {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let segments =
+            parse(&raw).map_err(|error| format!("failed to parse JSONPath {raw:?}: {error}"))?;
+        Ok(Self { raw, segments })
+    }
+}
+
+impl fmt::Display for ParsedJsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>, String> {
+    let mut rest = path.strip_prefix('$').ok_or("path should start with `$`")?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        let descendant = if let Some(after) = rest.strip_prefix("..") {
+            rest = after;
+            true
+        } else if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+            false
+        } else if rest.starts_with('[') {
+            false
+        } else {
+            return Err(format!("unexpected {rest:?}"));
+        };
+
+        let selector;
+        (selector, rest) = if let Some(after) = rest.strip_prefix('[') {
+            parse_bracket(after)?
+        } else if let Some(after) = rest.strip_prefix('*') {
+            (SegmentSelector::Wildcard, after)
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err("expected a name".to_owned());
+            }
+            (SegmentSelector::Name(rest[..end].to_owned()), &rest[end..])
+        };
+        segments.push(Segment {
+            descendant,
+            selector,
+        });
+    }
+    Ok(segments)
+}
+
+/// Parses the selector enclosed in brackets, returning the rest of the path after the closing one.
+fn parse_bracket(rest: &str) -> Result<(SegmentSelector, &str), String> {
+    if let Some(quote) = rest.chars().next().filter(|c| matches!(c, '\'' | '"')) {
+        let quoted = &rest[1..];
+        let end = quoted.find(quote).ok_or("unclosed quote")?;
+        let rest = quoted[end + 1..]
+            .strip_prefix(']')
+            .ok_or("expected `]` after the name")?;
+        return Ok((SegmentSelector::Name(quoted[..end].to_owned()), rest));
+    }
+
+    let end = rest.find(']').ok_or("unclosed bracket")?;
+    let selector = match rest[..end].trim() {
+        "*" => SegmentSelector::Wildcard,
+        index => SegmentSelector::Index(
+            index
+                .parse()
+                .map_err(|_| format!("invalid index {index:?}"))?,
+        ),
+    };
+    Ok((selector, &rest[end + 1..]))
+}
+
+impl ParsedJsonPath {
+    pub fn apply<'doc>(&self, item: JsonItem<'doc>) -> Vec<JsonItem<'doc>> {
+        let mut items = vec![item];
+        for Segment {
+            descendant,
+            selector,
+        } in &self.segments
+        {
+            if *descendant {
+                items = items
+                    .iter()
+                    .flat_map(JsonItem::descendants_or_self)
+                    .collect();
+            }
+            items = items
+                .iter()
+                .flat_map(|item| selector.select(item))
+                .collect();
+        }
+        items
+    }
+}
+
+impl SegmentSelector {
+    fn select<'doc>(&self, item: &JsonItem<'doc>) -> Vec<JsonItem<'doc>> {
+        match (self, item.value) {
+            (Self::Name(name), serde_json::Value::Object(object)) => object
+                .get(name)
+                .map(|value| item.child(name, value))
+                .into_iter()
+                .collect(),
+            (Self::Index(index), serde_json::Value::Array(array)) => {
+                let index = if *index < 0 {
+                    array.len().checked_sub(index.unsigned_abs() as usize)
+                } else {
+                    Some(*index as usize)
+                };
+                index
+                    .and_then(|index| Some(item.child(&index.to_string(), array.get(index)?)))
+                    .into_iter()
+                    .collect()
+            }
+            (Self::Wildcard, _) => item.children(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Value of a JSON document along with its [JSON Pointer](https://datatracker.ietf.org/doc/html/rfc6901).
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonItem<'doc> {
+    pub pointer: String,
+    pub value: &'doc serde_json::Value,
+}
+
+impl<'doc> JsonItem<'doc> {
+    fn child(&self, key: &str, value: &'doc serde_json::Value) -> Self {
+        Self {
+            pointer: format!(
+                "{}/{}",
+                self.pointer,
+                key.replace('~', "~0").replace('/', "~1")
+            ),
+            value,
+        }
+    }
+
+    /// Members of the object or elements of the array in their order.
+    fn children(&self) -> Vec<Self> {
+        match self.value {
+            serde_json::Value::Object(object) => object
+                .iter()
+                .map(|(key, value)| self.child(key, value))
+                .collect(),
+            serde_json::Value::Array(array) => array
+                .iter()
+                .enumerate()
+                .map(|(index, value)| self.child(&index.to_string(), value))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn descendants_or_self(&self) -> Vec<Self> {
+        let mut items = vec![self.clone()];
+        for child in self.children() {
+            items.extend(child.descendants_or_self());
+        }
+        items
+    }
+
+    /// Text of the scalar value, with numbers and booleans formatted as in JSON.
+    fn text(&self) -> Option<Cow<'doc, str>> {
+        match self.value {
+            serde_json::Value::String(text) => Some(Cow::Borrowed(text)),
+            serde_json::Value::Number(number) => Some(Cow::Owned(number.to_string())),
+            serde_json::Value::Bool(boolean) => Some(Cow::Owned(boolean.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl<'doc> Document<'doc> for &'doc serde_json::Value {
+    type Item = JsonItem<'doc>;
+
+    fn roots(self) -> Result<Vec<Self::Item>, SelectError> {
+        Ok(vec![JsonItem {
+            pointer: String::new(),
+            value: self,
+        }])
+    }
+
+    /// Items of combined selectors are ordered as the selectors are listed.
+    fn select(self, selector: &Selector, item: Self::Item) -> Result<Vec<Self::Item>, SelectError> {
+        match selector {
+            Selector::JsonPath(path) => Ok(path.apply(item)),
+            Selector::XPath(_) => Err(SelectError::Unsupported(
+                "XPath selectors cannot be applied to JSON documents",
+            )),
+            Selector::Combined(Combinator::Ordered(selectors) | Combinator::Union(selectors)) => {
+                let mut items = Vec::<JsonItem>::new();
+                for selector in selectors {
+                    for found in self.select(selector, item.clone())? {
+                        if !items.iter().any(|item| item.pointer == found.pointer) {
+                            items.push(found);
+                        }
+                    }
+                }
+                Ok(items)
+            }
+            Selector::Combined(
+                Combinator::Intersect(selectors) | Combinator::Difference(selectors),
+            ) => {
+                let matched = matches!(selector, Selector::Combined(Combinator::Intersect(_)));
+                let mut selectors = selectors.iter();
+                let mut items = match selectors.next() {
+                    Some(selector) => self.select(selector, item.clone())?,
+                    None => vec![],
+                };
+                for selector in selectors {
+                    let other = self.select(selector, item.clone())?;
+                    items.retain(|item| {
+                        other.iter().any(|other| other.pointer == item.pointer) == matched
+                    });
+                }
+                Ok(items)
+            }
+        }
+    }
+
+    fn text(self, item: &Self::Item) -> Option<Cow<'doc, str>> {
+        item.text()
+    }
+
+    /// Extractors which work with text are applied to scalar values, while objects and arrays are unknown.
+    fn extract(self, extractor: &ValueExtractor, items: Vec<Self::Item>) -> Vec<Value<'doc>> {
+        match extractor {
+            ValueExtractor::Count => return vec![Value::Number(items.len() as f64)],
+            ValueExtractor::Exists => return vec![Value::Bool(!items.is_empty())],
+            ValueExtractor::Join {
+                separator,
+                on_empty,
+            } => {
+                return vec![job::join_texts(
+                    items.iter().filter_map(JsonItem::text),
+                    separator,
+                    *on_empty,
+                )]
+            }
+            _ => {}
+        }
+        items
+            .into_iter()
+            .map(|item| match extractor {
+                ValueExtractor::Json => Value::Json(item.value.clone()),
+                ValueExtractor::Text => item.text().map_or(Value::Unknown, Value::String),
+                ValueExtractor::Attribute(name) => match item.value.get(name) {
+                    Some(value) => item.child(name, value).text(),
+                    None => None,
+                }
+                .map_or(Value::Unknown, Value::String),
+                ValueExtractor::Number { .. } if item.value.is_number() => {
+                    item.value.as_f64().map_or(Value::Unknown, Value::Number)
+                }
+                extractor => item
+                    .text()
+                    .map_or(Value::Unknown, |text| extractor.extract_text(text)),
+            })
+            .collect()
+    }
+
+    fn seo(self, _items: Vec<Self::Item>, _base: Option<&Url>) -> ProcessingResult<'doc> {
+        ProcessingResult::Error(SelectError::Unsupported(
+            "SEO metadata can only be extracted from HTML documents",
+        ))
+    }
+
+    fn path(self, item: &Self::Item) -> Option<String> {
+        Some(item.pointer.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn path(raw: &str) -> ParsedJsonPath {
+        raw.to_owned().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_json_path_selects_items() {
+        let document = json!({
+            "store": {
+                "items": [
+                    { "name": "Loom Hoodie", "price": 49 },
+                    { "name": "Canvas Tee", "price": 19, "tags": { "name": "sale" } },
+                ],
+                "owner.name": "Jarvis",
+            },
+        });
+        let select = |raw| {
+            path(raw)
+                .apply(document.roots().unwrap().remove(0))
+                .into_iter()
+                .map(|item| (item.pointer, item.value.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            select("$.store.items[*].name"),
+            [
+                ("/store/items/0/name".to_owned(), json!("Loom Hoodie")),
+                ("/store/items/1/name".to_owned(), json!("Canvas Tee")),
+            ]
+        );
+        assert_eq!(
+            select("$.store.items[-1].price"),
+            [("/store/items/1/price".to_owned(), json!(19))]
+        );
+        assert_eq!(
+            select("$['store']['owner.name']"),
+            [("/store/owner.name".to_owned(), json!("Jarvis"))]
+        );
+        assert_eq!(
+            select("$..name")
+                .into_iter()
+                .map(|(pointer, _)| pointer)
+                .collect::<Vec<_>>(),
+            [
+                "/store/items/0/name",
+                "/store/items/1/name",
+                "/store/items/1/tags/name"
+            ]
+        );
+        assert!(select("$.store.items[2]").is_empty());
+        assert!(select("$.store.missing.name").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_json_paths_are_rejected() {
+        for raw in ["store.items", "$.store[", "$.items[one]", "$['name", "$.."] {
+            assert!(
+                ParsedJsonPath::try_from(raw.to_owned()).is_err(),
+                "{raw:?} should be invalid"
+            );
+        }
+    }
+}
@@ -1,27 +1,101 @@
+mod api;
 mod cmd;
+mod cookies;
+mod dedup;
+mod diff;
+mod document;
+mod fetch;
 mod job;
+mod join;
+mod json;
+mod product;
+mod progress;
+mod quota;
+mod robots;
+mod seo;
+mod series;
+mod sink;
+#[cfg(test)]
+mod testing;
 
-use std::{borrow::Cow, collections::VecDeque, io, path::PathBuf, process::ExitCode};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    io, mem,
+    net::SocketAddr,
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
+use chrono::Utc;
 use clap::Parser;
 use config::{Config, ConfigError};
+use encoding_rs::Encoding;
+use futures_util::FutureExt;
 use indexmap::IndexMap;
-use job::Job;
-use serde::Deserialize;
+use job::{Job, JobState};
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use skyscraper::{
     html,
-    xpath::{xpath_item_set::XpathItemSet, ExpressionApplyError, XpathItemTree},
+    xpath::{
+        grammar::{
+            data_model::{Node, XpathItem},
+            XpathItemTreeNodeData,
+        },
+        XpathItemTree,
+    },
 };
-use tokio::{fs, signal::ctrl_c};
-use tracing::{debug, error, info, span, warn, Level};
-
+use tokio::{
+    fs,
+    io::AsyncReadExt,
+    signal::ctrl_c,
+    sync::{watch, Notify, OnceCell},
+    task::JoinHandle,
+};
+use tracing::{debug, error, info, span, warn, Instrument, Level, Span};
+use url::Url;
 
-use crate::cmd::CmdArgs;
+use crate::{
+    api::PollEvent,
+    cmd::{CmdArgs, Command, FailOn, LogFormat, RunArgs},
+    dedup::Dedup,
+    document::{Document, SelectError},
+    fetch::{Cassette, Fetched, Fetcher, RedirectError},
+    progress::Progress,
+    quota::{Quota, RequestBudget},
+    sink::OwnedEvent,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct AppConfig {
     /// Resources to be queried
     resources: Vec<Job>,
+    /// Limit on the number of requests performed by all resources
+    #[serde(default)]
+    quota: Option<Quota>,
+    /// Maximal number of requests performed by all resources simultaneously
+    #[serde(default)]
+    max_concurrent_requests: Option<usize>,
+    /// Limit on the rate of requests to each domain performed by all resources
+    #[serde(default)]
+    domain_rate: Option<Quota>,
+    /// Maximal size of a response body of all resources in bytes
+    #[serde(default)]
+    max_body_bytes: Option<u64>,
+    /// User agent sent with requests of all resources
+    #[serde(default)]
+    user_agent: Option<job::UserAgent>,
+    /// Proxy which requests of all resources are routed through
+    #[serde(default)]
+    proxy: Option<job::Proxy>,
 }
 
 /// An error which may occur while loading [config][`AppConfig`].
@@ -34,25 +108,48 @@ pub enum ConfigLoadError {
 }
 
 fn main() -> ExitCode {
-    let config = cmd::CmdArgs::parse();
+    let CmdArgs {
+        config: config_path,
+        log_format,
+        command,
+        run:
+            RunArgs {
+                record,
+                replay,
+                once,
+                fail_on,
+                tags,
+                api_addr,
+            },
+    } = CmdArgs::parse();
 
     #[cfg(feature = "tokio-console")]
-    console_subscriber::init();
+    {
+        let _ = log_format;
+        console_subscriber::init();
+    }
     #[cfg(not(feature = "tokio-console"))]
     {
-        if let Err(error) = tracing_subscriber::fmt()
-            .with_env_filter(tracing_subscriber::EnvFilter::from_env("WETCHER_LOG"))
-            .try_init()
-        {
+        let filter = tracing_subscriber::EnvFilter::from_env("WETCHER_LOG");
+        let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+        let result = match log_format {
+            LogFormat::Text => subscriber.try_init(),
+            LogFormat::Json => subscriber.json().try_init(),
+        };
+        if let Err(error) = result {
             error!("Failed to initialize fmt tracing subscriber: {error}");
             return ExitCode::FAILURE;
         }
     }
 
-    let config = match load_config(config) {
+    if let Some(Command::Validate) = command {
+        return validate(config_path);
+    }
+
+    let config = match load_config(config_path.clone()) {
         Ok(config) => {
             info!("Loaded config: {config:?}");
-            config
+            select_tagged(config, &tags)
         }
         Err(error) => {
             error!("Failed to load configuration: {error}");
@@ -60,172 +157,3331 @@ fn main() -> ExitCode {
         }
     };
 
+    let cassette = match (record, replay) {
+        (Some(directory), _) => Some(Cassette::Record(directory)),
+        (None, Some(directory)) => Some(Cassette::Replay(directory)),
+        (None, None) => None,
+    };
+    let mut fetcher = match fetch::client(
+        config.user_agent.as_ref(),
+        config.proxy.as_ref(),
+        None,
+        None,
+    ) {
+        Ok(client) => Fetcher::new(client, cassette),
+        Err(error) => {
+            error!("Failed to create HTTP client: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(quota) = config.quota {
+        fetcher = fetcher.with_budget(RequestBudget::new(quota));
+    }
+    if let Some(limit) = config.max_concurrent_requests {
+        fetcher = fetcher.with_concurrency_limit(limit);
+    }
+    if let Some(rate) = config.domain_rate {
+        fetcher = fetcher.with_domain_rate(rate);
+    }
+    if let Some(max_body_bytes) = config.max_body_bytes {
+        fetcher = fetcher.with_max_body_bytes(max_body_bytes);
+    }
+    if let Some(user_agent) = config.user_agent.clone() {
+        fetcher = fetcher.with_user_agent(user_agent);
+    }
+    if let Some(proxy) = config.proxy.clone() {
+        fetcher = fetcher.with_proxy(proxy);
+    }
+    let api = match api_addr.map(bind_api).transpose() {
+        Ok(api) => api,
+        Err(error) => {
+            error!("Failed to bind API: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
     info!("Running app..");
 
-    match start(config) {
-        Ok(()) => {
-            info!("Received CTRL-C signal, shutting down");
-            ExitCode::SUCCESS
+    let stats = if once {
+        Ok(start_once(config, fetcher))
+    } else {
+        start(config_path, config, fetcher, api, &tags)
+    };
+    match stats {
+        Ok(stats) => {
+            info!("All jobs finished, shutting down");
+            if has_failed(fail_on, &stats) {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
         }
         Err(error) => {
-            error!("Failed to await for CTRL-C signal: {error}");
+            error!("Failed to listen for signals: {error}");
             ExitCode::FAILURE
         }
     }
 }
 
-fn load_config(CmdArgs { config }: CmdArgs) -> Result<AppConfig, ConfigLoadError> {
+/// Keeps only the jobs having any of the tags, unless there are none.
+fn select_tagged(mut config: AppConfig, tags: &[String]) -> AppConfig {
+    if tags.is_empty() {
+        return config;
+    }
+    config.resources.retain(|job| {
+        let selected = job.tags.iter().any(|tag| tags.contains(tag));
+        if !selected {
+            debug!("Skipping job {:?} without any of the tags", job.name());
+        }
+        selected
+    });
+    info!(
+        "Selected {} jobs tagged with any of {tags:?}",
+        config.resources.len()
+    );
+    config
+}
+
+/// Binds the listener of the [API](api) before the runtime is started, so that failing to do so fails early.
+fn bind_api(address: SocketAddr) -> io::Result<std::net::TcpListener> {
+    let listener = std::net::TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Checks the config without running it, printing the found problems.
+fn validate(config: PathBuf) -> ExitCode {
+    let config = match load_config(config) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Invalid config: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut problems = 0;
+    for (key, quota) in [("quota", config.quota), ("domain_rate", config.domain_rate)] {
+        if let Some(problem) = quota.as_ref().and_then(Quota::problem) {
+            eprintln!("{key} {problem}");
+            problems += 1;
+        }
+    }
+    for job in &config.resources {
+        for problem in job.problems().into_iter().chain(sample_problems(job)) {
+            eprintln!("Resource {}: {problem}", job.name());
+            problems += 1;
+        }
+    }
+    if problems != 0 {
+        eprintln!("Config is invalid, problems found: {problems}");
+        return ExitCode::FAILURE;
+    }
+    println!(
+        "Config is valid, resources found: {}",
+        config.resources.len()
+    );
+    ExitCode::SUCCESS
+}
+
+/// Describes the targets of the job which match nothing in its sample document, if it has one.
+fn sample_problems(job: &Job) -> Vec<String> {
+    let Some(sample) = &job.sample else {
+        return vec![];
+    };
+    let document = match std::fs::read(sample) {
+        Ok(document) => fetch::decode(&document, job.encoding()),
+        Err(error) => {
+            return vec![format!(
+                "failed to read sample {}: {error}",
+                sample.display()
+            )]
+        }
+    };
+    let mut problems = Vec::new();
+    match Documents::parse(job, vec![(None, document)]) {
+        Ok(Documents::Html(trees)) => {
+            for (_, tree) in &trees {
+                sample_roots_problems(tree, &job.targets, &mut problems);
+            }
+        }
+        Ok(Documents::Json(values)) => {
+            for (_, value) in &values {
+                sample_roots_problems(value, &job.targets, &mut problems);
+            }
+        }
+        Err(error) => problems.push(format!("invalid sample {}: {error}", sample.display())),
+    }
+    problems
+}
+
+fn sample_roots_problems<'doc, D: Document<'doc>>(
+    document: D,
+    targets: &job::Targets,
+    problems: &mut Vec<String>,
+) {
+    match document.roots() {
+        Ok(roots) => dead_targets(document, roots, targets, "", problems),
+        Err(error) => problems.push(format!("failed to select roots of the sample: {error}")),
+    }
+}
+
+/// Describes the targets which match nothing relative to all of the items,
+/// checking the nested targets against the items matched by their parents.
+fn dead_targets<'doc, D: Document<'doc>>(
+    document: D,
+    items: Vec<D::Item>,
+    targets: &job::Targets,
+    parent: &str,
+    problems: &mut Vec<String>,
+) {
+    'targets: for (name, target) in &targets.0 {
+        let name = format!("{parent}/{name}");
+        let mut found = Vec::new();
+        for item in &items {
+            match document.select(&target.path, item.clone()) {
+                Ok(items) => found.extend(items),
+                Err(error) => {
+                    problems.push(format!(
+                        "target {name:?} cannot be applied to the sample: {error}"
+                    ));
+                    continue 'targets;
+                }
+            }
+        }
+        if found.is_empty() {
+            problems.push(format!("target {name:?} matches nothing in the sample"));
+        } else if let job::Then::Get(targets) = &target.then {
+            dead_targets(document, found, targets, &name, problems);
+        }
+    }
+}
+
+fn load_config(config: PathBuf) -> Result<AppConfig, ConfigLoadError> {
     let Some(config) = config.to_str() else {
         return Err(ConfigLoadError::NonUtf8Path(config));
     };
 
-    let config: AppConfig = Config::builder()
+    let config = Config::builder()
         .add_source(config::Environment::with_prefix("WETCHER").separator("_"))
         .add_source(config::File::with_name(config).required(false))
-        .build()
-        .and_then(Config::try_deserialize)?;
+        .build()?;
+    let sources: Vec<serde_json::Value> = config.get("resources")?;
+    let mut config: AppConfig = config.try_deserialize()?;
+    let mut names = HashSet::new();
+    for (index, (job, source)) in config.resources.iter_mut().zip(sources).enumerate() {
+        if job.name.is_empty() {
+            job.name = job.resource.address();
+            // Unnamed jobs of the same resource are told apart by their indices.
+            if names.contains(&job.name) {
+                job.name = format!("{} #{index}", job.name);
+            }
+        }
+        names.insert(job.name.clone());
+        job.digest = format!("{:x}", Sha256::digest(source.to_string()));
+    }
 
     Ok(config)
 }
 
+/// Numbers of successful and failed polls of a job, updated as it runs.
+#[derive(Debug, Default)]
+struct PollStats {
+    succeeded: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl PollStats {
+    fn record(&self, succeeded: bool) {
+        let counter = if succeeded {
+            &self.succeeded
+        } else {
+            &self.failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Checks if the process should exit with a failure because of the polls of all the jobs it has run.
+fn has_failed(fail_on: FailOn, stats: &[Arc<PollStats>]) -> bool {
+    let counts = stats.iter().map(|stats| {
+        (
+            stats.succeeded.load(Ordering::Relaxed),
+            stats.failed.load(Ordering::Relaxed),
+        )
+    });
+    let failed = match fail_on {
+        FailOn::AnyFailure => counts
+            .filter(|(_, failed)| *failed > 0)
+            .inspect(|(succeeded, failed)| {
+                error!("Job has failed {failed} polls and succeeded {succeeded}");
+            })
+            .count(),
+        FailOn::NeverSucceeded => counts
+            .filter(|(succeeded, failed)| *succeeded == 0 && *failed > 0)
+            .inspect(|(_, failed)| error!("Job has failed all of its {failed} polls"))
+            .count(),
+        FailOn::Never => 0,
+    };
+    if failed > 0 {
+        error!("{failed} jobs failed");
+    }
+    failed > 0
+}
+
+/// Runs the jobs until CTRL-C is received, reloading them on `SIGHUP`,
+/// and returns the stats of all jobs which have been run.
 #[tokio::main]
-async fn start(config: AppConfig) -> io::Result<()> {
-    for Job {
-        resource,
-        period,
-        targets,
-        continuation,
-    } in config.resources
-    {
-        let _span = span!(Level::INFO, "job", resource = ?&resource).entered();
-        let client = reqwest::Client::new();
-        let mut period = tokio::time::interval(period);
-        let base_resource = resource.clone();
-        tokio::spawn(async move {
-            loop {
-                period.tick().await;
-                let mut resource_queue = VecDeque::new();
-                resource_queue.push_back(base_resource.clone());
-                while let Some(resource) = resource_queue.pop_front() {
-                    match handle(&client, resource.clone(), &targets, &continuation).await {
-                        Ok(continuations) => {
-                            info!("Found continuations: {continuations:?}");
-                            match resource {
-                                job::Resource::Url(url) => {
-                                    resource_queue.extend(continuations.into_iter().map(
-                                        |continuation| {
-                                            let mut url = url.clone();
-                                            url.set_path(
-                                                if continuation.as_bytes().first() == Some(&b'/') {
-                                                    &continuation[1..]
-                                                } else {
-                                                    &continuation
-                                                },
-                                            );
-                                            job::Resource::Url(url)
-                                        },
-                                    ));
+async fn start(
+    config_path: PathBuf,
+    config: AppConfig,
+    fetcher: Fetcher,
+    api: Option<std::net::TcpListener>,
+    tags: &[String],
+) -> io::Result<Vec<Arc<PollStats>>> {
+    let api = match api {
+        Some(listener) => Some(tokio::spawn(api::serve(
+            tokio::net::TcpListener::from_std(listener)?,
+            fetcher.latest_results().clone(),
+        ))),
+        None => None,
+    };
+    let mut jobs = Jobs::new(fetcher);
+    jobs.update(config.resources).await;
+
+    let mut reloads = Signal::hangup()?;
+    let mut triggers = Signal::user_defined1()?;
+    loop {
+        tokio::select! {
+            result = ctrl_c() => {
+                result?;
+                break;
+            }
+            () = triggers.recv() => {
+                info!("Polling all jobs immediately");
+                jobs.trigger();
+            }
+            () = reloads.recv() => {
+                info!("Reloading configuration");
+                match load_config(config_path.clone()) {
+                    Ok(config) => jobs.update(select_tagged(config, tags).resources).await,
+                    Err(error) => {
+                        error!("Failed to reload configuration, keeping the old one: {error}");
+                    }
+                }
+            }
+        }
+    }
+
+    info!("Received CTRL-C signal, waiting for jobs to finish");
+    let stats = jobs.stats.clone();
+    tokio::select! {
+        () = jobs.shutdown() => {}
+        result = ctrl_c() => {
+            result?;
+            warn!("Received CTRL-C signal again, exiting immediately");
+        }
+    }
+    if let Some(api) = api {
+        api.abort();
+    }
+    Ok(stats)
+}
+
+/// Unix signal, which never happens on other platforms.
+struct Signal {
+    #[cfg(unix)]
+    signal: tokio::signal::unix::Signal,
+}
+
+impl Signal {
+    /// `SIGHUP`, which reloads the configuration.
+    fn hangup() -> io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    /// `SIGUSR1`, which makes all jobs poll immediately.
+    fn user_defined1() -> io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if self.signal.recv().await.is_some() {
+            return;
+        }
+        std::future::pending().await
+    }
+}
+
+/// Polls each job once concurrently, returning the stats of the jobs.
+#[tokio::main]
+async fn start_once(config: AppConfig, fetcher: Fetcher) -> Vec<Arc<PollStats>> {
+    let (_shutdown, receiver) = watch::channel(false);
+    let handles: Vec<_> = config
+        .resources
+        .into_iter()
+        .filter(|job| {
+            if !job.enabled {
+                info!("Skipping disabled job {:?}", job.name());
+            }
+            job.enabled
+        })
+        .map(|job| {
+            let span = job_span(&job);
+            let (fetcher, shutdown) = (fetcher.clone(), receiver.clone());
+            tokio::spawn(
+                async move {
+                    let fetcher = match fetcher.for_job(&job) {
+                        Ok(fetcher) => fetcher,
+                        Err(error) => {
+                            error!("Failed to create HTTP client of the job: {error}");
+                            return false;
+                        }
+                    };
+                    load_series(&fetcher, &job).await;
+                    let mut state = JobState::default();
+                    let succeeded = poll_once_isolated(&fetcher, &job, &mut state, &shutdown).await;
+                    state.flush().await;
+                    if let Err(error) = fetcher.save_cookies().await {
+                        error!("Failed to save cookies: {error}");
+                    }
+                    succeeded
+                }
+                .instrument(span),
+            )
+        })
+        .collect();
+
+    let mut stats = Vec::new();
+    for handle in handles {
+        let job_stats = PollStats::default();
+        match handle.await {
+            Ok(succeeded) => job_stats.record(succeeded),
+            Err(error) => {
+                error!("Job failed: {error}");
+                job_stats.record(false);
+            }
+        }
+        stats.push(Arc::new(job_stats));
+    }
+    stats
+}
+
+/// Jobs running in the background, identified by their names.
+struct Jobs {
+    fetcher: Fetcher,
+    running: HashMap<String, RunningJob>,
+    /// Makes the jobs waiting for their next tick poll immediately
+    trigger: Arc<Notify>,
+    /// Stats of all jobs which have been started, including the stopped ones
+    stats: Vec<Arc<PollStats>>,
+}
+
+struct RunningJob {
+    digest: String,
+    shutdown: watch::Sender<bool>,
+    handle: JoinHandle<()>,
+    stats: Arc<PollStats>,
+}
+
+impl Jobs {
+    fn new(fetcher: Fetcher) -> Self {
+        Self {
+            fetcher,
+            running: HashMap::new(),
+            trigger: Arc::default(),
+            stats: Vec::new(),
+        }
+    }
+
+    /// Starts the new jobs and restarts the changed ones, stopping the jobs which are no longer configured.
+    ///
+    /// The jobs which have not changed keep running uninterrupted.
+    async fn update(&mut self, jobs: Vec<Job>) {
+        let mut previous = mem::take(&mut self.running);
+        for job in jobs {
+            let name = job.name().to_owned();
+            if self.running.contains_key(&name) {
+                warn!("Job name {name:?} is duplicated, ignoring the job");
+                continue;
+            }
+            if !job.enabled {
+                match previous.remove(&name) {
+                    Some(running) => {
+                        info!("Stopping disabled job {name:?}");
+                        running.stop().await;
+                    }
+                    None => info!("Skipping disabled job {name:?}"),
+                }
+                continue;
+            }
+            match previous.remove(&name) {
+                Some(running) if running.digest == job.digest => {
+                    debug!("Job {name:?} has not changed");
+                    self.running.insert(name, running);
+                    continue;
+                }
+                Some(running) => {
+                    info!("Restarting changed job {name:?}");
+                    running.stop().await;
+                }
+                None => info!("Starting job {name:?}"),
+            }
+            let running = RunningJob::spawn(job, self.fetcher.clone(), self.trigger.clone());
+            self.stats.push(running.stats.clone());
+            self.running.insert(name, running);
+        }
+        for (name, running) in previous {
+            info!("Stopping removed job {name:?}");
+            running.stop().await;
+        }
+    }
+
+    /// Makes the jobs poll immediately, after which they keep polling at their schedules.
+    ///
+    /// Jobs which are polling at the moment are not polled again.
+    fn trigger(&self) {
+        self.trigger.notify_waiters();
+    }
+
+    /// Stops all jobs, letting their current polls complete.
+    async fn shutdown(self) {
+        for running in self.running.values() {
+            running.shutdown.send_replace(true);
+        }
+        for running in self.running.into_values() {
+            running.join().await;
+        }
+    }
+}
+
+impl RunningJob {
+    fn spawn(job: Job, fetcher: Fetcher, trigger: Arc<Notify>) -> Self {
+        let span = job_span(&job);
+        let (shutdown, receiver) = watch::channel(false);
+        let stats = Arc::new(PollStats::default());
+        Self {
+            digest: job.digest.clone(),
+            shutdown,
+            handle: tokio::spawn(
+                poll(job, fetcher, receiver, trigger, stats.clone()).instrument(span),
+            ),
+            stats,
+        }
+    }
+
+    /// Stops the job, letting its current poll complete.
+    async fn stop(self) {
+        self.shutdown.send_replace(true);
+        self.join().await;
+    }
+
+    async fn join(self) {
+        if let Err(error) = self.handle.await {
+            error!("Job failed: {error}");
+            self.stats.record(false);
+        }
+    }
+}
+
+/// Span of the events of the job, keeping its name and resource as structured fields.
+fn job_span(job: &Job) -> Span {
+    span!(Level::INFO, "job", job = job.name(), resource = ?&job.resource)
+}
+
+/// Periodically polls the resources of the job until the shutdown is signalled.
+async fn poll(
+    job: Job,
+    fetcher: Fetcher,
+    mut shutdown: watch::Receiver<bool>,
+    trigger: Arc<Notify>,
+    stats: Arc<PollStats>,
+) {
+    let fetcher = match fetcher.for_job(&job) {
+        Ok(fetcher) => fetcher,
+        Err(error) => {
+            error!("Failed to create HTTP client of the job: {error}");
+            stats.record(false);
+            return;
+        }
+    };
+    load_series(&fetcher, &job).await;
+    if let Some(start_delay) = job.start_delay {
+        let delay = start_delay.sample(&mut rand::thread_rng());
+        debug!("Delaying the first poll by {delay:?}");
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {}
+            _ = shutdown.changed() => return,
+        }
+    }
+    let mut ticker = job.ticker();
+    let mut state = JobState::default();
+    loop {
+        tokio::select! {
+            () = ticker.tick() => {}
+            () = trigger.notified() => debug!("Polling immediately on request"),
+            _ = shutdown.changed() => break,
+        }
+        stats.record(poll_once_isolated(&fetcher, &job, &mut state, &shutdown).await);
+        if let Err(error) = fetcher.save_cookies().await {
+            error!("Failed to save cookies: {error}");
+        }
+        info!("Awaiting again...");
+    }
+    state.flush().await;
+}
+
+/// Polls the job like [`poll_once`], catching panics so that they only fail the poll.
+///
+/// The state of the job is [reset](JobState::reset) after a panic as it may have been left inconsistent.
+async fn poll_once_isolated(
+    fetcher: &Fetcher,
+    job: &Job,
+    state: &mut JobState,
+    shutdown: &watch::Receiver<bool>,
+) -> bool {
+    match catch_panic(poll_once(fetcher, job, &mut *state, shutdown)).await {
+        Ok(succeeded) => succeeded,
+        Err(message) => {
+            error!("Job {:?} panicked while polling: {message}", job.name());
+            state.reset();
+            false
+        }
+    }
+}
+
+/// Awaits the future, turning its panic into an error with the message of the panic.
+async fn catch_panic<T>(future: impl Future<Output = T>) -> Result<T, String> {
+    AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .map_err(|panic| {
+            panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown cause".to_owned())
+        })
+}
+
+/// Polls the resource of the job and its continuations,
+/// returning whether all of them have been handled successfully.
+async fn poll_once(
+    fetcher: &Fetcher,
+    job: &Job,
+    state: &mut JobState,
+    shutdown: &watch::Receiver<bool>,
+) -> bool {
+    let mut succeeded = true;
+    let mut resource_queue = VecDeque::new();
+    let mut visited = HashSet::new();
+    let progress = match &job.progress {
+        Some(path) => progress::load(path, job.name()).await,
+        None => Progress::default(),
+    };
+    if progress.pending.is_empty() {
+        resource_queue.push_back(job.resource.clone());
+        if let job::Resource::Url(url) = &job.resource {
+            visited.insert(url.clone());
+        }
+    } else {
+        info!("Resuming poll at {} pending pages", progress.pending.len());
+        visited.extend(progress.visited);
+        visited.extend(progress.pending.iter().cloned());
+        resource_queue.extend(progress.pending.into_iter().map(job::Resource::Url));
+    }
+    let mut dedup = job.dedup_by.clone().map(Dedup::new);
+    let mut pages = 0;
+    let started = Instant::now();
+    fetcher.latest_results().publish(|| PollEvent::PollStarted {
+        job: job.name().to_owned(),
+    });
+    while let Some(resource) = resource_queue.pop_front() {
+        if *shutdown.borrow() {
+            info!("Shutting down, dropping remaining continuations");
+            break;
+        }
+        if job.max_pages.is_some_and(|max_pages| pages >= max_pages) {
+            info!("Reached the maximal number of pages, dropping remaining continuations");
+            break;
+        }
+        pages += 1;
+        match handle(fetcher, job, state, &resource, pages, dedup.as_mut()).await {
+            Ok(continuations) => {
+                info!("Found continuations: {continuations:?}");
+                match resource {
+                    job::Resource::Url(_) => {
+                        resource_queue.extend(continuations.into_iter().filter_map(
+                            |continuation| match continuation {
+                                Ok(url) if !visited.insert(url.clone()) => {
+                                    debug!("Page {url} has already been visited, skipping");
+                                    None
                                 }
-                                job::Resource::Path(_) => {
-                                    warn!("Path resource does not support continuation yet");
+                                Ok(url) => Some(job::Resource::Url(url)),
+                                Err(error) => {
+                                    warn!("Skipping continuation: {error}");
+                                    None
                                 }
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to handle: {e}");
-                        }
+                            },
+                        ));
+                    }
+                    job::Resource::Path(_) => {
+                        warn!("Path resource does not support continuation yet");
+                    }
+                    job::Resource::Glob(_) => {
+                        warn!("Glob resource does not support continuation yet");
+                    }
+                    job::Resource::Mirrors(_) => {
+                        warn!("Mirrors resource does not support continuation yet");
+                    }
+                    job::Resource::Stdin => {
+                        warn!("Stdin resource does not support continuation yet");
                     }
                 }
-                info!("Awaiting again...");
             }
+            Err(e @ (HandleError::QuotaExceeded | HandleError::DisallowedByRobots(_))) => {
+                warn!("Skipping poll: {e}");
+                succeeded = false;
+            }
+            Err(e) => {
+                error!("Failed to handle: {e}");
+                succeeded = false;
+            }
+        }
+        save_progress(job, &resource_queue, &visited).await;
+    }
+    if !*shutdown.borrow() {
+        save_progress(job, &VecDeque::new(), &visited).await;
+    }
+    if let Some(result) = dedup.and_then(Dedup::into_result) {
+        emit(fetcher, job, state, &job.resource, result).await;
+    }
+    info!(
+        pages,
+        duration_ms = started.elapsed().as_millis() as u64,
+        succeeded,
+        "Finished poll"
+    );
+    fetcher
+        .latest_results()
+        .publish(|| PollEvent::PollCompleted {
+            job: job.name().to_owned(),
+            pages,
+            succeeded,
         });
+    succeeded
+}
+
+/// Persists the pages remaining in the queue if the job keeps its progress, clearing it if there are none.
+async fn save_progress(job: &Job, queue: &VecDeque<job::Resource>, visited: &HashSet<Url>) {
+    let Some(path) = &job.progress else {
+        return;
+    };
+    let pending: Vec<_> = queue
+        .iter()
+        .filter_map(|resource| match resource {
+            job::Resource::Url(url) => Some(url.clone()),
+            _ => None,
+        })
+        .collect();
+    let progress = Progress {
+        visited: if pending.is_empty() {
+            vec![]
+        } else {
+            visited.iter().cloned().collect()
+        },
+        pending,
+    };
+    if let Err(error) = progress::save(path, job.name(), progress).await {
+        error!("Failed to save progress to {}: {error}", path.display());
     }
+}
 
-    ctrl_c().await
+/// Restores the series of the tracked targets of the job if it keeps them.
+async fn load_series(fetcher: &Fetcher, job: &Job) {
+    if let Some(path) = &job.series {
+        let series = series::load(path, job.name()).await;
+        debug!("Loaded series of {} tracked targets", series.len());
+        fetcher.latest_results().restore_series(job.name(), series);
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 enum HandleError {
     #[error("failed to execute request")]
     Send(#[from] reqwest::Error),
+    #[error("no response recorded for {0} {1}")]
+    NotRecorded(reqwest::Method, Url),
+    #[error("invalid recorded response")]
+    InvalidRecord(#[from] serde_json::Error),
+    #[error("invalid JSON document")]
+    InvalidJson(#[source] serde_json::Error),
     #[error(transparent)]
     InvalidHtml(#[from] html::parse::ParseError),
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error("invalid glob pattern")]
+    InvalidGlob(#[from] glob::PatternError),
+    #[error(transparent)]
+    Redirect(RedirectError),
+    #[error("request timed out")]
+    Timeout,
+    #[error("robots.txt is unavailable: {0}")]
+    RobotsUnavailable(reqwest::StatusCode),
+    #[error("{0} is disallowed by robots.txt")]
+    DisallowedByRobots(Url),
+    #[error("request quota is exceeded")]
+    QuotaExceeded,
+    #[error("no mirrors are configured")]
+    NoMirrors,
+    #[error("document does not satisfy the success criterion")]
+    Unsuccessful,
+    #[error("unsuccessful response status: {0}")]
+    HttpStatus(reqwest::StatusCode),
+    #[error("response body exceeds {0} bytes")]
+    BodyTooLarge(u64),
+    #[error("standard input is not valid UTF-8: {0}")]
+    NonUtf8Stdin(#[source] std::str::Utf8Error),
 }
 
-#[tracing::instrument(skip(client), fields(resource = %resource))]
+/// Handles the resource, emitting its results unless they should be accumulated for deduplication.
+///
+/// The [query](job::QueryParams) of the job rendered for the page is only applied to the request,
+/// so that the state of the resource is kept under its configured URL between polls.
+#[tracing::instrument(skip(fetcher, job, state, dedup), fields(job = job.name(), resource = %resource))]
 async fn handle(
-    client: &reqwest::Client,
-    resource: job::Resource,
-    targets: &job::Targets,
-    continuation: &job::Continuation,
-) -> Result<Vec<String>, HandleError> {
+    fetcher: &Fetcher,
+    job: &Job,
+    state: &mut JobState,
+    resource: &job::Resource,
+    page: usize,
+    dedup: Option<&mut Dedup>,
+) -> Result<Vec<Result<Url, job::InvalidContinuation>>, HandleError> {
     info!("Performing request");
-    let document = match resource {
-        job::Resource::Url(url) => client.get(url).send().await?.text().await?,
-        job::Resource::Path(path) => fs::read_to_string(path).await?,
+    let mut headers = None;
+    let (documents, base) = match resource {
+        job::Resource::Url(url) => {
+            let request_url = job.query.apply(url, page);
+            let response = fetch_url(fetcher, job, state.validators(url), &request_url).await?;
+            if !is_modified(job, state, url, &response) {
+                return Ok(vec![]);
+            }
+            if !job.response_headers.is_empty() {
+                headers = Some(capture_headers(&response.headers, &job.response_headers));
+            }
+            (vec![(None, response.body)], Some(url.clone()))
+        }
+        job::Resource::Mirrors(mirrors) => {
+            let (response, url) = fetch_mirrors(fetcher, job, state, mirrors).await?;
+            if !is_modified(job, state, &url, &response) {
+                return Ok(vec![]);
+            }
+            if !job.response_headers.is_empty() {
+                headers = Some(capture_headers(&response.headers, &job.response_headers));
+            }
+            (vec![(None, response.body)], Some(url))
+        }
+        job::Resource::Path(path) => {
+            if !job.response_headers.is_empty() {
+                warn!("Path resource has no response headers to capture");
+            }
+            let document = fetch::decode(&fs::read(path).await?, job.encoding());
+            (vec![(None, document)], None)
+        }
+        job::Resource::Glob(pattern) => {
+            if !job.response_headers.is_empty() {
+                warn!("Glob resource has no response headers to capture");
+            }
+            (read_glob(pattern, job.encoding()).await?, None)
+        }
+        job::Resource::Stdin => {
+            if !job.response_headers.is_empty() {
+                warn!("Stdin resource has no response headers to capture");
+            }
+            (vec![(None, read_stdin().await?)], None)
+        }
     };
-    debug!("Received document body: {document:?}");
 
-    let document = html::parse(&document)?;
+    let documents = Documents::parse(job, documents)?;
+    let context = ProcessingContext {
+        base: base.as_ref(),
+        annotate_paths: job.annotate_paths,
+    };
+    let mut result = match &documents {
+        Documents::Html(trees) => process_documents(trees, &job.targets, context),
+        Documents::Json(values) => process_documents(values, &job.targets, context),
+    };
+    let missing = result.is_missing();
+    if let (ProcessingResult::Group(group), Some(headers)) = (&mut result, headers) {
+        if group.contains_key(HEADERS_KEY) {
+            warn!("Results already have {HEADERS_KEY:?}, not adding captured headers");
+        } else {
+            group.insert(Cow::Borrowed(HEADERS_KEY), headers);
+        }
+    }
+    match dedup {
+        Some(dedup) => dedup.merge(result.into_owned()),
+        None => emit(fetcher, job, state, resource, result).await,
+    }
 
-    let tree = XpathItemTree::from(&document);
-    let result = process_targets(
-        &tree,
-        skyscraper::xpath::parse("//")
-            .unwrap()
-            .apply(&tree)
-            .unwrap(),
-        targets,
-    );
-    info!("Found: {result:#?}");
+    if matches!(job.continuation, job::Continuation::Offset { .. }) && missing {
+        debug!("Page has no results, not following continuation");
+        return Ok(vec![]);
+    }
+    let Some(page) = &base else {
+        return Ok(vec![]);
+    };
+    Ok(match &documents {
+        Documents::Html(trees) => trees
+            .iter()
+            .flat_map(|(_, tree)| job.continuations(page, tree))
+            .collect(),
+        Documents::Json(_) => job.json_continuations(page),
+    })
+}
 
-    Ok(continuation.evaluate(&tree))
+/// Named documents parsed according to the [format](job::DocumentFormat) of the job.
+enum Documents {
+    Html(Vec<(Option<String>, XpathItemTree)>),
+    Json(Vec<(Option<String>, serde_json::Value)>),
 }
 
-fn process_targets<'tree>(
-    tree: &'tree XpathItemTree,
-    items: XpathItemSet<'tree>,
-    targets: &'tree job::Targets,
-) -> ProcessingResult<'tree> {
-    ProcessingResult::Group(
-        items
-            .iter()
-            .enumerate()
-            .map(|(id, item)| {
-                let group = ProcessingResult::Group(
-                    targets
-                        .0
-                        .iter()
-                        .map(|(name, job::Target { path, then })| {
-                            (
-                                Cow::Borrowed(name.as_str()),
-                                match path.to_xpath().apply_to_item(tree, item.clone()) {
-                                    Ok(items) => match then {
-                                        job::Then::Get(next_targets) => {
-                                            process_targets(tree, items, next_targets)
-                                        }
-                                        job::Then::Extract(extractor) => {
-                                            ProcessingResult::Values(extractor.extract(items))
-                                        }
-                                    },
-                                    Err(error) => ProcessingResult::Error(error),
-                                },
-                            )
-                        })
-                        .collect(),
+impl Documents {
+    /// Parses the documents, failing if any of them does not satisfy the success criterion of the job.
+    fn parse(job: &Job, documents: Vec<(Option<String>, String)>) -> Result<Self, HandleError> {
+        let check = |document: &str, tree: Option<&XpathItemTree>| match &job.success_when {
+            Some(criterion) if !criterion.is_met(document, tree) => Err(HandleError::Unsuccessful),
+            _ => Ok(()),
+        };
+        let documents = documents.into_iter().inspect(|(_, document)| {
+            debug!("Received document body: {document:?}");
+        });
+        Ok(match job.format {
+            job::DocumentFormat::Html => Self::Html(
+                documents
+                    .map(|(name, document)| {
+                        let tree = XpathItemTree::from(&html::parse(&document)?);
+                        check(&document, Some(&tree))?;
+                        Ok((name, tree))
+                    })
+                    .collect::<Result<_, HandleError>>()?,
+            ),
+            job::DocumentFormat::Json => Self::Json(
+                documents
+                    .map(|(name, document)| {
+                        let value =
+                            serde_json::from_str(&document).map_err(HandleError::InvalidJson)?;
+                        check(&document, None)?;
+                        Ok((name, value))
+                    })
+                    .collect::<Result<_, HandleError>>()?,
+            ),
+        })
+    }
+}
+
+/// Queues the results of the resource for the sinks of the job,
+/// or only their changes since the previous poll if they should be detected.
+async fn emit(
+    fetcher: &Fetcher,
+    job: &Job,
+    state: &mut JobState,
+    resource: &job::Resource,
+    mut result: ProcessingResult<'_>,
+) {
+    let results = fetcher.latest_results();
+    if let Some(join) = &job.join {
+        match results.get(&join.job) {
+            Some(other) => {
+                let joined = join::join(&mut result, &join.by, other);
+                debug!(
+                    "Joined {joined} records with the latest results of {:?}",
+                    join.job
                 );
-                (Cow::Owned(format!("[{id}]")), group)
-            })
-            .collect(),
-    )
+            }
+            None => debug!("No results of {:?} to join yet", join.job),
+        }
+    }
+    results.update(job.name(), result.to_json());
+    let now = Utc::now();
+    for target in &job.track {
+        match result.number(target) {
+            Some(number) => results.track(job.name(), target, now, number),
+            None => debug!("No number of tracked target {target:?} found"),
+        }
+    }
+    if let Some(path) = &job.series {
+        if let Err(error) = series::save(path, job.name(), results.job_series(job.name())).await {
+            error!("Failed to save series to {}: {error}", path.display());
+        }
+    }
+    let changes = if job.detect_changes || job.change_count {
+        let changes = state.changes(resource, result.to_json(), &job.targets);
+        if let Some(changes) = &changes {
+            results.count_changes(job.name(), changes.len());
+            if !changes.is_empty() {
+                results.publish(|| PollEvent::Changed {
+                    job: job.name().to_owned(),
+                    changes: changes.clone(),
+                });
+            }
+        }
+        changes
+    } else {
+        None
+    };
+    let event = if job.detect_changes {
+        debug!("Found: {result:#?}");
+        match changes {
+            None => {
+                info!("Remembered initial results");
+                None
+            }
+            Some(changes) if changes.is_empty() => {
+                info!("No changes since previous poll");
+                None
+            }
+            Some(changes) => {
+                for change in &changes {
+                    info!("Changed: {change}");
+                }
+                Some(OwnedEvent::Changed(changes))
+            }
+        }
+    } else {
+        info!("Found: {result:#?}");
+        let mut result = result.into_owned();
+        if let (true, ProcessingResult::Group(group)) = (job.change_count, &mut result) {
+            // The count of the initial results is unknown as there is nothing to compare them with.
+            let count = changes.map_or(job::Value::Unknown, |changes| {
+                job::Value::Number(changes.len() as f64)
+            });
+            group.insert(
+                Cow::Borrowed(CHANGE_COUNT_KEY),
+                ProcessingResult::Values(vec![count]),
+            );
+        }
+        Some(OwnedEvent::Polled(result))
+    };
+    if let Some(event) = event {
+        state.write(fetcher.shared_client(), job, event).await;
+    }
 }
 
-#[allow(dead_code)] // Ony used for `Debug`.
-#[derive(Debug)]
-enum ProcessingResult<'tree> {
-    Group(IndexMap<Cow<'tree, str>, ProcessingResult<'tree>>),
+/// Fetches the URL, retrying network failures and server errors as configured by the job.
+///
+/// The request is conditional if validators of the previous response are given.
+async fn fetch_url(
+    fetcher: &Fetcher,
+    job: &Job,
+    validators: Option<&job::Validators>,
+    url: &Url,
+) -> Result<Fetched, HandleError> {
+    if job.respect_robots
+        && !fetcher
+            .allowed_by_robots(url, job.user_agent.as_ref())
+            .await?
+    {
+        return Err(HandleError::DisallowedByRobots(url.clone()));
+    }
+    let mut delays = job.retry.iter().flat_map(job::Retry::delays);
+    loop {
+        fetcher.wait_for_domain(url, job.domain_rate).await;
+        let request = fetcher.request(job, validators, url)?;
+        let started = Instant::now();
+        let result = fetcher.fetch(request).await;
+        let duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(response) => info!(
+                %url,
+                status = response.status.as_u16(),
+                duration_ms,
+                "Received response"
+            ),
+            Err(error) => warn!(%url, duration_ms, %error, "Request failed"),
+        }
+        let retryable = match &result {
+            Ok(response) => {
+                response.status.is_server_error()
+                    || response.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(HandleError::Send(_) | HandleError::Timeout) => true,
+            Err(_) => false,
+        };
+        match delays.next() {
+            Some(mut delay) if retryable => {
+                let requested = result
+                    .as_ref()
+                    .ok()
+                    .and_then(|response| fetch::retry_after(&response.headers));
+                if let (Some(requested), Some(retry)) = (requested, &job.retry) {
+                    if requested > retry.max_retry_after {
+                        warn!(
+                            "Server requested retrying in {requested:?}, limiting it to {:?}",
+                            retry.max_retry_after
+                        );
+                    }
+                    delay = requested.min(retry.max_retry_after);
+                }
+                warn!("Request to {url} failed, retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+            }
+            _ => return result.and_then(|response| check_status(job, response)),
+        }
+    }
+}
+
+/// Fails on an unsuccessful status unless it is accepted by the job.
+///
+/// `304 Not Modified` is always accepted as it is handled by [`is_modified`].
+fn check_status(job: &Job, response: Fetched) -> Result<Fetched, HandleError> {
+    let status = response.status;
+    if status.is_success()
+        || status == reqwest::StatusCode::NOT_MODIFIED
+        || job.accept_status.contains(&status.as_u16())
+    {
+        Ok(response)
+    } else {
+        Err(HandleError::HttpStatus(status))
+    }
+}
+
+/// Fetches one of the mirrors chosen by weight, falling back to the others on failure.
+async fn fetch_mirrors(
+    fetcher: &Fetcher,
+    job: &Job,
+    state: &JobState,
+    mirrors: &[job::Mirror],
+) -> Result<(Fetched, Url), HandleError> {
+    let mirrors = job::Mirror::weighted_order(mirrors, &mut rand::thread_rng());
+    let mut last_error = HandleError::NoMirrors;
+    for job::Mirror { url, .. } in mirrors {
+        match fetch_url(fetcher, job, state.validators(url), url).await {
+            Ok(response) => return Ok((response, url.clone())),
+            Err(error @ HandleError::QuotaExceeded) => return Err(error),
+            Err(error) => {
+                warn!("Failed to fetch mirror {url}, falling back: {error}");
+                last_error = error;
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Checks whether the response is not `304 Not Modified`, remembering its validators if needed.
+fn is_modified(job: &Job, state: &mut JobState, url: &Url, response: &Fetched) -> bool {
+    if response.status == reqwest::StatusCode::NOT_MODIFIED {
+        info!("{url} is not modified since previous poll, skipping");
+        return false;
+    }
+    if job.conditional_requests {
+        state.remember_validators(url, &response.headers);
+    }
+    true
+}
+
+/// Reads all files matching the glob pattern, naming each document by its path.
+///
+/// Files which cannot be read are skipped with an error logged, so that they do not fail the others.
+async fn read_glob(
+    pattern: &str,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<(Option<String>, String)>, HandleError> {
+    let mut documents = Vec::new();
+    for path in glob::glob(pattern)? {
+        let path = match path {
+            Ok(path) => path,
+            Err(error) => {
+                error!(
+                    "Failed to read {}, skipping: {error}",
+                    error.path().display()
+                );
+                continue;
+            }
+        };
+        match fs::read(&path).await {
+            Ok(document) => documents.push((
+                Some(path.display().to_string()),
+                fetch::decode(&document, encoding),
+            )),
+            Err(error) => error!("Failed to read {}, skipping: {error}", path.display()),
+        }
+    }
+
+    Ok(documents)
+}
+
+/// Reads the standard input to the end on the first call, returning the same document on the following ones.
+async fn read_stdin() -> Result<String, HandleError> {
+    static STDIN: OnceCell<Vec<u8>> = OnceCell::const_new();
+
+    let input = STDIN
+        .get_or_try_init(|| async {
+            let mut input = Vec::new();
+            tokio::io::stdin().read_to_end(&mut input).await?;
+            Ok::<_, io::Error>(input)
+        })
+        .await?;
+    decode_stdin(input)
+}
+
+fn decode_stdin(input: &[u8]) -> Result<String, HandleError> {
+    std::str::from_utf8(input)
+        .map(str::to_owned)
+        .map_err(HandleError::NonUtf8Stdin)
+}
+
+/// Processes targets against each of the documents.
+///
+/// A single unnamed document produces its result as is,
+/// otherwise the results are grouped by document names.
+fn process_documents<'doc, T>(
+    documents: &'doc [(Option<String>, T)],
+    targets: &'doc job::Targets,
+    context: ProcessingContext,
+) -> ProcessingResult<'doc>
+where
+    &'doc T: Document<'doc>,
+{
+    let process = |document: &'doc T| match document.roots() {
+        Ok(roots) => process_targets(document, roots, targets, None, context),
+        Err(error) => ProcessingResult::Error(error),
+    };
+
+    match documents {
+        [(None, document)] => process(document),
+        documents => ProcessingResult::Group(
+            documents
+                .iter()
+                .map(|(name, document)| {
+                    (
+                        Cow::Borrowed(name.as_deref().unwrap_or_default()),
+                        process(document),
+                    )
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Key of the captured response headers in the root group of results,
+/// which is not a record of the documents and is skipped as such by sinks and deduplication.
+const HEADERS_KEY: &str = "_headers";
+
+/// Key of the number of [changes](job::Job::change_count) in the root group of results,
+/// which is not a record of the documents either.
+const CHANGE_COUNT_KEY: &str = "_change_count";
+
+/// Captures values of the given response headers, keyed by the configured header names.
+fn capture_headers(
+    headers: &reqwest::header::HeaderMap,
+    names: &[String],
+) -> ProcessingResult<'static> {
+    ProcessingResult::Group(
+        names
+            .iter()
+            .map(|name| {
+                let values = headers
+                    .get_all(name.as_str())
+                    .iter()
+                    .map(|value| {
+                        value.to_str().map_or(job::Value::Unknown, |value| {
+                            job::Value::String(Cow::Owned(value.to_owned()))
+                        })
+                    })
+                    .collect();
+                (Cow::Owned(name.clone()), ProcessingResult::Values(values))
+            })
+            .collect(),
+    )
+}
+
+/// Settings shared by all targets processed within a single document.
+#[derive(Debug, Clone, Copy, Default)]
+struct ProcessingContext<'a> {
+    /// URL against which relative URLs are resolved
+    base: Option<&'a Url>,
+    /// Whether each record should be annotated with the absolute XPath of its node
+    annotate_paths: bool,
+}
+
+/// Processes the targets against each of the items, producing a record per item.
+///
+/// Records are keyed by the text found by the `key` selector or by the index of the item otherwise.
+fn process_targets<'doc, D: Document<'doc>>(
+    document: D,
+    items: Vec<D::Item>,
+    targets: &'doc job::Targets,
+    key: Option<&'doc job::Selector>,
+    context: ProcessingContext,
+) -> ProcessingResult<'doc> {
+    let mut records = IndexMap::new();
+    for (id, item) in items.iter().enumerate() {
+        let Some(record) = process_record(document, id, item, targets, context) else {
+            continue;
+        };
+        let index = || Cow::Owned(format!("[{id}]"));
+        let name = match key.map(|key| record_key(document, item, key)) {
+            None => index(),
+            Some(Some(name)) if !records.contains_key(&name) => name,
+            Some(Some(name)) => {
+                warn!("Record [{id}] has duplicate key {name:?}, keying it by index");
+                index()
+            }
+            Some(None) => {
+                warn!("Record [{id}] has no key, keying it by index");
+                index()
+            }
+        };
+        records.insert(name, record);
+    }
+
+    ProcessingResult::Group(records)
+}
+
+fn process_record<'doc, D: Document<'doc>>(
+    document: D,
+    id: usize,
+    item: &D::Item,
+    targets: &'doc job::Targets,
+    context: ProcessingContext,
+) -> Option<ProcessingResult<'doc>> {
+    let mut group: IndexMap<_, _> = targets
+        .0
+        .iter()
+        .map(|(name, target)| {
+            let result = process_target(document, item, target, context);
+            if target.required && result.is_missing() {
+                debug!("Skipping record [{id}] with missing required target {name:?}");
+                return None;
+            }
+
+            Some((Cow::Borrowed(name.as_str()), result))
+        })
+        .collect::<Option<_>>()?;
+    if context.annotate_paths {
+        let path = document.path(item).map_or(job::Value::Unknown, |path| {
+            job::Value::String(Cow::Owned(path))
+        });
+        group.insert(Cow::Borrowed("_path"), ProcessingResult::Values(vec![path]));
+    }
+
+    Some(ProcessingResult::Group(group))
+}
+
+/// Gets the text of the first item found by the key selector relative to the record's item.
+fn record_key<'doc, D: Document<'doc>>(
+    document: D,
+    item: &D::Item,
+    key: &job::Selector,
+) -> Option<Cow<'doc, str>> {
+    let items = document
+        .select(key, item.clone())
+        .inspect_err(|error| warn!("Failed to apply key selector: {error}"))
+        .ok()?;
+    let key = document.text(items.first()?)?;
+    (!key.is_empty()).then_some(key)
+}
+
+fn process_target<'doc, D: Document<'doc>>(
+    document: D,
+    item: &D::Item,
+    target: &'doc job::Target,
+    context: ProcessingContext,
+) -> ProcessingResult<'doc> {
+    let job::Target {
+        path, then, key, ..
+    } = target;
+    match document.select(path, item.clone()) {
+        Ok(items) => match then {
+            job::Then::Get(next_targets) => {
+                process_targets(document, items, next_targets, key.as_ref(), context)
+            }
+            job::Then::Extract(extractor) => {
+                let mut values = document.extract(extractor, items);
+                target.transform(&mut values);
+                target.fill_defaults(&mut values);
+                ProcessingResult::Values(values)
+            }
+            job::Then::Seo => document.seo(items, context.base),
+            job::Then::Zip(zip) => zip_items(document, items, zip),
+            job::Then::RegexCaptures { pattern } => capture_groups(document, items, pattern),
+            job::Then::Product(product) => product::extract(document, items, product, context.base),
+        },
+        Err(error) => ProcessingResult::Error(error),
+    }
+}
+
+/// Pairs labels with values found relative to each of the items by their positions.
+///
+/// Excess labels or values are ignored with a warning.
+fn zip_items<'doc, D: Document<'doc>>(
+    document: D,
+    items: Vec<D::Item>,
+    job::Zip { labels, values }: &'doc job::Zip,
+) -> ProcessingResult<'doc> {
+    let mut group = IndexMap::<_, Vec<_>>::new();
+    for item in items {
+        let (labels, values) = match (
+            document.select(labels, item.clone()),
+            document.select(values, item),
+        ) {
+            (Ok(labels), Ok(values)) => (labels, values),
+            (Err(error), _) | (_, Err(error)) => return ProcessingResult::Error(error),
+        };
+        if labels.len() != values.len() {
+            warn!(
+                "Found {} labels but {} values to zip, ignoring the excess",
+                labels.len(),
+                values.len()
+            );
+        }
+        for (label, value) in labels.iter().zip(&values) {
+            let Some(label) = document.text(label) else {
+                warn!("Label {label:?} has no text, skipping it");
+                continue;
+            };
+            let value = document
+                .text(value)
+                .map_or(job::Value::Unknown, job::Value::String);
+            group.entry(label).or_default().push(value);
+        }
+    }
+
+    ProcessingResult::Group(
+        group
+            .into_iter()
+            .map(|(label, values)| (label, ProcessingResult::Values(values)))
+            .collect(),
+    )
+}
+
+/// Maps the named capture groups of the pattern to their matches in the text of each item.
+///
+/// Items whose text does not match are skipped, while groups not participating in a match are unknown.
+fn capture_groups<'doc, D: Document<'doc>>(
+    document: D,
+    items: Vec<D::Item>,
+    job::ParsedRegex(regex): &'doc job::ParsedRegex,
+) -> ProcessingResult<'doc> {
+    let mut group = IndexMap::<_, Vec<_>>::new();
+    for item in items {
+        let Some(text) = document.text(&item) else {
+            continue;
+        };
+        let Some(captures) = regex.captures(&text) else {
+            debug!("Text {text:?} does not match {regex}, skipping it");
+            continue;
+        };
+        for name in regex.capture_names().flatten() {
+            let value = captures.name(name).map_or(job::Value::Unknown, |capture| {
+                job::Value::String(Cow::Owned(capture.as_str().to_owned()))
+            });
+            group.entry(Cow::Borrowed(name)).or_default().push(value);
+        }
+    }
+
+    ProcessingResult::Group(
+        group
+            .into_iter()
+            .map(|(name, values)| (name, ProcessingResult::Values(values)))
+            .collect(),
+    )
+}
+
+/// Computes an absolute XPath uniquely addressing the given item in the tree.
+///
+/// Only items belonging to the tree have such path.
+fn absolute_path(tree: &XpathItemTree, item: &XpathItem) -> Option<String> {
+    let mut node = item.as_node().and_then(Node::as_tree_node).ok()?.clone();
+    let mut steps = Vec::new();
+    while let Some(parent) = node.parent(tree) {
+        let test = match node.data {
+            XpathItemTreeNodeData::ElementNode(element) => element.name.as_str(),
+            XpathItemTreeNodeData::TextNode(_) => "text()",
+            XpathItemTreeNodeData::CommentNode(_) => "comment()",
+            XpathItemTreeNodeData::PINode(_) => "processing-instruction()",
+            XpathItemTreeNodeData::DocumentNode(_) => return None,
+        };
+        let position = parent
+            .children(tree)
+            .filter(|sibling| match (sibling.data, node.data) {
+                (
+                    XpathItemTreeNodeData::ElementNode(sibling),
+                    XpathItemTreeNodeData::ElementNode(element),
+                ) => sibling.name == element.name,
+                (sibling, node) => mem::discriminant(sibling) == mem::discriminant(node),
+            })
+            .position(|sibling| sibling == node)?
+            + 1;
+        steps.push(format!("{test}[{position}]"));
+        node = parent;
+    }
+
+    Some(
+        steps
+            .iter()
+            .rev()
+            .fold(String::new(), |path, step| path + "/" + step),
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum ProcessingResult<'tree> {
+    Group(IndexMap<Cow<'tree, str>, ProcessingResult<'tree>>),
     Values(Vec<job::Value<'tree>>),
-    Error(ExpressionApplyError),
+    Error(SelectError),
+}
+
+/// Groups are serialized as maps, values as sequences and errors as their messages.
+impl Serialize for ProcessingResult<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Group(group) => serializer.collect_map(group),
+            Self::Values(values) => serializer.collect_seq(values),
+            Self::Error(error) => serializer.collect_str(error),
+        }
+    }
+}
+
+impl<'tree> ProcessingResult<'tree> {
+    /// Converts this result into the JSON document written into sinks and served by the API.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("results should be serializable")
+    }
+
+    /// Flattens the groups of this result into a map from their names joined by the separator to the values.
+    ///
+    /// Names of items such as `[0]` are appended without the separator, e.g. `[0].products[1].name`.
+    /// Errors are omitted, while values of keys which coincide after joining are concatenated.
+    pub fn flatten(&self, separator: &str) -> IndexMap<String, Vec<job::Value<'tree>>> {
+        let mut flat = IndexMap::new();
+        self.flatten_into(&mut String::new(), separator, &mut flat);
+        flat
+    }
+
+    fn flatten_into(
+        &self,
+        key: &mut String,
+        separator: &str,
+        flat: &mut IndexMap<String, Vec<job::Value<'tree>>>,
+    ) {
+        match self {
+            Self::Group(group) => {
+                for (name, result) in group {
+                    let length = key.len();
+                    if !key.is_empty() && !name.starts_with('[') {
+                        key.push_str(separator);
+                    }
+                    key.push_str(name);
+                    result.flatten_into(key, separator, flat);
+                    key.truncate(length);
+                }
+            }
+            Self::Values(values) => flat
+                .entry(key.clone())
+                .or_default()
+                .extend(values.iter().cloned()),
+            Self::Error(_) => {}
+        }
+    }
+
+    /// Detaches this result from the trees it was found in.
+    fn into_owned(self) -> ProcessingResult<'static> {
+        match self {
+            Self::Group(group) => ProcessingResult::Group(
+                group
+                    .into_iter()
+                    .map(|(name, result)| (Cow::Owned(name.into_owned()), result.into_owned()))
+                    .collect(),
+            ),
+            Self::Values(values) => {
+                ProcessingResult::Values(values.into_iter().map(job::Value::into_owned).collect())
+            }
+            Self::Error(error) => ProcessingResult::Error(error),
+        }
+    }
+
+    /// Finds the first number extracted by a target with the name, searching the groups depth-first.
+    fn number(&self, target: &str) -> Option<f64> {
+        let Self::Group(group) = self else {
+            return None;
+        };
+        group.iter().find_map(|(name, result)| match result {
+            Self::Values(values) if name == target => values.iter().find_map(|value| match value {
+                job::Value::Number(number) => Some(*number),
+                _ => None,
+            }),
+            result => result.number(target),
+        })
+    }
+
+    /// Checks if this result carries no useful data,
+    /// i.e. it is an error, has no known values or consists of such results only.
+    fn is_missing(&self) -> bool {
+        match self {
+            Self::Group(group) => group.values().all(Self::is_missing),
+            Self::Values(values) => values.iter().all(|value| *value == job::Value::Unknown),
+            Self::Error(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn targets(source: &str) -> job::Targets {
+        Config::builder()
+            .add_source(config::File::from_str(source, config::FileFormat::Json5))
+            .build()
+            .and_then(Config::try_deserialize)
+            .expect("targets should be valid")
+    }
+
+    fn process<'tree>(
+        tree: &'tree XpathItemTree,
+        targets: &'tree job::Targets,
+    ) -> ProcessingResult<'tree> {
+        process_targets(
+            tree,
+            tree.roots().unwrap(),
+            targets,
+            None,
+            ProcessingContext::default(),
+        )
+    }
+
+    #[test]
+    fn test_missing_required_target_skips_record() {
+        let document = html::parse(include_str!("../fixtures/products.html")).unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                products: {
+                    path: "//div",
+                    then: {
+                        Get: {
+                            id: { path: "/span/text()", then: { Extract: "Text" }, required: true },
+                            name: { path: "/h3/text()", then: { Extract: "Text" } },
+                        },
+                    },
+                },
+            }"#,
+        );
+
+        let ProcessingResult::Group(root) = process(&tree, &targets) else {
+            panic!("root result should be a group");
+        };
+        let ProcessingResult::Group(root) = &root["[0]"] else {
+            panic!("document result should be a group");
+        };
+        let ProcessingResult::Group(products) = &root["products"] else {
+            panic!("products should be a group");
+        };
+        assert_eq!(
+            products.keys().collect::<Vec<_>>(),
+            ["[0]", "[2]"],
+            "product without an ID should be skipped"
+        );
+    }
+
+    #[test]
+    fn test_defaults_substitute_missing_values() {
+        let document = html::parse(
+            "<main><div><h3>Loom Hoodie</h3><b>In stock</b></div><div><h3>Canvas Tee</h3></div></main>",
+        )
+        .unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                products: {
+                    path: "//div",
+                    then: {
+                        Get: {
+                            stock: { path: "/b/text()", then: { Extract: "Text" }, default: "Sold out" },
+                            price: { path: "/span", then: { Extract: { Number: {} } }, default: 0 },
+                        },
+                    },
+                },
+            }"#,
+        );
+
+        let result = process(&tree, &targets).to_json();
+        assert_eq!(
+            result["[0]"]["products"],
+            serde_json::json!({
+                "[0]": { "stock": ["In stock"], "price": [0.0] },
+                "[1]": { "stock": ["Sold out"], "price": [0.0] },
+            })
+        );
+    }
+
+    #[test]
+    fn test_nested_targets_are_processed_per_matched_item() {
+        let document = html::parse(include_str!("../fixtures/catalog.html")).unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                category: {
+                    path: "//section",
+                    then: {
+                        Get: {
+                            name: { path: "/h2/text()", then: { Extract: "Text" } },
+                            products: {
+                                path: "/div",
+                                then: {
+                                    Get: {
+                                        title: { path: "/h3/text()", then: { Extract: "Text" } },
+                                        price: { path: "/span/text()", then: { Extract: "Text" } },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+            }"#,
+        );
+        let text = |text| ProcessingResult::Values(vec![job::Value::String(Cow::Borrowed(text))]);
+        let product = |title, price| {
+            ProcessingResult::Group(IndexMap::from([
+                (Cow::Borrowed("title"), text(title)),
+                (Cow::Borrowed("price"), text(price)),
+            ]))
+        };
+
+        let ProcessingResult::Group(root) = process(&tree, &targets) else {
+            panic!("root result should be a group");
+        };
+        let ProcessingResult::Group(record) = &root["[0]"] else {
+            panic!("document result should be a group");
+        };
+        let ProcessingResult::Group(categories) = &record["category"] else {
+            panic!("categories should be a group");
+        };
+        assert_eq!(categories.len(), 2);
+
+        let ProcessingResult::Group(clothing) = &categories["[0]"] else {
+            panic!("category should be a group");
+        };
+        assert_eq!(clothing["name"], text("Clothing"));
+        let ProcessingResult::Group(products) = &clothing["products"] else {
+            panic!("products should be a group");
+        };
+        assert_eq!(products.len(), 2);
+        assert_eq!(products["[0]"], product("Loom Hoodie", "49"));
+        assert_eq!(products["[1]"], product("Panama Tee", "19"));
+
+        let ProcessingResult::Group(kitchen) = &categories["[1]"] else {
+            panic!("category should be a group");
+        };
+        assert_eq!(kitchen["name"], text("Kitchen"));
+        assert_eq!(
+            kitchen["products"],
+            ProcessingResult::Group(IndexMap::new())
+        );
+    }
+
+    #[test]
+    fn test_records_are_keyed_by_key_selector() {
+        let document = html::parse(include_str!("../fixtures/catalog.html")).unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                many: {
+                    path: "//section[1]/div",
+                    key: "/h3/text()",
+                    then: { Get: { price: { path: "/span/text()", then: { Extract: "Text" } } } },
+                },
+                one: {
+                    path: "//section[1]/div[2]",
+                    key: "/h3/text()",
+                    then: { Get: { price: { path: "/span/text()", then: { Extract: "Text" } } } },
+                },
+                none: {
+                    path: "//section[2]/div",
+                    key: "/h3/text()",
+                    then: { Get: { price: { path: "/span/text()", then: { Extract: "Text" } } } },
+                },
+            }"#,
+        );
+        let price = |price| {
+            ProcessingResult::Group(IndexMap::from([(
+                Cow::Borrowed("price"),
+                ProcessingResult::Values(vec![job::Value::String(Cow::Borrowed(price))]),
+            )]))
+        };
+
+        let ProcessingResult::Group(root) = process(&tree, &targets) else {
+            panic!("root result should be a group");
+        };
+        let ProcessingResult::Group(record) = &root["[0]"] else {
+            panic!("document result should be a group");
+        };
+        assert_eq!(
+            record["many"],
+            ProcessingResult::Group(IndexMap::from([
+                (Cow::Borrowed("Loom Hoodie"), price("49")),
+                (Cow::Borrowed("Panama Tee"), price("19")),
+            ]))
+        );
+        assert_eq!(
+            record["one"],
+            ProcessingResult::Group(IndexMap::from([(Cow::Borrowed("Panama Tee"), price("19"))]))
+        );
+        assert_eq!(record["none"], ProcessingResult::Group(IndexMap::new()));
+    }
+
+    #[test]
+    fn test_results_are_converted_into_json() {
+        let result = ProcessingResult::Group(IndexMap::from([
+            (
+                Cow::Borrowed("name"),
+                ProcessingResult::Values(vec![job::Value::String(Cow::Borrowed("Loom Hoodie"))]),
+            ),
+            (
+                Cow::Borrowed("price"),
+                ProcessingResult::Values(vec![job::Value::Number(49.5), job::Value::Unknown]),
+            ),
+            (
+                Cow::Borrowed("seo"),
+                ProcessingResult::Error(SelectError::Unsupported("not an HTML document")),
+            ),
+        ]));
+
+        assert_eq!(
+            result.to_json(),
+            serde_json::json!({
+                "name": ["Loom Hoodie"],
+                "price": [49.5, null],
+                "seo": "not an HTML document",
+            })
+        );
+    }
+
+    #[test]
+    fn test_results_are_flattened_with_indexed_keys() {
+        let values = |values: &[&'static str]| {
+            ProcessingResult::Values(
+                values
+                    .iter()
+                    .map(|value| job::Value::String(Cow::Borrowed(value)))
+                    .collect(),
+            )
+        };
+        let product = |name| {
+            ProcessingResult::Group(IndexMap::from([(Cow::Borrowed("name"), values(&[name]))]))
+        };
+        let result = ProcessingResult::Group(IndexMap::from([(
+            Cow::Borrowed("[0]"),
+            ProcessingResult::Group(IndexMap::from([
+                (
+                    Cow::Borrowed("products"),
+                    ProcessingResult::Group(IndexMap::from([
+                        (Cow::Borrowed("[0]"), product("Loom Hoodie")),
+                        (Cow::Borrowed("[1]"), product("Canvas Tee")),
+                    ])),
+                ),
+                (
+                    Cow::Borrowed("seo"),
+                    ProcessingResult::Error(SelectError::Unsupported("not an HTML document")),
+                ),
+                (Cow::Borrowed("tags.sale"), values(&["summer"])),
+                (
+                    Cow::Borrowed("tags"),
+                    ProcessingResult::Group(IndexMap::from([(
+                        Cow::Borrowed("sale"),
+                        values(&["clearance"]),
+                    )])),
+                ),
+            ])),
+        )]));
+
+        let flat = result.flatten(".");
+        assert_eq!(
+            flat.keys().collect::<Vec<_>>(),
+            [
+                "[0].products[0].name",
+                "[0].products[1].name",
+                "[0].tags.sale"
+            ]
+        );
+        assert_eq!(
+            flat["[0].tags.sale"],
+            [
+                job::Value::String(Cow::Borrowed("summer")),
+                job::Value::String(Cow::Borrowed("clearance"))
+            ]
+        );
+        assert_eq!(
+            result.flatten("/").keys().next().unwrap(),
+            "[0]/products[0]/name"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_panics_are_caught_with_their_messages() {
+        assert_eq!(catch_panic(async { 42 }).await, Ok(42));
+        assert_eq!(
+            catch_panic(async { panic!("unexpected {}", "item") }).await,
+            Err::<(), _>("unexpected item".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_dead_targets_are_found_in_sample() {
+        let job = testing::job(
+            r#"
+                resource: { url: "https://shop.example/products" },
+                sample: "fixtures/products.html",
+                targets: {
+                    products: {
+                        path: "//div[@class='product']",
+                        then: {
+                            Get: {
+                                id: { path: "/span/text()", then: { Extract: "Text" } },
+                                name: { path: "/h2/text()", then: { Extract: "Text" } },
+                            },
+                        },
+                    },
+                    reviews: { path: "//section[@id='reviews']", then: { Extract: "Text" } },
+                },
+            "#,
+        );
+        assert_eq!(
+            sample_problems(&job),
+            [
+                r#"target "/products/name" matches nothing in the sample"#,
+                r#"target "/reviews" matches nothing in the sample"#,
+            ]
+        );
+
+        let job = Job {
+            sample: Some(PathBuf::from("fixtures/missing.html")),
+            ..job
+        };
+        assert_eq!(sample_problems(&job).len(), 1);
+    }
+
+    #[test]
+    fn test_unnamed_jobs_are_named_by_their_resources() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("config.json5");
+        let job = |fields: &str| {
+            format!(
+                r#"{{ {fields}, period: {{ secs: 60, nanos: 0 }}, targets: {{}}, continuation: {{ ref: "//a/@href" }} }}"#
+            )
+        };
+        let resources = [
+            job(r#"resource: { url: "https://shop.example/tees" }"#),
+            job(r#"name: "hoodies", resource: { url: "https://shop.example/hoodies" }"#),
+            job(r#"resource: { path: "fixtures/products.html" }"#),
+            job(r#"resource: { url: "https://shop.example/tees" }"#),
+        ];
+        std::fs::write(
+            &path,
+            format!("{{ resources: [{}] }}", resources.join(", ")),
+        )
+        .unwrap();
+
+        let config = load_config(path).unwrap();
+        assert_eq!(
+            config.resources.iter().map(Job::name).collect::<Vec<_>>(),
+            [
+                "https://shop.example/tees",
+                "hoodies",
+                "fixtures/products.html",
+                "https://shop.example/tees #3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jobs_are_selected_by_tags() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("config.json5");
+        let job = |name: &str, tags: &str| {
+            format!(
+                r#"{{ name: "{name}", tags: {tags}, resource: {{ path: "fixtures/products.html" }}, period: {{ secs: 60, nanos: 0 }}, targets: {{}}, continuation: {{ ref: "//a/@href" }} }}"#
+            )
+        };
+        let resources = [
+            job("tees", r#"["prices"]"#),
+            job("hoodies", r#"["prices", "stock"]"#),
+            job("caps", r#"["stock"]"#),
+            job("bags", "[]"),
+        ];
+        std::fs::write(
+            &path,
+            format!("{{ resources: [{}] }}", resources.join(", ")),
+        )
+        .unwrap();
+        let selected = |tags: &[&str]| {
+            let tags: Vec<_> = tags.iter().map(|tag| tag.to_string()).collect();
+            select_tagged(load_config(path.clone()).unwrap(), &tags)
+                .resources
+                .iter()
+                .map(|job| job.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(selected(&[]), ["tees", "hoodies", "caps", "bags"]);
+        assert_eq!(selected(&["prices"]), ["tees", "hoodies"]);
+        assert_eq!(selected(&["stock", "prices"]), ["tees", "hoodies", "caps"]);
+        assert!(selected(&["sale"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+            .mount(&server)
+            .await;
+        let job = testing::job(&format!(
+            r#"
+                resource: {{ url: "{}" }},
+                retry: {{ attempts: 3, delay: {{ secs: 0, nanos: 1000000 }} }},
+            "#,
+            server.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let job::Resource::Url(url) = &job.resource else {
+            unreachable!();
+        };
+        let response = fetch_url(&fetcher, &job, None, url).await.unwrap();
+        assert_eq!(response.status, reqwest::StatusCode::OK);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        let response = fetch_url(&fetcher, &job, None, url).await.unwrap();
+        assert_eq!(response.status, reqwest::StatusCode::OK);
+        assert_eq!(server.received_requests().await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_takes_precedence_over_backoff() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::path("/limited"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::path("/pinned"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "86400"))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+            .mount(&server)
+            .await;
+        let job = testing::job(&format!(
+            r#"
+                resource: {{ url: "{}" }},
+                retry: {{
+                    attempts: 2,
+                    delay: {{ secs: 3600, nanos: 0 }},
+                    max_retry_after: {{ secs: 0, nanos: 1000000 }},
+                }},
+            "#,
+            server.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        for path in ["/limited", "/pinned"] {
+            let url = format!("{}{path}", server.uri()).parse().unwrap();
+            let response = tokio::time::timeout(
+                std::time::Duration::from_secs(5),
+                fetch_url(&fetcher, &job, None, &url),
+            )
+            .await
+            .expect("Retry-After should override the backoff")
+            .unwrap();
+            assert_eq!(response.status, reqwest::StatusCode::OK);
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_unsuccessful_status_fails_unless_accepted() {
+        use wiremock::ResponseTemplate;
+
+        let server =
+            testing::serve(ResponseTemplate::new(404).set_body_string("<h3>Not Found</h3>")).await;
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let job = |accept_status| {
+            testing::job(&format!(
+                r#"
+                    resource: {{ url: "{}" }},
+                    accept_status: {accept_status},
+                "#,
+                server.uri()
+            ))
+        };
+
+        let url = Url::parse(&server.uri()).unwrap();
+        assert!(matches!(
+            fetch_url(&fetcher, &job("[]"), None, &url).await,
+            Err(HandleError::HttpStatus(reqwest::StatusCode::NOT_FOUND))
+        ));
+
+        let response = fetch_url(&fetcher, &job("[404]"), None, &url)
+            .await
+            .unwrap();
+        assert_eq!(response.status, reqwest::StatusCode::NOT_FOUND);
+        assert_eq!(response.body, "<h3>Not Found</h3>");
+    }
+
+    #[tokio::test]
+    async fn test_slow_response_times_out() {
+        use std::time::Duration;
+
+        use wiremock::ResponseTemplate;
+
+        let server =
+            testing::serve(ResponseTemplate::new(200).set_delay(Duration::from_secs(5))).await;
+        let job = testing::job(&format!(
+            r#"
+                resource: {{ url: "{}" }},
+                timeout: {{ secs: 0, nanos: 50000000 }},
+            "#,
+            server.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        assert!(matches!(
+            handle(
+                &fetcher,
+                &job,
+                &mut JobState::default(),
+                &job.resource,
+                1,
+                None
+            )
+            .await,
+            Err(HandleError::Timeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_templated_query_keeps_state_between_polls() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<h3>Canvas Tee</h3>"))
+            .expect(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"v1\"")
+                    .set_body_string("<h3>Loom Hoodie</h3>"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let directory = tempfile::tempdir().unwrap();
+        let job = testing::job(&format!(
+            r#"
+                name: "shop",
+                resource: {{ url: "{}" }},
+                query: [{{ name: "t", value: "{{now}}" }}],
+                conditional_requests: true,
+                detect_changes: true,
+                targets: {{ name: {{ path: "//h3/text()", then: {{ Extract: "Text" }} }} }},
+                sinks: [{{ Json: {{ path: "{}/{{job}}.json" }} }}],
+            "#,
+            server.uri(),
+            directory.path().display()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let (_shutdown, receiver) = watch::channel(false);
+        let mut state = JobState::default();
+        assert!(poll_once(&fetcher, &job, &mut state, &receiver).await);
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        assert!(poll_once(&fetcher, &job, &mut state, &receiver).await);
+        state.flush().await;
+
+        let queries = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| request.url.query().unwrap_or_default().to_owned())
+            .collect::<Vec<_>>();
+        assert_ne!(queries[0], queries[1]);
+        let written = std::fs::read_to_string(directory.path().join("shop.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&written).unwrap(),
+            serde_json::json!([{
+                "change": "changed",
+                "path": "/[0]/name/0",
+                "old": "Loom Hoodie",
+                "new": "Canvas Tee",
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_count_matches_changed_fields() {
+        let directory = tempfile::tempdir().unwrap();
+        let job = testing::job(&format!(
+            r#"
+                name: "shop",
+                change_count: true,
+                targets: {{
+                    name: {{ path: "//h3/text()", then: {{ Extract: "Text" }} }},
+                    price: {{ path: "//span/text()", then: {{ Extract: "Text" }} }},
+                    stock: {{ path: "//b/text()", then: {{ Extract: "Text" }} }},
+                }},
+                sinks: [{{ Json: {{ path: "{}/{{job}}.json" }} }}],
+            "#,
+            directory.path().display()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let mut state = JobState::default();
+        let mut counts = Vec::new();
+        for document in [
+            "<div><h3>Loom Hoodie</h3><span>49</span><b>In stock</b></div>",
+            "<div><h3>Loom Hoodie</h3><span>39</span><b>Sold out</b></div>",
+            "<div><h3>Loom Hoodie</h3><span>39</span><b>Sold out</b></div>",
+        ] {
+            let tree = XpathItemTree::from(&html::parse(document).unwrap());
+            let result = process(&tree, &job.targets);
+            emit(&fetcher, &job, &mut state, &job.resource, result).await;
+            state.flush().await;
+            let written = std::fs::read_to_string(directory.path().join("shop.json")).unwrap();
+            let written: serde_json::Value = serde_json::from_str(&written).unwrap();
+            assert_eq!(written["[0]"]["name"], serde_json::json!(["Loom Hoodie"]));
+            counts.push(written["_change_count"].clone());
+        }
+        assert_eq!(
+            counts,
+            [
+                serde_json::json!([null]),
+                serde_json::json!([2.0]),
+                serde_json::json!([0.0])
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sinks_bypass_proxy_of_job() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let proxy = MockServer::start().await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(502))
+            .expect(0)
+            .mount(&proxy)
+            .await;
+        let webhook = MockServer::start().await;
+        Mock::given(matchers::method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&webhook)
+            .await;
+        let job = testing::job(&format!(
+            r#"
+                proxy: {{ url: "{}" }},
+                targets: {{ name: {{ path: "//h3/text()", then: {{ Extract: "Text" }} }} }},
+                sinks: [{{ Webhook: {{ url: "{}" }} }}],
+            "#,
+            proxy.uri(),
+            webhook.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None)
+            .for_job(&job)
+            .unwrap();
+        let tree = XpathItemTree::from(&html::parse("<h3>Loom Hoodie</h3>").unwrap());
+        let mut state = JobState::default();
+        emit(
+            &fetcher,
+            &job,
+            &mut state,
+            &job.resource,
+            process(&tree, &job.targets),
+        )
+        .await;
+        state.flush().await;
+    }
+
+    #[tokio::test]
+    async fn test_records_are_joined_with_latest_results_of_other_job() {
+        let directory = tempfile::tempdir().unwrap();
+        let item = |targets: &str| {
+            format!(
+                r#"{{ products: {{ path: "//li", then: {{ get: {{
+                    sku: {{ path: "b/text()", then: {{ Extract: "Text" }} }},
+                    {targets}
+                }} }} }} }}"#
+            )
+        };
+        let reviews = testing::job(&format!(
+            r#"name: "reviews", targets: {},"#,
+            item(r#"rating: { path: "span/text()", then: { Extract: "Text" } },"#)
+        ));
+        let products = testing::job(&format!(
+            r#"
+                name: "products",
+                targets: {},
+                join: {{ job: "reviews", by: "sku" }},
+                sinks: [{{ Json: {{ path: "{}/{{job}}.json" }} }}],
+            "#,
+            item(r#"name: { path: "h2/text()", then: { Extract: "Text" } },"#),
+            directory.path().display()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        for (job, document) in [
+            (
+                &reviews,
+                "<ul><li><b>L-1</b><span>4.5</span></li><li><b>W-3</b><span>3</span></li></ul>",
+            ),
+            (
+                &products,
+                "<ul><li><b>L-1</b><h2>Loom Hoodie</h2></li><li><b>C-2</b><h2>Canvas Tee</h2></li></ul>",
+            ),
+        ] {
+            let tree = XpathItemTree::from(&html::parse(document).unwrap());
+            let mut state = JobState::default();
+            emit(&fetcher, job, &mut state, &job.resource, process(&tree, &job.targets)).await;
+            state.flush().await;
+        }
+
+        let written = std::fs::read_to_string(directory.path().join("products.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&written).unwrap()["[0]"],
+            serde_json::json!({
+                "products": {
+                    "[0]": { "sku": ["L-1"], "name": ["Loom Hoodie"], "rating": ["4.5"] },
+                    "[1]": { "sku": ["C-2"], "name": ["Canvas Tee"] },
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_series_of_tracked_targets_are_reloaded_after_restart() {
+        let directory = tempfile::tempdir().unwrap();
+        let job = testing::job(&format!(
+            r#"
+                name: "hoodies",
+                targets: {{ price: {{ path: "//b/text()", then: {{ Extract: "Text" }}, transforms: [{{ ParseNumber: {{}} }}] }} }},
+                track: ["price"],
+                series: "{}/series.json",
+            "#,
+            directory.path().display()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        load_series(&fetcher, &job).await;
+        for price in ["59.90", "49.90"] {
+            let tree = XpathItemTree::from(&html::parse(&format!("<b>{price}</b>")).unwrap());
+            let mut state = JobState::default();
+            emit(
+                &fetcher,
+                &job,
+                &mut state,
+                &job.resource,
+                process(&tree, &job.targets),
+            )
+            .await;
+        }
+        let tracked = fetcher.latest_results().job_series("hoodies");
+        assert_eq!(
+            tracked["price"]
+                .iter()
+                .map(|point| point.value)
+                .collect::<Vec<_>>(),
+            [59.9, 49.9]
+        );
+
+        let restarted = Fetcher::new(reqwest::Client::new(), None);
+        load_series(&restarted, &job).await;
+        assert_eq!(restarted.latest_results().job_series("hoodies"), tracked);
+    }
+
+    #[tokio::test]
+    async fn test_poll_events_are_streamed_to_api_clients() {
+        let job = testing::job(
+            r#"
+                name: "shop",
+                detect_changes: true,
+                targets: { name: { path: "//h2/text()", then: { Extract: "Text" } } },
+            "#,
+        );
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(api::serve(listener, fetcher.latest_results().clone()));
+
+        let mut events = reqwest::get(format!("http://{address}/events"))
+            .await
+            .unwrap();
+        assert_eq!(events.headers()["content-type"], "text/event-stream");
+        let (_shutdown, receiver) = watch::channel(false);
+        assert!(poll_once(&fetcher, &job, &mut JobState::default(), &receiver).await);
+
+        let mut stream = String::new();
+        while !stream.contains("event: poll_completed") {
+            let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), events.chunk())
+                .await
+                .expect("events should arrive after the poll")
+                .unwrap()
+                .unwrap();
+            stream.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        assert_eq!(
+            stream,
+            "event: poll_started\n\
+             data: {\"event\":\"poll_started\",\"job\":\"shop\"}\n\n\
+             event: poll_completed\n\
+             data: {\"event\":\"poll_completed\",\"job\":\"shop\",\"pages\":1,\"succeeded\":true}\n\n"
+        );
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_not_modified_resource_is_skipped() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"v1\"")
+                    .set_body_string(r#"<div><a href="/page/2">Next</a></div>"#),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let job = testing::job(&format!(
+            r#"
+                resource: {{ url: "{}" }},
+                conditional_requests: true,
+            "#,
+            server.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let mut state = JobState::default();
+        assert_eq!(
+            handle(&fetcher, &job, &mut state, &job.resource, 1, None)
+                .await
+                .unwrap(),
+            [Ok(Url::parse(&server.uri())
+                .unwrap()
+                .join("/page/2")
+                .unwrap())]
+        );
+        assert!(handle(&fetcher, &job, &mut state, &job.resource, 1, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_single_poll_reports_failures() {
+        let (_shutdown, receiver) = watch::channel(false);
+        let poll = |path| {
+            let shutdown = receiver.clone();
+            async move {
+                let job = testing::job(&format!(
+                    r#"
+                    resource: {{ path: "{path}" }},
+                "#
+                ));
+                let fetcher = Fetcher::new(reqwest::Client::new(), None);
+                poll_once(&fetcher, &job, &mut JobState::default(), &shutdown).await
+            }
+        };
+
+        assert!(poll("fixtures/catalog.html").await);
+        assert!(!poll("fixtures/missing.html").await);
+    }
+
+    #[tokio::test]
+    async fn test_offset_continuation_stops_on_page_without_results() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<div></div>"))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+            .mount(&server)
+            .await;
+        let job = testing::job(&format!(
+            r#"
+                resource: {{ url: "{}/products?page=1" }},
+                targets: {{ name: {{ path: "/h3/text()", then: {{ Extract: "Text" }} }} }},
+                continuation: {{ Offset: {{ param: "page", start: 1, max_pages: 10 }} }},
+            "#,
+            server.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let (_shutdown, receiver) = watch::channel(false);
+        assert!(poll_once(&fetcher, &job, &mut JobState::default(), &receiver).await);
+        let pages: Vec<_> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| request.url.query().unwrap_or_default().to_owned())
+            .collect();
+        assert_eq!(pages, ["page=1", "page=2"]);
+    }
+
+    #[tokio::test]
+    async fn test_json_api_is_paginated_by_offset() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{ "items": [] }"#))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"{ "items": [{ "name": "Loom Hoodie" }] }"#),
+            )
+            .mount(&server)
+            .await;
+        let job = testing::job(&format!(
+            r#"
+                resource: {{ url: "{}/api/products?page=1" }},
+                format: "Json",
+                targets: {{ name: {{ path: "$.items[*].name", then: {{ Extract: "Text" }} }} }},
+                continuation: {{ Offset: {{ param: "page", start: 1, max_pages: 10 }} }},
+            "#,
+            server.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let (_shutdown, receiver) = watch::channel(false);
+        assert!(poll_once(&fetcher, &job, &mut JobState::default(), &receiver).await);
+        let pages: Vec<_> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| request.url.query().unwrap_or_default().to_owned())
+            .collect();
+        assert_eq!(pages, ["page=1", "page=2"]);
+    }
+
+    #[test]
+    fn test_json_targets_are_processed_by_json_path() {
+        let documents = [(
+            None,
+            serde_json::json!({
+                "products": [
+                    { "sku": "LH-1", "name": "Loom Hoodie", "price": 49.5, "tags": ["warm"] },
+                    { "sku": "CT-2", "name": "Canvas Tee", "price": "1 299,00 ₽" },
+                ],
+            }),
+        )];
+        let targets = targets(
+            r#"{
+                products: {
+                    path: "$.products[*]",
+                    key: "$.sku",
+                    then: { Get: {
+                        name: { path: "$.name", then: { Extract: "Text" } },
+                        price: { path: "$.price", then: { Extract: { Number: { locale: "Ru" } } } },
+                        tags: { path: "$.tags", then: { Extract: "Json" } },
+                    } },
+                },
+            }"#,
+        );
+        let context = ProcessingContext {
+            annotate_paths: true,
+            ..ProcessingContext::default()
+        };
+
+        assert_eq!(
+            process_documents(&documents, &targets, context).to_json(),
+            serde_json::json!({
+                "[0]": {
+                    "products": {
+                        "LH-1": {
+                            "name": ["Loom Hoodie"],
+                            "price": [49.5],
+                            "tags": [["warm"]],
+                            "_path": ["/products/0"],
+                        },
+                        "CT-2": {
+                            "name": ["Canvas Tee"],
+                            "price": [1299.0],
+                            "tags": [],
+                            "_path": ["/products/1"],
+                        },
+                    },
+                    "_path": [""],
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_continuations_stop_at_visited_pages_and_max_pages() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        for (page, next) in [("/a", "b"), ("/b", "/c?sort=new"), ("/c", "a")] {
+            Mock::given(matchers::path(page))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_string(format!(r#"<div><a href="{next}">Next</a></div>"#)),
+                )
+                .mount(&server)
+                .await;
+        }
+        let poll = |max_pages: &str| {
+            let job = testing::job(&format!(
+                r#"
+                    resource: {{ url: "{}/a" }},
+                    {max_pages}
+                "#,
+                server.uri()
+            ));
+            let server = &server;
+            async move {
+                let requested = server.received_requests().await.unwrap().len();
+                let fetcher = Fetcher::new(reqwest::Client::new(), None);
+                let (_shutdown, receiver) = watch::channel(false);
+                poll_once(&fetcher, &job, &mut JobState::default(), &receiver).await;
+                server.received_requests().await.unwrap()[requested..]
+                    .iter()
+                    .map(|request| request.url.path().to_owned())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(poll("").await, ["/a", "/b", "/c"]);
+        assert_eq!(poll("max_pages: 2,").await, ["/a", "/b"]);
+    }
+
+    #[tokio::test]
+    async fn test_poll_is_resumed_from_saved_progress() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        for (page, next) in [("/a", "b"), ("/b", "c"), ("/c", "a")] {
+            Mock::given(matchers::path(page))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_string(format!(r#"<div><a href="{next}">Next</a></div>"#)),
+                )
+                .mount(&server)
+                .await;
+        }
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("progress.json");
+        let job = testing::job(&format!(
+            r#"
+                name: "pages",
+                resource: {{ url: "{0}/a" }},
+                progress: {1:?},
+            "#,
+            server.uri(),
+            path,
+        ));
+        let url = |page: &str| format!("{}/{page}", server.uri()).parse::<Url>().unwrap();
+        progress::save(
+            &path,
+            "pages",
+            Progress {
+                pending: vec![url("b")],
+                visited: vec![url("a")],
+            },
+        )
+        .await
+        .unwrap();
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let (_shutdown, receiver) = watch::channel(false);
+        assert!(poll_once(&fetcher, &job, &mut JobState::default(), &receiver).await);
+        let requested: Vec<_> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| request.url.path().to_owned())
+            .collect();
+        assert_eq!(requested, ["/b", "/c"]);
+        assert_eq!(progress::load(&path, "pages").await, Progress::default());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_in_flight_poll() {
+        use std::time::Duration;
+
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("<h3>Loom Hoodie</h3>")
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let directory = tempfile::tempdir().unwrap();
+        let job = testing::job(&format!(
+            r#"
+                name: "shop",
+                resource: {{ url: "{}" }},
+                targets: {{ name: {{ path: "/h3/text()", then: {{ Extract: "Text" }} }} }},
+                sinks: [{{ Json: {{ path: "{}/{{job}}.json" }} }}],
+            "#,
+            server.uri(),
+            directory.path().display()
+        ));
+
+        let mut jobs = Jobs::new(Fetcher::new(reqwest::Client::new(), None));
+        jobs.update(vec![job]).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let stats = jobs.stats.clone();
+        tokio::time::timeout(Duration::from_secs(5), jobs.shutdown())
+            .await
+            .expect("jobs should finish after shutdown");
+        assert_eq!(stats[0].succeeded.load(Ordering::Relaxed), 1);
+        assert!(!has_failed(FailOn::AnyFailure, &stats));
+
+        let written = std::fs::read_to_string(directory.path().join("shop.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&written).unwrap(),
+            serde_json::json!({ "[0]": { "name": ["Loom Hoodie"] } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_sink_does_not_block_others() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let directory = tempfile::tempdir().unwrap();
+        let job = testing::job(&format!(
+            r#"
+                name: "shop",
+                resource: {{ url: "https://shop.example/" }},
+                sinks: [
+                    {{ Webhook: {{ url: "{}" }} }},
+                    {{ Json: {{ path: "{}/{{job}}.json" }} }},
+                ],
+            "#,
+            server.uri(),
+            directory.path().display()
+        ));
+        let result = ProcessingResult::Values(vec![job::Value::String(Cow::Borrowed("49"))]);
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let mut state = JobState::default();
+        emit(&fetcher, &job, &mut state, &job.resource, result).await;
+        state.flush().await;
+
+        let written = std::fs::read_to_string(directory.path().join("shop.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&written).unwrap(),
+            serde_json::json!(["49"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_sinks_do_not_delay_next_polls() {
+        use std::time::Duration;
+
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+            .mount(&server)
+            .await;
+        let job = testing::job(&format!(
+            r#"
+                targets: {{ name: {{ path: "//h3/text()", then: {{ Extract: "Text" }} }} }},
+                sinks: [{{ Webhook: {{ url: "{}" }} }}],
+                sink_queue: {{ capacity: 1, when_full: "drop" }},
+            "#,
+            server.uri()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let (_shutdown, receiver) = watch::channel(false);
+        let mut state = JobState::default();
+        let started = tokio::time::Instant::now();
+        for _ in 0..3 {
+            assert!(poll_once(&fetcher, &job, &mut state, &receiver).await);
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+        // The writer outlives the polls, still writing the results in the background.
+        while server.received_requests().await.unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_sink_queue_waits_or_drops_as_configured() {
+        use std::time::Duration;
+
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        for (when_full, expected) in [("wait", &["49", "47", "45"][..]), ("drop", &["49", "47"])] {
+            let server = MockServer::start().await;
+            Mock::given(matchers::method("POST"))
+                .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(200)))
+                .mount(&server)
+                .await;
+            let job = testing::job(&format!(
+                r#"
+                    name: "shop",
+                    resource: {{ url: "https://shop.example/" }},
+                    sinks: [{{ Webhook: {{ url: "{}" }} }}],
+                    sink_queue: {{ capacity: 1, when_full: "{when_full}" }},
+                "#,
+                server.uri()
+            ));
+            let event = |price| {
+                OwnedEvent::Polled(ProcessingResult::Values(vec![job::Value::String(
+                    Cow::Borrowed(price),
+                )]))
+            };
+
+            let writer = sink::SinkWriter::spawn(reqwest::Client::new(), &job);
+            writer.send(event("49")).await;
+            // The first event is being written, so the second one fills the queue
+            while server.received_requests().await.unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            writer.send(event("47")).await;
+            writer.send(event("45")).await;
+            writer.close().await;
+
+            let written = server
+                .received_requests()
+                .await
+                .unwrap()
+                .iter()
+                .map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+                .collect::<Vec<_>>();
+            let expected = expected
+                .iter()
+                .map(|price| serde_json::json!([price]))
+                .collect::<Vec<_>>();
+            assert_eq!(written, expected, "{when_full}");
+        }
+    }
+
+    #[test]
+    fn test_failure_follows_fail_on_policy() {
+        let stats = |succeeded, failed| {
+            Arc::new(PollStats {
+                succeeded: AtomicUsize::new(succeeded),
+                failed: AtomicUsize::new(failed),
+            })
+        };
+        let flaky = [stats(3, 0), stats(5, 2), stats(0, 0)];
+        let broken = [stats(3, 0), stats(0, 4)];
+
+        assert!(has_failed(FailOn::AnyFailure, &flaky));
+        assert!(!has_failed(FailOn::NeverSucceeded, &flaky));
+        assert!(has_failed(FailOn::NeverSucceeded, &broken));
+        assert!(!has_failed(FailOn::Never, &broken));
+        assert!(!has_failed(FailOn::AnyFailure, &[]));
+    }
+
+    #[tokio::test]
+    async fn test_only_changed_jobs_are_restarted_on_update() {
+        use std::time::Duration;
+
+        use wiremock::{matchers, Mock, ResponseTemplate};
+
+        let server =
+            testing::serve(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+                .await;
+        let job = |name: &str, digest: &str| {
+            let mut job = testing::job(&format!(
+                r#"
+                    name: "{name}",
+                    resource: {{ url: "{}/{name}" }},
+                "#,
+                server.uri()
+            ));
+            job.digest = String::from(digest);
+            job
+        };
+        let polls = || async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let mut polls = server
+                .received_requests()
+                .await
+                .unwrap()
+                .iter()
+                .map(|request| request.url.path().to_owned())
+                .collect::<Vec<_>>();
+            polls.sort();
+            server.reset().await;
+            Mock::given(matchers::any())
+                .respond_with(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+                .mount(&server)
+                .await;
+            polls
+        };
+
+        let mut jobs = Jobs::new(Fetcher::new(reqwest::Client::new(), None));
+        jobs.update(vec![
+            job("hoodies", "a"),
+            job("tees", "a"),
+            job("caps", "a"),
+        ])
+        .await;
+        assert_eq!(polls().await, ["/caps", "/hoodies", "/tees"]);
+
+        jobs.update(vec![
+            job("hoodies", "a"),
+            job("tees", "b"),
+            job("bags", "a"),
+        ])
+        .await;
+        assert_eq!(polls().await, ["/bags", "/tees"]);
+        let mut names: Vec<_> = jobs.running.keys().collect();
+        names.sort();
+        assert_eq!(names, ["bags", "hoodies", "tees"]);
+
+        let disabled = Job {
+            enabled: false,
+            ..job("tees", "c")
+        };
+        jobs.update(vec![job("hoodies", "a"), disabled, job("bags", "a")])
+            .await;
+        assert!(polls().await.is_empty());
+        let mut names: Vec<_> = jobs.running.keys().collect();
+        names.sort();
+        assert_eq!(names, ["bags", "hoodies"]);
+
+        jobs.update(vec![
+            job("hoodies", "a"),
+            job("tees", "d"),
+            job("bags", "a"),
+        ])
+        .await;
+        assert_eq!(polls().await, ["/tees"]);
+
+        jobs.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_triggered_jobs_poll_immediately() {
+        use std::time::Duration;
+
+        use wiremock::ResponseTemplate;
+
+        let server =
+            testing::serve(ResponseTemplate::new(200).set_body_string("<h3>Loom Hoodie</h3>"))
+                .await;
+        let job = testing::job(&format!(
+            r#"
+                resource: {{ url: "{}" }},
+                period: {{ secs: 3600, nanos: 0 }},
+            "#,
+            server.uri()
+        ));
+        let requests = || async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            server.received_requests().await.unwrap().len()
+        };
+
+        let mut jobs = Jobs::new(Fetcher::new(reqwest::Client::new(), None));
+        jobs.update(vec![job]).await;
+        assert_eq!(requests().await, 1);
+        jobs.trigger();
+        assert_eq!(requests().await, 2);
+        assert_eq!(requests().await, 2);
+
+        jobs.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_glob_results_are_keyed_by_file() {
+        let documents = read_glob("fixtures/pages/*.html", None).await.unwrap();
+        let trees: Vec<_> = documents
+            .into_iter()
+            .map(|(name, document)| (name, XpathItemTree::from(&html::parse(&document).unwrap())))
+            .collect();
+        let targets = targets(r#"{ name: { path: "//h3/text()", then: { Extract: "Text" } } }"#);
+
+        let ProcessingResult::Group(result) =
+            process_documents(&trees, &targets, ProcessingContext::default())
+        else {
+            panic!("glob result should be a group");
+        };
+        assert_eq!(
+            result.keys().collect::<Vec<_>>(),
+            ["fixtures/pages/page-1.html", "fixtures/pages/page-2.html"]
+        );
+        for (file, name) in [
+            ("fixtures/pages/page-1.html", "Loom Hoodie"),
+            ("fixtures/pages/page-2.html", "Valhalla Mug"),
+        ] {
+            let ProcessingResult::Group(document) = &result[file] else {
+                panic!("document result should be a group");
+            };
+            let ProcessingResult::Group(record) = &document["[0]"] else {
+                panic!("record should be a group");
+            };
+            assert_eq!(
+                record["name"],
+                ProcessingResult::Values(vec![job::Value::String(Cow::Borrowed(name))])
+            );
+        }
+    }
+
+    #[test]
+    fn test_stdin_resource_is_decoded_as_utf8() {
+        let job = testing::job(
+            r#"
+                resource: "Stdin",
+            "#,
+        );
+        assert!(matches!(job.resource, job::Resource::Stdin));
+
+        assert_eq!(
+            decode_stdin("<h3>Худи</h3>".as_bytes()).unwrap(),
+            "<h3>Худи</h3>"
+        );
+        assert_eq!(
+            decode_stdin(&[b'<', 0xC3, 0x28]).unwrap_err().to_string(),
+            "standard input is not valid UTF-8: invalid utf-8 sequence of 1 bytes from index 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_glob_files_are_skipped() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("page-1.html"), "<h3>Loom Hoodie</h3>").unwrap();
+        std::fs::create_dir(directory.path().join("page-2.html")).unwrap();
+        let (page, _, _) =
+            encoding_rs::WINDOWS_1251.encode(r#"<meta charset="windows-1251"><h3>Толстовка</h3>"#);
+        std::fs::write(directory.path().join("page-3.html"), page).unwrap();
+
+        let documents = read_glob(&format!("{}/*.html", directory.path().display()), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            documents,
+            [
+                (
+                    Some(directory.path().join("page-1.html").display().to_string()),
+                    "<h3>Loom Hoodie</h3>".to_owned()
+                ),
+                (
+                    Some(directory.path().join("page-3.html").display().to_string()),
+                    r#"<meta charset="windows-1251"><h3>Толстовка</h3>"#.to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ordered_combinator_follows_document_order() {
+        let document = html::parse(
+            "<article><h2>Intro</h2><p>Hello</p><h2>Details</h2><p>World</p></article>",
+        )
+        .unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                content: {
+                    path: { Ordered: ["//p/text()", "//h2/text()"] },
+                    then: { Extract: "Text" },
+                },
+            }"#,
+        );
+
+        let ProcessingResult::Group(root) = process(&tree, &targets) else {
+            panic!("root result should be a group");
+        };
+        let ProcessingResult::Group(record) = &root["[0]"] else {
+            panic!("document result should be a group");
+        };
+        assert_eq!(
+            record["content"],
+            ProcessingResult::Values(
+                ["Intro", "Hello", "Details", "World"]
+                    .map(|text| job::Value::String(Cow::Borrowed(text)))
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_set_combinators_over_overlapping_selections() {
+        let document = html::parse(include_str!("../fixtures/products.html")).unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                union: {
+                    path: { Union: ["//div[span]/h3/text()", "//div/h3/text()"] },
+                    then: { Extract: "Text" },
+                },
+                intersect: {
+                    path: { Intersect: ["//div/h3/text()", "//div[span]/h3/text()"] },
+                    then: { Extract: "Text" },
+                },
+                difference: {
+                    path: { Difference: ["//div/h3/text()", "//div[span]/h3/text()"] },
+                    then: { Extract: "Text" },
+                },
+            }"#,
+        );
+
+        let ProcessingResult::Group(root) = process(&tree, &targets) else {
+            panic!("root result should be a group");
+        };
+        let ProcessingResult::Group(record) = &root["[0]"] else {
+            panic!("document result should be a group");
+        };
+        let texts = |texts: &[&'static str]| {
+            ProcessingResult::Values(
+                texts
+                    .iter()
+                    .map(|text| job::Value::String(Cow::Borrowed(text)))
+                    .collect(),
+            )
+        };
+        assert_eq!(
+            record["union"],
+            texts(&["Loom Hoodie", "Valhalla Mug", "Mystery Box"])
+        );
+        assert_eq!(record["intersect"], texts(&["Loom Hoodie", "Valhalla Mug"]));
+        assert_eq!(record["difference"], texts(&["Mystery Box"]));
+    }
+
+    #[test]
+    fn test_zip_pairs_labels_with_values_by_index() {
+        let document = html::parse(
+            "<div>\
+                <nav>\
+                    <ul><li>Intro</li><li>Usage</li></ul>\
+                    <ol><li>1</li><li>4</li></ol>\
+                </nav>\
+                <aside>\
+                    <ul><li>Intro</li><li>Usage</li><li>License</li></ul>\
+                    <ol><li>1</li><li>4</li></ol>\
+                </aside>\
+            </div>",
+        )
+        .unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                equal: {
+                    path: "//nav",
+                    then: { Zip: { labels: "/ul/li", values: "/ol/li" } },
+                },
+                mismatched: {
+                    path: "//aside",
+                    then: { Zip: { labels: "/ul/li", values: "/ol/li/text()" } },
+                },
+            }"#,
+        );
+
+        let ProcessingResult::Group(root) = process(&tree, &targets) else {
+            panic!("root result should be a group");
+        };
+        let ProcessingResult::Group(record) = &root["[0]"] else {
+            panic!("document result should be a group");
+        };
+        for name in ["equal", "mismatched"] {
+            let ProcessingResult::Group(toc) = &record[name] else {
+                panic!("zipped result should be a group");
+            };
+            assert_eq!(
+                toc.iter()
+                    .map(|(label, pages)| (label.as_ref(), pages))
+                    .collect::<Vec<_>>(),
+                [
+                    (
+                        "Intro",
+                        &ProcessingResult::Values(vec![job::Value::String("1".into())])
+                    ),
+                    (
+                        "Usage",
+                        &ProcessingResult::Values(vec![job::Value::String("4".into())])
+                    ),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_regex_captures_map_named_groups() {
+        let document = html::parse(
+            "<div>\
+                <address>Springfield, IL 62701</address>\
+                <address>Shelbyville, IL</address>\
+                <address>Unknown</address>\
+            </div>",
+        )
+        .unwrap();
+        let tree = XpathItemTree::from(&document);
+        let targets = targets(
+            r#"{
+                address: {
+                    path: "//address",
+                    then: { RegexCaptures: { pattern: "(?<city>[^,]+), (?<state>[A-Z]{2})(?: (?<zip>\\d{5}))?" } },
+                },
+                missing: {
+                    path: "//address[3]",
+                    then: { RegexCaptures: { pattern: "(?<city>[^,]+), (?<state>[A-Z]{2})" } },
+                },
+            }"#,
+        );
+
+        let result = process(&tree, &targets).to_json();
+        assert_eq!(
+            result["[0]"],
+            serde_json::json!({
+                "address": {
+                    "city": ["Springfield", "Shelbyville"],
+                    "state": ["IL", "IL"],
+                    "zip": ["62701", null],
+                },
+                "missing": {},
+            })
+        );
+    }
+
+    #[test]
+    fn test_absolute_path_reapplies_to_same_node() {
+        let document = html::parse(include_str!("../fixtures/products.html")).unwrap();
+        let tree = XpathItemTree::from(&document);
+        let items = skyscraper::xpath::parse("//div/h3/text()")
+            .unwrap()
+            .apply(&tree)
+            .unwrap();
+
+        for item in &items {
+            let path = absolute_path(&tree, item).expect("tree node should have a path");
+            let found = skyscraper::xpath::parse(&path)
+                .unwrap()
+                .apply(&tree)
+                .unwrap();
+            assert_eq!(found.iter().collect::<Vec<_>>(), [item], "path: {path}");
+        }
+        assert_eq!(
+            absolute_path(&tree, &items[1]).as_deref(),
+            Some("/html[1]/body[1]/div[2]/h3[1]/text()[1]")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_captured_headers_are_not_written_as_csv_records() {
+        use wiremock::ResponseTemplate;
+
+        let server = testing::serve(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "max-age=600")
+                .set_body_string("<ul><li>Loom Hoodie</li><li>Canvas Tee</li></ul>"),
+        )
+        .await;
+        let directory = tempfile::tempdir().unwrap();
+        let job = testing::job(&format!(
+            r#"
+                name: "shop",
+                resource: {{ url: "{}" }},
+                response_headers: ["cache-control"],
+                targets: {{
+                    products: {{
+                        path: "//li",
+                        then: {{ Get: {{ name: {{ path: "/text()", then: {{ Extract: "Text" }} }} }} }},
+                    }},
+                }},
+                sinks: [{{ Csv: {{ path: "{}/{{job}}.csv" }} }}],
+            "#,
+            server.uri(),
+            directory.path().display()
+        ));
+
+        let fetcher = Fetcher::new(reqwest::Client::new(), None);
+        let mut state = JobState::default();
+        handle(&fetcher, &job, &mut state, &job.resource, 1, None)
+            .await
+            .unwrap();
+        state.flush().await;
+
+        assert_eq!(
+            std::fs::read_to_string(directory.path().join("shop.csv")).unwrap(),
+            "name\nLoom Hoodie\nCanvas Tee\n"
+        );
+    }
+
+    #[test]
+    fn test_capture_headers() {
+        use reqwest::header::{HeaderMap, HeaderValue, CACHE_CONTROL};
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=600"));
+
+        let ProcessingResult::Group(captured) = capture_headers(
+            &headers,
+            &["cache-control".to_owned(), "x-served-by".to_owned()],
+        ) else {
+            panic!("captured headers should be a group");
+        };
+        assert_eq!(
+            captured["cache-control"],
+            ProcessingResult::Values(vec![job::Value::String(Cow::Borrowed("max-age=600"))])
+        );
+        assert_eq!(captured["x-served-by"], ProcessingResult::Values(vec![]));
+    }
 }
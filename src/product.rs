@@ -0,0 +1,237 @@
+//! Turnkey extraction of product data normalized to the same fields on any site.
+
+use std::borrow::Cow;
+
+use indexmap::IndexMap;
+use url::Url;
+
+use crate::{
+    document::{Document, SelectError},
+    job::{self, Product, Selector, Value},
+    seo, ProcessingResult,
+};
+
+/// ISO 4217 codes of the currencies recognized in prices.
+const CURRENCY_CODES: [&str; 21] = [
+    "USD", "EUR", "GBP", "JPY", "CNY", "CHF", "CAD", "AUD", "RUB", "UAH", "BYN", "KZT", "PLN",
+    "CZK", "SEK", "NOK", "DKK", "INR", "KRW", "TRY", "BRL",
+];
+
+/// Symbols of the currencies recognized in prices, preceding the shorter symbols they contain.
+const CURRENCY_SYMBOLS: [(&str, &str); 17] = [
+    ("US$", "USD"),
+    ("C$", "CAD"),
+    ("A$", "AUD"),
+    ("R$", "BRL"),
+    ("руб", "RUB"),
+    ("zł", "PLN"),
+    ("Kč", "CZK"),
+    ("$", "USD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("¥", "JPY"),
+    ("₽", "RUB"),
+    ("₴", "UAH"),
+    ("₹", "INR"),
+    ("₩", "KRW"),
+    ("₺", "TRY"),
+    ("₸", "KZT"),
+];
+
+/// Schema.org item availabilities by their names with letters only, in lowercase.
+const AVAILABILITIES: [(&str, &str); 8] = [
+    ("instock", "InStock"),
+    ("outofstock", "OutOfStock"),
+    ("soldout", "SoldOut"),
+    ("preorder", "PreOrder"),
+    ("backorder", "BackOrder"),
+    ("limitedavailability", "LimitedAvailability"),
+    ("discontinued", "Discontinued"),
+    ("instoreonly", "InStoreOnly"),
+];
+
+/// Collects the name, price, currency, availability, image and SKU of each of the items.
+///
+/// Each field has a value for every item, which is unknown if the item lacks the field.
+pub fn extract<'doc, D: Document<'doc>>(
+    document: D,
+    items: Vec<D::Item>,
+    product: &Product,
+    base: Option<&Url>,
+) -> ProcessingResult<'doc> {
+    let mut fields = IndexMap::<_, Vec<_>>::new();
+    for item in items {
+        let values = match product_fields(document, item, product, base) {
+            Ok(values) => values,
+            Err(error) => return ProcessingResult::Error(error),
+        };
+        for (field, value) in values {
+            fields.entry(Cow::Borrowed(field)).or_default().push(value);
+        }
+    }
+
+    ProcessingResult::Group(
+        fields
+            .into_iter()
+            .map(|(field, values)| (field, ProcessingResult::Values(values)))
+            .collect(),
+    )
+}
+
+fn product_fields<'doc, D: Document<'doc>>(
+    document: D,
+    item: D::Item,
+    product: &Product,
+    base: Option<&Url>,
+) -> Result<[(&'static str, Value<'doc>); 6], SelectError> {
+    let text = |selector: &Option<Selector>| match selector {
+        Some(selector) => document
+            .select(selector, item.clone())
+            .map(|items| items.iter().find_map(|item| document.text(item))),
+        None => Ok(None),
+    };
+    let string =
+        |text: Option<String>| text.map_or(Value::Unknown, |text| Value::String(Cow::Owned(text)));
+
+    let price = text(&product.price)?;
+    let currency = text(&product.currency)?
+        .as_deref()
+        .and_then(currency_code)
+        .or_else(|| price.as_deref().and_then(currency_code));
+    Ok([
+        (
+            "name",
+            string(text(&product.name)?.map(|name| job::normalize_whitespace(&name, true, true))),
+        ),
+        (
+            "price",
+            price
+                .and_then(|price| product.locale.parse(&price))
+                .map_or(Value::Unknown, Value::Number),
+        ),
+        ("currency", string(currency)),
+        (
+            "availability",
+            string(
+                text(&product.availability)?
+                    .as_deref()
+                    .map(normalize_availability),
+            ),
+        ),
+        (
+            "image",
+            string(
+                text(&product.image)?.map(|image| seo::resolve(image.trim(), base).into_owned()),
+            ),
+        ),
+        (
+            "sku",
+            string(text(&product.sku)?.map(|sku| sku.trim().to_owned())),
+        ),
+    ])
+}
+
+/// Finds the ISO 4217 code of the currency written in the text as a code or a symbol.
+///
+/// Text consisting of another three-letter code, such as the one of a dedicated `<meta>`, is taken as is.
+fn currency_code(text: &str) -> Option<String> {
+    let text = text.trim();
+    let code = CURRENCY_CODES
+        .into_iter()
+        .find(|code| {
+            text.split(|char: char| !char.is_ascii_alphabetic())
+                .any(|word| word.eq_ignore_ascii_case(code))
+        })
+        .or_else(|| {
+            CURRENCY_SYMBOLS
+                .into_iter()
+                .find(|(symbol, _)| text.contains(symbol))
+                .map(|(_, code)| code)
+        });
+    match code {
+        Some(code) => Some(code.to_owned()),
+        None if text.len() == 3 && text.chars().all(|char| char.is_ascii_alphabetic()) => {
+            Some(text.to_ascii_uppercase())
+        }
+        None => None,
+    }
+}
+
+/// Maps the availability to the name of the schema.org one, keeping unrecognized text trimmed.
+fn normalize_availability(text: &str) -> String {
+    let text = text.trim();
+    let name = text
+        .rsplit_once("schema.org/")
+        .map_or(text, |(_, name)| name);
+    let key = name
+        .chars()
+        .filter(|char| char.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect::<String>();
+    AVAILABILITIES
+        .into_iter()
+        .find(|(availability, _)| *availability == key)
+        .map_or_else(|| text.to_owned(), |(_, name)| name.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use skyscraper::{html, xpath::XpathItemTree};
+
+    use super::*;
+
+    #[test]
+    fn test_extract_normalized_product() {
+        let document = html::parse(include_str!("../fixtures/product.html")).unwrap();
+        let tree = XpathItemTree::from(&document);
+        let items = skyscraper::xpath::parse("//main")
+            .unwrap()
+            .apply(&tree)
+            .unwrap()
+            .into_iter()
+            .collect();
+        let product: Product = serde_json::from_value(serde_json::json!({
+            "name": "//h1",
+            "price": "//span[@class='price']",
+            "availability": "//link[@itemprop='availability']/@href",
+            "image": "//img/@src",
+            "sku": "//span[@itemprop='sku']",
+            "locale": "Ru",
+        }))
+        .unwrap();
+        let base = Url::parse("https://shop.example/products/42").unwrap();
+
+        assert_eq!(
+            extract(&tree, items, &product, Some(&base)).to_json(),
+            serde_json::json!({
+                "name": ["Loom Hoodie"],
+                "price": [1299.9],
+                "currency": ["EUR"],
+                "availability": ["InStock"],
+                "image": ["https://shop.example/images/loom-hoodie.jpg"],
+                "sku": ["LH-042"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_currencies_are_found_by_codes_and_symbols() {
+        assert_eq!(currency_code("19.99 usd").as_deref(), Some("USD"));
+        assert_eq!(currency_code("C$ 24.50").as_deref(), Some("CAD"));
+        assert_eq!(currency_code("1 299 руб.").as_deref(), Some("RUB"));
+        assert_eq!(currency_code("£12").as_deref(), Some("GBP"));
+        assert_eq!(currency_code(" sgd ").as_deref(), Some("SGD"));
+        assert_eq!(currency_code("19.99"), None);
+    }
+
+    #[test]
+    fn test_availabilities_are_normalized_to_schema_org() {
+        assert_eq!(
+            normalize_availability("http://schema.org/OutOfStock"),
+            "OutOfStock"
+        );
+        assert_eq!(normalize_availability(" In stock "), "InStock");
+        assert_eq!(normalize_availability("Pre-order"), "PreOrder");
+        assert_eq!(normalize_availability("Ships in 3 days"), "Ships in 3 days");
+    }
+}
@@ -0,0 +1,129 @@
+//! Progress of polls through continuations, persisted so that they are resumed after a restart.
+
+use std::{collections::BTreeMap, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tokio::{fs, sync::Mutex};
+use tracing::{debug, warn};
+use url::Url;
+
+/// Version of the format of progress files, which are ignored if they have another one.
+const VERSION: u32 = 1;
+
+/// Guards reading and rewriting of progress files, which may be shared by several jobs.
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Contents of a progress file.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProgressFile {
+    version: u32,
+    /// Progress of each job by its name
+    jobs: BTreeMap<String, Progress>,
+}
+
+/// Pages of a poll which are still to be handled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    pub pending: Vec<Url>,
+    /// Pages which have been handled or queued, so that they are not handled again
+    pub visited: Vec<Url>,
+}
+
+/// Loads the progress of the job from the file, which is empty if there is none.
+///
+/// Missing, corrupt and outdated files are treated as having no progress.
+pub async fn load(path: &Path, job: &str) -> Progress {
+    let _guard = LOCK.lock().await;
+    read(path)
+        .await
+        .and_then(|mut file| file.jobs.remove(job))
+        .unwrap_or_default()
+}
+
+/// Saves the progress of the job into the file, removing it if the progress is empty.
+pub async fn save(path: &Path, job: &str, progress: Progress) -> io::Result<()> {
+    let _guard = LOCK.lock().await;
+    let mut file = read(path).await.unwrap_or_else(|| ProgressFile {
+        version: VERSION,
+        jobs: BTreeMap::new(),
+    });
+    if progress.pending.is_empty() {
+        if file.jobs.remove(job).is_none() {
+            return Ok(());
+        }
+    } else {
+        file.jobs.insert(job.to_owned(), progress);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let contents = serde_json::to_vec_pretty(&file).expect("progress should be serializable");
+    fs::write(path, contents).await?;
+    debug!("Saved progress to {}", path.display());
+    Ok(())
+}
+
+async fn read(path: &Path) -> Option<ProgressFile> {
+    let contents = match fs::read(path).await {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+        Err(error) => {
+            warn!("Failed to read progress from {}: {error}", path.display());
+            return None;
+        }
+    };
+    match serde_json::from_slice::<ProgressFile>(&contents) {
+        Ok(file) if file.version == VERSION => Some(file),
+        Ok(file) => {
+            warn!(
+                "Ignoring progress in {} of unsupported version {}",
+                path.display(),
+                file.version
+            );
+            None
+        }
+        Err(error) => {
+            warn!("Ignoring corrupt progress in {}: {error}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_progress_is_kept_per_job() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("progress.json");
+        let progress = |pages: &[&str]| Progress {
+            pending: pages.iter().map(|page| page.parse().unwrap()).collect(),
+            visited: vec![],
+        };
+
+        assert_eq!(load(&path, "shop").await, Progress::default());
+        save(&path, "shop", progress(&["https://shop.example/?page=3"]))
+            .await
+            .unwrap();
+        save(&path, "blog", progress(&["https://blog.example/2"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            load(&path, "shop").await,
+            progress(&["https://shop.example/?page=3"])
+        );
+
+        save(&path, "shop", Progress::default()).await.unwrap();
+        assert_eq!(load(&path, "shop").await, Progress::default());
+        assert_eq!(
+            load(&path, "blog").await,
+            progress(&["https://blog.example/2"])
+        );
+
+        for corrupt in [r#"{ "version": 0, "jobs": {} }"#, "{ not json"] {
+            std::fs::write(&path, corrupt).unwrap();
+            assert_eq!(load(&path, "blog").await, Progress::default());
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Limits on the number of performed requests.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tokio::time::Instant;
+use tracing::debug;
+use url::Url;
+
+/// Maximal number of requests which may be performed within a time window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub struct Quota {
+    /// Number of requests allowed per window
+    pub requests: u32,
+    /// Duration of the window
+    pub window: Duration,
+}
+
+impl Quota {
+    /// Describes why the quota can never be satisfied, if it cannot.
+    pub fn problem(&self) -> Option<&'static str> {
+        if self.requests == 0 {
+            Some("allows no requests")
+        } else if self.window.is_zero() {
+            Some("has a zero window")
+        } else {
+            None
+        }
+    }
+}
+
+/// Token bucket enforcing the [quota](Quota).
+///
+/// The bucket holds up to [`Quota::requests`] tokens and is refilled continuously
+/// so that it becomes full again after [`Quota::window`].
+#[derive(Debug)]
+pub struct RequestBudget {
+    quota: Quota,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RequestBudget {
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            bucket: Mutex::new(Bucket {
+                tokens: quota.requests.into(),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token for a single request, returning `false` if the quota is exhausted.
+    pub fn try_acquire(&self) -> bool {
+        self.take().is_ok()
+    }
+
+    /// Takes a token for a single request, waiting for the bucket to be refilled if needed.
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.take() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token if there is one, otherwise returns the time left until the next one.
+    fn take(&self) -> Result<(), Duration> {
+        let Quota { requests, window } = self.quota;
+        let capacity = f64::from(requests);
+        let mut bucket = self
+            .bucket
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at);
+        bucket.tokens = if elapsed >= window {
+            capacity
+        } else {
+            (bucket.tokens + capacity * elapsed.as_secs_f64() / window.as_secs_f64()).min(capacity)
+        };
+        bucket.refilled_at = now;
+
+        if bucket.tokens < 1.0 {
+            let missing = (1.0 - bucket.tokens) / capacity;
+            return Err(
+                Duration::try_from_secs_f64(window.as_secs_f64() * missing).unwrap_or(window)
+            );
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Separate [budgets](RequestBudget) of requests to each domain.
+///
+/// Requests to the same domain share the budget only if they are limited by the same rate.
+#[derive(Debug, Default)]
+pub struct DomainRates {
+    budgets: Mutex<HashMap<(String, Quota), Arc<RequestBudget>>>,
+}
+
+impl DomainRates {
+    /// Waits until a request to the domain of the URL is allowed by the rate.
+    pub async fn acquire(&self, url: &Url, rate: Quota) {
+        let Some(domain) = url.host_str() else {
+            return;
+        };
+        let budget = self
+            .budgets
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .entry((domain.to_owned(), rate))
+            .or_insert_with(|| Arc::new(RequestBudget::new(rate)))
+            .clone();
+        if !budget.try_acquire() {
+            debug!("Waiting for the rate limit of {domain}");
+            budget.acquire().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_excess_requests_are_rejected_within_window() {
+        let budget = RequestBudget::new(Quota {
+            requests: 2,
+            window: Duration::from_secs(60),
+        });
+
+        assert!(budget.try_acquire());
+        assert!(budget.try_acquire());
+        assert!(!budget.try_acquire());
+
+        time::advance(Duration::from_secs(20)).await;
+        assert!(!budget.try_acquire());
+
+        time::advance(Duration::from_secs(10)).await;
+        assert!(budget.try_acquire());
+        assert!(!budget.try_acquire());
+
+        time::advance(Duration::from_secs(60)).await;
+        assert!(budget.try_acquire());
+        assert!(budget.try_acquire());
+        assert!(!budget.try_acquire());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_to_same_domain_wait_for_rate() {
+        let rates = DomainRates::default();
+        let rate = Quota {
+            requests: 1,
+            window: Duration::from_secs(2),
+        };
+        let url = |url| Url::parse(url).unwrap();
+
+        let start = Instant::now();
+        rates
+            .acquire(&url("https://shop.example/hoodie"), rate)
+            .await;
+        rates.acquire(&url("https://blog.example/"), rate).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        rates.acquire(&url("https://shop.example/tee"), rate).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        rates.acquire(&url("https://shop.example/cap"), rate).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,63 @@
+//! Caching of `robots.txt` files and checking URLs against them.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use robotstxt::DefaultMatcher;
+use tokio::time::Instant;
+use url::Url;
+
+/// Period after which a cached `robots.txt` file should be fetched again.
+const TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Rules of `robots.txt` files by the origins they were fetched from.
+#[derive(Debug, Default)]
+pub struct RobotsCache {
+    entries: Mutex<HashMap<String, (Instant, Arc<str>)>>,
+}
+
+impl RobotsCache {
+    /// Gets the rules of the origin unless they are missing or have expired.
+    pub fn get(&self, origin: &str) -> Option<Arc<str>> {
+        let entries = self.entries.lock().expect("lock should not be poisoned");
+        let (fetched, rules) = entries.get(origin)?;
+        (fetched.elapsed() < TTL).then(|| rules.clone())
+    }
+
+    pub fn insert(&self, origin: String, rules: Arc<str>) {
+        self.entries
+            .lock()
+            .expect("lock should not be poisoned")
+            .insert(origin, (Instant::now(), rules));
+    }
+}
+
+/// Checks whether the rules of a `robots.txt` file allow the user agent to fetch the URL.
+///
+/// The rules are matched against the product token of the user agent, e.g. `Mozilla` of `Mozilla/5.0 (X11)`.
+pub fn allows(rules: &str, user_agent: &str, url: &Url) -> bool {
+    DefaultMatcher::default().one_agent_allowed_by_robots(
+        rules,
+        product_token(user_agent),
+        url.as_str(),
+    )
+}
+
+fn product_token(user_agent: &str) -> &str {
+    let end = user_agent
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(user_agent.len());
+    &user_agent[..end]
+}
+
+/// `robots.txt` URL of the origin of the URL.
+pub fn robots_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_path("/robots.txt");
+    url.set_query(None);
+    url.set_fragment(None);
+    url
+}
@@ -0,0 +1,202 @@
+//! Turnkey extraction of SEO-relevant page metadata.
+
+use std::borrow::Cow;
+
+use indexmap::IndexMap;
+use skyscraper::xpath::{
+    grammar::{
+        data_model::{ElementNode, Node},
+        XpathItemTreeNode,
+    },
+    xpath_item_set::XpathItemSet,
+    XpathItemTree,
+};
+use url::Url;
+
+use crate::{job::Value, ProcessingResult};
+
+/// Prefix of Open Graph `<meta>` properties.
+const OPEN_GRAPH_PREFIX: &str = "og:";
+
+/// Collects canonical URL, robots directives, title, description and Open Graph tags
+/// found in the subtrees of the given items.
+///
+/// Relative canonical URLs are resolved against `base` when it is known.
+pub fn extract<'tree>(
+    tree: &'tree XpathItemTree,
+    items: XpathItemSet<'tree>,
+    base: Option<&Url>,
+) -> ProcessingResult<'tree> {
+    let mut metadata = SeoMetadata::default();
+    for item in &items {
+        if let Ok(node) = item.as_node().and_then(Node::as_tree_node) {
+            metadata.visit(tree, node.clone(), base);
+        }
+    }
+
+    metadata.into_result()
+}
+
+#[derive(Default)]
+struct SeoMetadata<'tree> {
+    canonical: Vec<Value<'tree>>,
+    robots: Vec<Value<'tree>>,
+    title: Vec<Value<'tree>>,
+    description: Vec<Value<'tree>>,
+    open_graph: IndexMap<Cow<'tree, str>, Vec<Value<'tree>>>,
+}
+
+impl<'tree> SeoMetadata<'tree> {
+    fn visit(
+        &mut self,
+        tree: &'tree XpathItemTree,
+        node: XpathItemTreeNode<'tree>,
+        base: Option<&Url>,
+    ) {
+        if let Ok(element) = node.data.as_element_node() {
+            self.visit_element(tree, &node, element, base);
+        }
+        for child in node.children(tree) {
+            self.visit(tree, child, base);
+        }
+    }
+
+    fn visit_element(
+        &mut self,
+        tree: &'tree XpathItemTree,
+        node: &XpathItemTreeNode<'tree>,
+        element: &'tree ElementNode,
+        base: Option<&Url>,
+    ) {
+        match element.name.to_ascii_lowercase().as_str() {
+            "title" => self
+                .title
+                .push(Value::String(Cow::Owned(node.all_text(tree)))),
+            "link" => {
+                let is_canonical = element.get_attribute("rel").is_some_and(|rel| {
+                    rel.split_ascii_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("canonical"))
+                });
+                if let (true, Some(href)) = (is_canonical, element.get_attribute("href")) {
+                    self.canonical.push(Value::String(resolve(href, base)));
+                }
+            }
+            "meta" => {
+                let Some(content) = element.get_attribute("content") else {
+                    return;
+                };
+                if let Some(name) = element.get_attribute("name") {
+                    if name.eq_ignore_ascii_case("robots") {
+                        self.robots.extend(
+                            content
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|directive| !directive.is_empty())
+                                .map(|directive| Value::String(Cow::Borrowed(directive))),
+                        );
+                    } else if name.eq_ignore_ascii_case("description") {
+                        self.description.push(Value::String(Cow::Borrowed(content)));
+                    }
+                }
+                if let Some(property) = element
+                    .get_attribute("property")
+                    .and_then(|property| property.strip_prefix(OPEN_GRAPH_PREFIX))
+                {
+                    self.open_graph
+                        .entry(Cow::Borrowed(property))
+                        .or_default()
+                        .push(Value::String(Cow::Borrowed(content)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn into_result(self) -> ProcessingResult<'tree> {
+        let Self {
+            canonical,
+            robots,
+            title,
+            description,
+            open_graph,
+        } = self;
+
+        ProcessingResult::Group(IndexMap::from([
+            (
+                Cow::Borrowed("canonical"),
+                ProcessingResult::Values(canonical),
+            ),
+            (Cow::Borrowed("robots"), ProcessingResult::Values(robots)),
+            (Cow::Borrowed("title"), ProcessingResult::Values(title)),
+            (
+                Cow::Borrowed("description"),
+                ProcessingResult::Values(description),
+            ),
+            (
+                Cow::Borrowed("og"),
+                ProcessingResult::Group(
+                    open_graph
+                        .into_iter()
+                        .map(|(property, values)| (property, ProcessingResult::Values(values)))
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+}
+
+pub fn resolve<'tree>(href: &'tree str, base: Option<&Url>) -> Cow<'tree, str> {
+    base.and_then(|base| base.join(href).ok())
+        .map_or(Cow::Borrowed(href), |url| Cow::Owned(url.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use skyscraper::html;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_seo_metadata() {
+        let document = html::parse(include_str!("../fixtures/seo.html")).unwrap();
+        let tree = XpathItemTree::from(&document);
+        let items = skyscraper::xpath::parse("/html/head")
+            .unwrap()
+            .apply(&tree)
+            .unwrap();
+        let base = Url::parse("https://example.com/catalog/").unwrap();
+
+        let ProcessingResult::Group(result) = extract(&tree, items, Some(&base)) else {
+            panic!("SEO metadata should be a group");
+        };
+
+        let string = |value: &'static str| Value::String(Cow::Borrowed(value));
+        assert_eq!(
+            result["canonical"],
+            ProcessingResult::Values(vec![string("https://example.com/products/42")])
+        );
+        assert_eq!(
+            result["robots"],
+            ProcessingResult::Values(vec![string("noindex"), string("follow")])
+        );
+        assert_eq!(
+            result["title"],
+            ProcessingResult::Values(vec![string("Loom Hoodie")])
+        );
+        assert_eq!(
+            result["description"],
+            ProcessingResult::Values(vec![string("A warm hoodie for Java developers")])
+        );
+        let ProcessingResult::Group(open_graph) = &result["og"] else {
+            panic!("Open Graph tags should be a group");
+        };
+        assert_eq!(
+            open_graph["title"],
+            ProcessingResult::Values(vec![string("Loom Hoodie")])
+        );
+        assert_eq!(
+            open_graph["type"],
+            ProcessingResult::Values(vec![string("product")])
+        );
+    }
+}
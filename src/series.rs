@@ -0,0 +1,86 @@
+//! Series of the numbers of tracked targets, persisted so that they survive a restart.
+
+use std::{collections::BTreeMap, io, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, sync::Mutex};
+use tracing::{debug, warn};
+
+/// Version of the format of series files, which are ignored if they have another one.
+const VERSION: u32 = 1;
+
+/// Guards reading and rewriting of series files, which may be shared by several jobs.
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Points of each tracked target by its name, oldest first.
+pub type Series = BTreeMap<String, Vec<Point>>;
+
+/// Number found by a poll of a job along with the time it has been found.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub time: DateTime<Utc>,
+    pub value: f64,
+}
+
+/// Contents of a series file.
+#[derive(Debug, Serialize, Deserialize)]
+struct SeriesFile {
+    version: u32,
+    /// Series of each job by its name
+    jobs: BTreeMap<String, Series>,
+}
+
+/// Loads the series of the job from the file, which are empty if there are none.
+///
+/// Missing, corrupt and outdated files are treated as having no series.
+pub async fn load(path: &Path, job: &str) -> Series {
+    let _guard = LOCK.lock().await;
+    read(path)
+        .await
+        .and_then(|mut file| file.jobs.remove(job))
+        .unwrap_or_default()
+}
+
+/// Saves the series of the job into the file, replacing the previously saved ones.
+pub async fn save(path: &Path, job: &str, series: Series) -> io::Result<()> {
+    let _guard = LOCK.lock().await;
+    let mut file = read(path).await.unwrap_or_else(|| SeriesFile {
+        version: VERSION,
+        jobs: BTreeMap::new(),
+    });
+    file.jobs.insert(job.to_owned(), series);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let contents = serde_json::to_vec(&file).expect("series should be serializable");
+    fs::write(path, contents).await?;
+    debug!("Saved series to {}", path.display());
+    Ok(())
+}
+
+async fn read(path: &Path) -> Option<SeriesFile> {
+    let contents = match fs::read(path).await {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+        Err(error) => {
+            warn!("Failed to read series from {}: {error}", path.display());
+            return None;
+        }
+    };
+    match serde_json::from_slice::<SeriesFile>(&contents) {
+        Ok(file) if file.version == VERSION => Some(file),
+        Ok(file) => {
+            warn!(
+                "Ignoring series in {} of unsupported version {}",
+                path.display(),
+                file.version
+            );
+            None
+        }
+        Err(error) => {
+            warn!("Ignoring corrupt series in {}: {error}", path.display());
+            None
+        }
+    }
+}
@@ -0,0 +1,943 @@
+//! Destinations to which results of polls are written.
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::{borrow::Cow, io, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use indexmap::IndexMap;
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinHandle,
+};
+use tracing::{debug, error, warn, Instrument, Span};
+use url::Url;
+
+use crate::{
+    diff::Change,
+    job::{Job, Retry, StaticHeaders, Value, WhenFull},
+    ProcessingResult, CHANGE_COUNT_KEY, HEADERS_KEY,
+};
+
+/// Destination to which results of each poll are written.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Sink {
+    /// JSON document written on each poll into the file at the templated path.
+    Json {
+        path: PathTemplate,
+        /// Separator of keys of the [flattened](ProcessingResult::flatten) results written instead of nested ones
+        #[serde(default)]
+        flatten: Option<String>,
+        /// Format in which the document is serialized
+        #[serde(default)]
+        format: Format,
+    },
+    /// CSV table written on each poll into the file at the templated path.
+    ///
+    /// The result has to be flat enough to map to columns: each record, i.e. a group of values only,
+    /// becomes a row, while groups of records are flattened in order.
+    /// All records have to share the same keys, which form the header.
+    Csv {
+        path: PathTemplate,
+        /// Whether rows should be appended to the existing file instead of overwriting it
+        #[serde(default)]
+        append: bool,
+    },
+    /// JSON document sent on each poll to the URL in a `POST` request.
+    Webhook {
+        url: Url,
+        /// Request headers sent in addition to `Content-Type` of the format
+        #[serde(default)]
+        headers: StaticHeaders,
+        /// Template of the request body, the JSON document itself is sent by default
+        #[serde(default)]
+        body: Option<BodyTemplate>,
+        /// Format in which the document is serialized unless the body is templated
+        #[serde(default)]
+        format: Format,
+        /// Retrying of requests failed due to network or server errors
+        #[serde(default)]
+        retry: Option<Retry>,
+    },
+    /// Rows appended on each poll to the tables of the SQLite database at the templated path.
+    ///
+    /// See [`sqlite`] for the schema.
+    #[cfg(feature = "sqlite")]
+    Sqlite { path: PathTemplate },
+}
+
+/// Serialization format of documents written by [`Json`](Sink::Json) and [`Webhook`](Sink::Webhook) sinks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Format {
+    #[default]
+    Json,
+    Yaml,
+    MsgPack,
+    /// JSON document per line, which is each element of arrays such as changes and the whole document otherwise
+    Ndjson,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Yaml => "application/yaml",
+            Self::MsgPack => "application/msgpack",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// Serializes the document, pretty-printing text formats except NDJSON.
+    pub fn serialize(self, document: &impl Serialize) -> Result<Vec<u8>, SinkError> {
+        Ok(match self {
+            Self::Json => serde_json::to_vec_pretty(document)?,
+            Self::Yaml => serde_yaml::to_string(document)?.into_bytes(),
+            Self::MsgPack => rmp_serde::to_vec_named(document)?,
+            Self::Ndjson => {
+                let lines = match serde_json::to_value(document)? {
+                    serde_json::Value::Array(items) => items,
+                    document => vec![document],
+                };
+                let mut text = Vec::new();
+                for line in lines {
+                    serde_json::to_writer(&mut text, &line)?;
+                    text.push(b'\n');
+                }
+                text
+            }
+        })
+    }
+}
+
+/// What is written into sinks after a poll.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(untagged)]
+pub enum Event<'a, 'tree> {
+    /// Results of the poll
+    Polled(&'a ProcessingResult<'tree>),
+    /// Changes of the results since the previous poll
+    Changed(&'a [Change]),
+}
+
+/// [Event] detached from the documents, so that it can be queued for a [writer](SinkWriter).
+#[derive(Debug)]
+pub enum OwnedEvent {
+    Polled(ProcessingResult<'static>),
+    Changed(Vec<Change>),
+}
+
+impl OwnedEvent {
+    pub fn as_event(&self) -> Event<'_, 'static> {
+        match self {
+            Self::Polled(result) => Event::Polled(result),
+            Self::Changed(changes) => Event::Changed(changes),
+        }
+    }
+}
+
+/// Task writing events of a job into its sinks, fed by a bounded queue
+/// so that fetching goes on while the sinks are written.
+#[derive(Debug)]
+pub struct SinkWriter {
+    sender: mpsc::Sender<OwnedEvent>,
+    when_full: WhenFull,
+    task: JoinHandle<()>,
+}
+
+impl SinkWriter {
+    /// Spawns the writer into the sinks of the job, which logs within the current span.
+    pub fn spawn(client: reqwest::Client, job: &Job) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<OwnedEvent>(job.sink_queue.capacity.get());
+        let name = job.name().to_owned();
+        let sinks = job.sinks.clone();
+        let task = tokio::spawn(
+            async move {
+                while let Some(event) = receiver.recv().await {
+                    write_all(&client, &name, &sinks, event.as_event()).await;
+                }
+            }
+            .instrument(Span::current()),
+        );
+        Self {
+            sender,
+            when_full: job.sink_queue.when_full,
+            task,
+        }
+    }
+
+    /// Queues the event, waiting for a free slot or dropping it if the queue is full as configured.
+    pub async fn send(&self, event: OwnedEvent) {
+        let event = match self.sender.try_send(event) {
+            Ok(()) => return,
+            Err(TrySendError::Full(event)) => event,
+            Err(TrySendError::Closed(_)) => {
+                error!("Sink writer has stopped, dropping results");
+                return;
+            }
+        };
+        match self.when_full {
+            WhenFull::Wait => {
+                debug!("Sink queue is full, waiting for sinks");
+                if self.sender.send(event).await.is_err() {
+                    error!("Sink writer has stopped, dropping results");
+                }
+            }
+            WhenFull::Drop => warn!("Sink queue is full, dropping results"),
+        }
+    }
+
+    /// Waits until all queued events are written.
+    pub async fn close(self) {
+        drop(self.sender);
+        if let Err(error) = self.task.await {
+            error!("Sink writer failed: {error}");
+        }
+    }
+}
+
+/// Writes the event into the sinks concurrently so that a slow one does not delay the others,
+/// while their errors are logged in the order of the sinks.
+async fn write_all(client: &reqwest::Client, job: &str, sinks: &[Sink], event: Event<'_, '_>) {
+    let writes = sinks.iter().map(|sink| sink.write(client, job, event));
+    for (index, (sink, result)) in sinks.iter().zip(join_all(writes).await).enumerate() {
+        if let Err(error) = result {
+            error!(
+                "Failed to write results into sink #{index} ({}): {error}",
+                sink.kind()
+            );
+        }
+    }
+}
+
+/// An error which may occur while writing results into a [sink](Sink).
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("failed to serialize results")]
+    Serialize(#[from] serde_json::Error),
+    #[error("failed to serialize results as YAML")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("failed to serialize results as MessagePack")]
+    MsgPack(#[from] rmp_serde::encode::Error),
+    #[error("failed to write CSV")]
+    Csv(#[from] csv::Error),
+    #[error("results cannot be written as a table: {0}")]
+    NotFlat(&'static str),
+    #[error("failed to send webhook request")]
+    Send(#[from] reqwest::Error),
+    #[error("webhook responded with {0}")]
+    Status(StatusCode),
+    #[cfg(feature = "sqlite")]
+    #[error("failed to write into SQLite database")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl Sink {
+    /// Name of the kind of the sink as it is configured.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Json { .. } => "Json",
+            Self::Csv { .. } => "Csv",
+            Self::Webhook { .. } => "Webhook",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite { .. } => "Sqlite",
+        }
+    }
+
+    /// Writes the event, performing requests with the client if needed.
+    pub async fn write(
+        &self,
+        client: &reqwest::Client,
+        job: &str,
+        event: Event<'_, '_>,
+    ) -> Result<(), SinkError> {
+        match self {
+            Self::Json {
+                path,
+                flatten,
+                format,
+            } => {
+                let document = match (event, flatten) {
+                    (Event::Polled(result), Some(separator)) => {
+                        format.serialize(&result.flatten(separator))?
+                    }
+                    _ => format.serialize(&event)?,
+                };
+                let path = path.render(job, Utc::now());
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(&path, document).await?;
+                debug!("Written results into {}", path.display());
+            }
+            Self::Csv { path, append } => {
+                let (header, rows) = match event {
+                    Event::Polled(result) => result_table(result)?,
+                    Event::Changed(changes) => changes_table(changes),
+                };
+                if rows.is_empty() {
+                    debug!("No records to write");
+                    return Ok(());
+                }
+
+                let path = path.render(job, Utc::now());
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                let mut file = fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(*append)
+                    .truncate(!*append)
+                    .open(&path)
+                    .await?;
+
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                if file.metadata().await?.len() == 0 {
+                    writer.write_record(&header)?;
+                }
+                for row in &rows {
+                    writer.write_record(row)?;
+                }
+                let table = writer.into_inner().map_err(|error| error.into_error())?;
+                file.write_all(&table).await?;
+                file.flush().await?;
+                debug!("Written {} records into {}", rows.len(), path.display());
+            }
+            Self::Webhook {
+                url,
+                headers,
+                body,
+                retry,
+                format,
+            } => {
+                // Templates are filled with JSON regardless of the format.
+                let (body, content_type) = match (body, format) {
+                    (Some(template), _) => (
+                        template
+                            .render(job, &serde_json::to_value(event)?)?
+                            .into_bytes(),
+                        Format::Json.content_type(),
+                    ),
+                    (None, Format::Json) => (serde_json::to_vec(&event)?, format.content_type()),
+                    (None, format) => (format.serialize(&event)?, format.content_type()),
+                };
+                let mut delays = retry.iter().flat_map(Retry::delays);
+                loop {
+                    let mut request = client
+                        .post(url.clone())
+                        .header(CONTENT_TYPE, content_type)
+                        .body(body.clone());
+                    for (name, value) in &headers.0 {
+                        request = request.header(name, value);
+                    }
+                    let result = match request.send().await {
+                        Ok(response) if response.status().is_success() => Ok(response.status()),
+                        Ok(response) => Err(SinkError::Status(response.status())),
+                        Err(error) => Err(error.into()),
+                    };
+                    let retryable = match &result {
+                        Ok(_) => false,
+                        Err(SinkError::Status(status)) => status.is_server_error(),
+                        Err(_) => true,
+                    };
+                    match delays.next() {
+                        Some(delay) if retryable => {
+                            warn!("Webhook request to {url} failed, retrying in {delay:?}");
+                            tokio::time::sleep(delay).await;
+                        }
+                        _ => {
+                            let status = result?;
+                            debug!("Webhook {url} responded with {status}");
+                            break;
+                        }
+                    }
+                }
+            }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite { path } => {
+                let now = Utc::now();
+                let path = path.render(job, now);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                let rows = sqlite::Rows::of(event);
+                let job = job.to_owned();
+                tokio::task::spawn_blocking(move || {
+                    rows.write(&path, &job, now)?;
+                    debug!("Written results into {}", path.display());
+                    Ok::<_, SinkError>(())
+                })
+                .await
+                .map_err(io::Error::from)??;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Header and rows of a CSV table.
+type Table = (Vec<String>, Vec<Vec<String>>);
+
+/// Lays out the records of the result as a table whose header consists of the keys of the records.
+fn result_table(result: &ProcessingResult) -> Result<Table, SinkError> {
+    let mut records = Vec::new();
+    match result {
+        ProcessingResult::Group(group)
+            if group.contains_key(HEADERS_KEY) || group.contains_key(CHANGE_COUNT_KEY) =>
+        {
+            for (name, result) in group {
+                if name != HEADERS_KEY && name != CHANGE_COUNT_KEY {
+                    collect_records(result, &mut records)?;
+                }
+            }
+        }
+        result => collect_records(result, &mut records)?,
+    }
+    let Some(header) = records.first().map(|record| record.keys()) else {
+        return Ok(Default::default());
+    };
+    if records
+        .iter()
+        .any(|record| !record.keys().eq(header.clone()))
+    {
+        return Err(SinkError::NotFlat("records have different keys"));
+    }
+
+    Ok((
+        header.map(|key| key.to_string()).collect(),
+        records
+            .iter()
+            .map(|record| record.values().map(cell).collect())
+            .collect(),
+    ))
+}
+
+/// Lays out the changes as a table with a row per change.
+fn changes_table(changes: &[Change]) -> Table {
+    let json = |value: &serde_json::Value| match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+    let header = ["change", "path", "old", "new"].map(String::from).into();
+    let rows = changes
+        .iter()
+        .map(|change| match change {
+            Change::Added { path, value } => {
+                vec!["added".into(), path.clone(), String::new(), json(value)]
+            }
+            Change::Removed { path, value } => {
+                vec!["removed".into(), path.clone(), json(value), String::new()]
+            }
+            Change::Changed { path, old, new } => {
+                vec!["changed".into(), path.clone(), json(old), json(new)]
+            }
+        })
+        .collect();
+    (header, rows)
+}
+
+type Record<'a, 'tree> = &'a IndexMap<Cow<'tree, str>, ProcessingResult<'tree>>;
+
+/// Collects groups consisting of values only, descending into groups consisting of groups only.
+fn collect_records<'a, 'tree>(
+    result: &'a ProcessingResult<'tree>,
+    records: &mut Vec<Record<'a, 'tree>>,
+) -> Result<(), SinkError> {
+    let ProcessingResult::Group(group) = result else {
+        return Err(SinkError::NotFlat("records have to be groups"));
+    };
+    if group
+        .values()
+        .all(|result| matches!(result, ProcessingResult::Values(_)))
+    {
+        records.push(group);
+        return Ok(());
+    }
+    for result in group.values() {
+        if !matches!(result, ProcessingResult::Group(_)) {
+            return Err(SinkError::NotFlat("groups mix values with nested groups"));
+        }
+        collect_records(result, records)?;
+    }
+    Ok(())
+}
+
+/// Formats the values of a record as a single cell, joining multiple values with `; `.
+fn cell(result: &ProcessingResult) -> String {
+    let ProcessingResult::Values(values) = result else {
+        unreachable!("records consist of values only");
+    };
+    values
+        .iter()
+        .map(|value| match value {
+            Value::Unknown => String::new(),
+            value => value.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Path whose placeholders are substituted each time results are written.
+///
+/// Supported placeholders are:
+/// - `{job}`: [name](crate::job::Job::name) of the job, with `/`, `\` and `:` replaced by `_`
+///   since the default names are URLs;
+/// - `{timestamp}`: current UNIX timestamp in seconds;
+/// - `{date}`: current UTC date formatted as `YYYY-MM-DD`;
+/// - `{hour}`: current UTC hour formatted as `HH`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct PathTemplate(String);
+
+impl PathTemplate {
+    pub fn render(&self, job: &str, now: DateTime<Utc>) -> PathBuf {
+        self.0
+            .replace("{job}", &job.replace(['/', '\\', ':'], "_"))
+            .replace("{timestamp}", &now.timestamp().to_string())
+            .replace("{date}", &now.format("%Y-%m-%d").to_string())
+            .replace("{hour}", &now.format("%H").to_string())
+            .into()
+    }
+}
+
+/// Template of a webhook request body.
+///
+/// Supported placeholders are:
+/// - `{job}`: [name](crate::job::Job::name) of the job, escaped to be used inside a JSON string;
+/// - `{results}`: the JSON document;
+/// - `{results_text}`: the JSON document as a JSON string, e.g. to be used as a message text.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct BodyTemplate(String);
+
+impl BodyTemplate {
+    /// Substitutes the placeholders in a single pass,
+    /// so that placeholders in the substituted values, such as the scraped ones, are kept as is.
+    pub fn render(&self, job: &str, document: &serde_json::Value) -> serde_json::Result<String> {
+        let mut body = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+        while let Some(start) = rest.find('{') {
+            body.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(after) = rest.strip_prefix("{job}") {
+                let quoted = serde_json::to_string(job)?;
+                body.push_str(&quoted[1..quoted.len() - 1]);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{results_text}") {
+                let text = serde_json::to_string_pretty(document)?;
+                body.push_str(&serde_json::to_string(&text)?);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{results}") {
+                body.push_str(&document.to_string());
+                rest = after;
+            } else {
+                body.push('{');
+                rest = &rest[1..];
+            }
+        }
+        body.push_str(rest);
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_csv_sink_writes_flat_records() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("prices.csv");
+        let product = |name, price: Value<'static>| {
+            ProcessingResult::Group(IndexMap::from([
+                (
+                    Cow::Borrowed("name"),
+                    ProcessingResult::Values(vec![Value::String(name)]),
+                ),
+                (
+                    Cow::Borrowed("price"),
+                    ProcessingResult::Values(vec![price]),
+                ),
+            ]))
+        };
+        let result = ProcessingResult::Group(IndexMap::from([(
+            Cow::Borrowed("[0]"),
+            ProcessingResult::Group(IndexMap::from([(
+                Cow::Borrowed("products"),
+                ProcessingResult::Group(IndexMap::from([
+                    (
+                        Cow::Borrowed("[0]"),
+                        product("Loom Hoodie".into(), Value::String("49".into())),
+                    ),
+                    (
+                        Cow::Borrowed("[1]"),
+                        product("Mystery Box, large".into(), Value::Unknown),
+                    ),
+                ])),
+            )])),
+        )]));
+
+        let sink = |append| Sink::Csv {
+            path: PathTemplate(path.to_str().unwrap().to_owned()),
+            append,
+        };
+        sink(false)
+            .write(&reqwest::Client::new(), "shop", Event::Polled(&result))
+            .await
+            .unwrap();
+        sink(true)
+            .write(&reqwest::Client::new(), "shop", Event::Polled(&result))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "name,price\n\
+             Loom Hoodie,49\n\
+             \"Mystery Box, large\",\n\
+             Loom Hoodie,49\n\
+             \"Mystery Box, large\",\n"
+        );
+        sink(false)
+            .write(&reqwest::Client::new(), "shop", Event::Polled(&result))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        let ProcessingResult::Group(mut mixed) = result else {
+            unreachable!();
+        };
+        mixed.insert(
+            Cow::Borrowed("title"),
+            ProcessingResult::Values(vec![Value::String("Shop".into())]),
+        );
+        assert!(matches!(
+            sink(false)
+                .write(
+                    &reqwest::Client::new(),
+                    "shop",
+                    Event::Polled(&ProcessingResult::Group(mixed))
+                )
+                .await,
+            Err(SinkError::NotFlat(_))
+        ));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_sink_keeps_history_of_values() {
+        use rusqlite::types::Value as SqlValue;
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("history/{job}.sqlite");
+        let sink = Sink::Sqlite {
+            path: PathTemplate(path.to_str().unwrap().to_owned()),
+        };
+        let result = |price| {
+            ProcessingResult::Group(IndexMap::from([(
+                Cow::Borrowed("products"),
+                ProcessingResult::Group(IndexMap::from([(
+                    Cow::Borrowed("LH-1"),
+                    ProcessingResult::Group(IndexMap::from([
+                        (
+                            Cow::Borrowed("name"),
+                            ProcessingResult::Values(vec![Value::String("Loom Hoodie".into())]),
+                        ),
+                        (
+                            Cow::Borrowed("price"),
+                            ProcessingResult::Values(vec![Value::Number(price), Value::Unknown]),
+                        ),
+                    ])),
+                )])),
+            )]))
+        };
+        for price in [49.0, 45.5] {
+            sink.write(
+                &reqwest::Client::new(),
+                "shop",
+                Event::Polled(&result(price)),
+            )
+            .await
+            .unwrap();
+        }
+        let changes = [Change::Changed {
+            path: "/products/LH-1/price/0".to_owned(),
+            old: json!(49.0),
+            new: json!(45.5),
+        }];
+        sink.write(&reqwest::Client::new(), "shop", Event::Changed(&changes))
+            .await
+            .unwrap();
+
+        let connection =
+            rusqlite::Connection::open(directory.path().join("history/shop.sqlite")).unwrap();
+        let prices: Vec<(String, i64, SqlValue)> = connection
+            .prepare("SELECT job, position, value FROM results WHERE path = '/products/LH-1/price' ORDER BY rowid")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            prices,
+            [
+                ("shop".to_owned(), 0, SqlValue::Real(49.0)),
+                ("shop".to_owned(), 1, SqlValue::Null),
+                ("shop".to_owned(), 0, SqlValue::Real(45.5)),
+                ("shop".to_owned(), 1, SqlValue::Null),
+            ]
+        );
+        let names: i64 = connection
+            .query_row(
+                "SELECT COUNT(*) FROM results WHERE path = '/products/LH-1/name' AND value = 'Loom Hoodie'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(names, 2);
+        let change: (String, SqlValue, SqlValue) = connection
+            .query_row("SELECT change, old, new FROM changes", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(
+            change,
+            (
+                "changed".to_owned(),
+                SqlValue::Real(49.0),
+                SqlValue::Real(45.5)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_sink_writes_templated_path() {
+        let directory = tempfile::tempdir().unwrap();
+        let template = format!(
+            "{}/{{job}}/{{date}}-{{hour}}.json",
+            directory.path().display()
+        );
+        let now = DateTime::from_timestamp(1_717_243_200, 0).unwrap();
+        assert_eq!(
+            PathTemplate(template.clone()).render("shop", now),
+            directory.path().join("shop/2024-06-01-12.json")
+        );
+        assert_eq!(
+            PathTemplate(template.clone()).render("https://shop.example/tees", now),
+            directory
+                .path()
+                .join("https___shop.example_tees/2024-06-01-12.json")
+        );
+
+        let result = ProcessingResult::Group(IndexMap::from([
+            (
+                Cow::Borrowed("name"),
+                ProcessingResult::Values(vec![Value::String("Loom Hoodie".into())]),
+            ),
+            (
+                Cow::Borrowed("price"),
+                ProcessingResult::Values(vec![Value::Unknown]),
+            ),
+            (
+                Cow::Borrowed("offers"),
+                ProcessingResult::Values(vec![Value::Json(json!({ "price": 49 }))]),
+            ),
+        ]));
+        let sink = Sink::Json {
+            path: PathTemplate(template),
+            flatten: None,
+            format: Format::Json,
+        };
+        sink.write(&reqwest::Client::new(), "shop", Event::Polled(&result))
+            .await
+            .unwrap();
+
+        let written: Vec<_> = std::fs::read_dir(directory.path().join("shop"))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(written.len(), 1);
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(written[0].path()).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            json!({
+                "name": ["Loom Hoodie"],
+                "price": [null],
+                "offers": [{ "price": 49 }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_results_round_trip_through_each_format() {
+        let directory = tempfile::tempdir().unwrap();
+        let result = ProcessingResult::Group(IndexMap::from([(
+            Cow::Borrowed("[0]"),
+            ProcessingResult::Group(IndexMap::from([
+                (
+                    Cow::Borrowed("name"),
+                    ProcessingResult::Values(vec![Value::String("Loom Hoodie".into())]),
+                ),
+                (
+                    Cow::Borrowed("price"),
+                    ProcessingResult::Values(vec![Value::Number(49.5), Value::Unknown]),
+                ),
+            ])),
+        )]));
+        let changes = [
+            Change::Changed {
+                path: "/[0]/price/0".to_owned(),
+                old: json!(49.5),
+                new: json!(39.0),
+            },
+            Change::Removed {
+                path: "/[0]/name/0".to_owned(),
+                value: json!("Loom Hoodie"),
+            },
+        ];
+
+        for format in [Format::Json, Format::Yaml, Format::MsgPack, Format::Ndjson] {
+            for event in [Event::Polled(&result), Event::Changed(&changes)] {
+                let path = directory.path().join(format!("{format:?}.out"));
+                let sink = Sink::Json {
+                    path: PathTemplate(path.to_str().unwrap().to_owned()),
+                    flatten: None,
+                    format,
+                };
+                sink.write(&reqwest::Client::new(), "shop", event)
+                    .await
+                    .unwrap();
+
+                let written = std::fs::read(&path).unwrap();
+                let decoded: serde_json::Value = match format {
+                    Format::Json => serde_json::from_slice(&written).unwrap(),
+                    Format::Yaml => serde_yaml::from_slice(&written).unwrap(),
+                    Format::MsgPack => rmp_serde::from_slice(&written).unwrap(),
+                    Format::Ndjson => {
+                        let lines: Vec<serde_json::Value> = std::str::from_utf8(&written)
+                            .unwrap()
+                            .lines()
+                            .map(|line| serde_json::from_str(line).unwrap())
+                            .collect();
+                        match event {
+                            Event::Polled(_) => lines.into_iter().next().unwrap(),
+                            Event::Changed(_) => lines.into(),
+                        }
+                    }
+                };
+                assert_eq!(decoded, serde_json::to_value(event).unwrap(), "{format:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_csv_sink_writes_changes() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("changes.csv");
+        let changes = [
+            Change::Changed {
+                path: "/[0]/price/0".to_owned(),
+                old: json!("49"),
+                new: json!("39"),
+            },
+            Change::Added {
+                path: "/[1]".to_owned(),
+                value: json!({ "name": ["Panama Tee"] }),
+            },
+        ];
+
+        let sink = Sink::Csv {
+            path: PathTemplate(path.to_str().unwrap().to_owned()),
+            append: false,
+        };
+        sink.write(&reqwest::Client::new(), "shop", Event::Changed(&changes))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "change,path,old,new\n\
+             changed,/[0]/price/0,49,39\n\
+             added,/[1],,\"{\"\"name\"\":[\"\"Panama Tee\"\"]}\"\n"
+        );
+    }
+
+    #[test]
+    fn test_body_template_does_not_substitute_inserted_values() {
+        let template = BodyTemplate(
+            r#"{"text": "{job} changed", "details": {results_text}, "raw": {results}}"#.to_owned(),
+        );
+        let document = json!({ "name": ["{results} and {job}"] });
+
+        let body = template
+            .render(r#"https://shop.example/"tees""#, &document)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({
+                "text": r#"https://shop.example/"tees" changed"#,
+                "details": serde_json::to_string_pretty(&document).unwrap(),
+                "raw": document,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_sink_retries_templated_request() {
+        use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(matchers::method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(matchers::method("POST"))
+            .and(matchers::header("content-type", "application/json"))
+            .and(matchers::header("x-channel", "prices"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let sink: Sink = serde_json::from_value(json!({
+            "Webhook": {
+                "url": server.uri(),
+                "headers": { "X-Channel": "prices" },
+                "body": r#"{"text": "{job} changed", "details": {results_text}, "raw": {results}}"#,
+                "retry": { "attempts": 2, "delay": { "secs": 0, "nanos": 1000000 } },
+            }
+        }))
+        .unwrap();
+
+        let changes = [Change::Removed {
+            path: "/[1]".to_owned(),
+            value: json!("Panama Tee"),
+        }];
+        sink.write(&reqwest::Client::new(), "shop", Event::Changed(&changes))
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        let raw = json!([{ "change": "removed", "path": "/[1]", "value": "Panama Tee" }]);
+        assert_eq!(
+            body,
+            json!({
+                "text": "shop changed",
+                "details": serde_json::to_string_pretty(&raw).unwrap(),
+                "raw": raw,
+            })
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! History of results kept in an SQLite database by the [SQLite sink](super::Sink::Sqlite).
+//!
+//! All jobs share the generic schema, so that the history of a single value is selected by its path:
+//!
+//! ```sql
+//! SELECT polled_at, value FROM results WHERE job = 'shop' AND path = '/products/LH-1/price';
+//! ```
+
+use std::path::Path;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, types::Value as SqlValue, Connection};
+
+use crate::{diff::Change, job::Value, ProcessingResult};
+
+use super::Event;
+
+/// Tables of the history, created unless they exist.
+///
+/// Columns of values have no type so that strings, numbers and `NULL` for unknown values are kept as such.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS results (
+    job TEXT NOT NULL,
+    polled_at TEXT NOT NULL,
+    path TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    value
+);
+CREATE INDEX IF NOT EXISTS results_by_path ON results (job, path, polled_at);
+CREATE TABLE IF NOT EXISTS changes (
+    job TEXT NOT NULL,
+    polled_at TEXT NOT NULL,
+    change TEXT NOT NULL,
+    path TEXT NOT NULL,
+    old,
+    new
+);
+";
+
+/// Rows of a single event, detached from the results so that they can be written on a blocking thread.
+#[derive(Debug, PartialEq)]
+pub enum Rows {
+    /// JSON Pointer of each target, position of each of its values and the value itself
+    Results(Vec<(String, usize, SqlValue)>),
+    /// Kind, path, old and new values of each change
+    Changes(Vec<(&'static str, String, SqlValue, SqlValue)>),
+}
+
+impl Rows {
+    /// Maps the results to a row per value of each target, omitting errors.
+    pub fn of(event: Event<'_, '_>) -> Self {
+        match event {
+            Event::Polled(result) => {
+                let mut rows = Vec::new();
+                collect(result, &mut String::new(), &mut rows);
+                Self::Results(rows)
+            }
+            Event::Changed(changes) => Self::Changes(
+                changes
+                    .iter()
+                    .map(|change| match change {
+                        Change::Added { path, value } => {
+                            ("added", path.clone(), SqlValue::Null, json(value))
+                        }
+                        Change::Removed { path, value } => {
+                            ("removed", path.clone(), json(value), SqlValue::Null)
+                        }
+                        Change::Changed { path, old, new } => {
+                            ("changed", path.clone(), json(old), json(new))
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Inserts the rows into the database at the path in a single transaction, creating it if needed.
+    pub fn write(&self, path: &Path, job: &str, polled_at: DateTime<Utc>) -> rusqlite::Result<()> {
+        let mut connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        let polled_at = polled_at.to_rfc3339_opts(SecondsFormat::Millis, true);
+        let transaction = connection.transaction()?;
+        match self {
+            Self::Results(rows) => {
+                let mut insert = transaction.prepare(
+                    "INSERT INTO results (job, polled_at, path, position, value) VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?;
+                for (path, position, value) in rows {
+                    insert.execute(params![job, polled_at, path, position, value])?;
+                }
+            }
+            Self::Changes(rows) => {
+                let mut insert = transaction.prepare(
+                    "INSERT INTO changes (job, polled_at, change, path, old, new) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?;
+                for (change, path, old, new) in rows {
+                    insert.execute(params![job, polled_at, change, path, old, new])?;
+                }
+            }
+        }
+        transaction.commit()
+    }
+}
+
+fn collect(
+    result: &ProcessingResult,
+    path: &mut String,
+    rows: &mut Vec<(String, usize, SqlValue)>,
+) {
+    match result {
+        ProcessingResult::Group(group) => {
+            for (name, result) in group {
+                let length = path.len();
+                path.push('/');
+                path.push_str(&name.replace('~', "~0").replace('/', "~1"));
+                collect(result, path, rows);
+                path.truncate(length);
+            }
+        }
+        ProcessingResult::Values(values) => {
+            rows.extend(values.iter().enumerate().map(|(position, value)| {
+                let value = match value {
+                    Value::Unknown => SqlValue::Null,
+                    Value::String(value) => SqlValue::Text(value.to_string()),
+                    Value::Number(value) => SqlValue::Real(*value),
+                    Value::Bool(value) => SqlValue::Integer(i64::from(*value)),
+                    Value::Json(value) => json(value),
+                };
+                (path.clone(), position, value)
+            }));
+        }
+        ProcessingResult::Error(_) => {}
+    }
+}
+
+/// Keeps scalars as SQLite values and serializes arrays and objects as JSON text.
+fn json(value: &serde_json::Value) -> SqlValue {
+    match value {
+        serde_json::Value::Null => SqlValue::Null,
+        serde_json::Value::Bool(value) => SqlValue::Integer(i64::from(*value)),
+        serde_json::Value::Number(number) => number
+            .as_i64()
+            .map(SqlValue::Integer)
+            .or_else(|| number.as_f64().map(SqlValue::Real))
+            .unwrap_or_else(|| SqlValue::Text(number.to_string())),
+        serde_json::Value::String(value) => SqlValue::Text(value.clone()),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
@@ -0,0 +1,48 @@
+//! Fixtures shared by tests of different modules.
+
+use std::fmt::Display;
+
+use config::{Config, ConfigError};
+use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+use crate::job::Job;
+
+/// Parses a job polling a local file with the given fields set additionally,
+/// overriding the defaults if they are repeated.
+pub fn job(fields: &str) -> Job {
+    try_job(fields).expect("job should be valid")
+}
+
+/// Parses a job like [`job`] without requiring it to be valid.
+pub fn try_job(fields: &str) -> Result<Job, ConfigError> {
+    Config::builder()
+        .add_source(config::File::from_str(
+            &format!(
+                r#"{{
+                    resource: {{ path: "fixtures/products.html" }},
+                    period: {{ secs: 1, nanos: 0 }},
+                    targets: {{}},
+                    continuation: {{ ref: "//a/@href" }},
+                    {fields}
+                }}"#
+            ),
+            config::FileFormat::Json5,
+        ))
+        .build()
+        .and_then(Config::try_deserialize)
+}
+
+/// Parses a job polling the URL with the given fields set additionally.
+pub fn url_job(url: impl Display, fields: &str) -> Job {
+    job(&format!(r#"resource: {{ url: "{url}" }}, {fields}"#))
+}
+
+/// Starts a server responding to every request with the response.
+pub async fn serve(response: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(matchers::any())
+        .respond_with(response)
+        .mount(&server)
+        .await;
+    server
+}